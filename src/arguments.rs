@@ -1,17 +1,58 @@
 use chrono::Utc;
 use clap::Parser;
 use std::io::{Write, ErrorKind};
-use std::net::{SocketAddr, Ipv4Addr, IpAddr, TcpStream};
+use std::collections::{HashMap, HashSet};
+use std::net::{SocketAddr, IpAddr, ToSocketAddrs};
 use std::path::Path;
 use std::process::exit;
+use std::sync::Arc;
 use std::time::Duration;
 use console::style;
 use std::{thread, env, fs::{OpenOptions, File}};
-use crossbeam::channel::{unbounded, Sender, RecvTimeoutError};
+use pnet_datalink::MacAddr;
+use crossbeam::channel::{unbounded, Receiver};
 
 mod services;
+mod targets;
+mod ports;
+mod top_ports;
+mod syn_scan;
+mod udp_scan;
+mod engine;
+mod rate_limit;
+mod rtt;
+mod discovery;
+mod arp;
+mod oui;
+mod interfaces;
+mod banner;
+mod http_fingerprint;
+mod protocols;
+mod starttls;
+mod service_probes;
+mod tls;
+mod tls_audit;
 pub mod arg_definitions;
 use services::*;
+use targets::*;
+use ports::*;
+use top_ports::*;
+use syn_scan::*;
+use udp_scan::*;
+use engine::*;
+use rate_limit::*;
+use rtt::*;
+use discovery::*;
+use arp::*;
+use oui::*;
+use interfaces::*;
+use banner::*;
+use http_fingerprint::*;
+use protocols::*;
+use starttls::*;
+use service_probes::*;
+use tls::*;
+use tls_audit::*;
 use arg_definitions::*;
 use arg_definitions::fmt;
 
@@ -19,18 +60,96 @@ const AUTHOR: &str = "liquidlegs";
 const VERSION: &str = "0.1.0";
 const MIN_PORT: u32 = 1;
 const MAX_PORT: u32 = 65535;
+const PROGRESS_INTERVAL: usize = 100;     // The number of probes a worker finishes before it reports progress.
+const MAX_BANNER_THREADS: usize = 64;     // Banner grabs block, so they run on a small pool of threads.
 
 #[derive(Debug, Parser, Clone)]
 #[clap(author, version, about, help = "")]
 pub struct Arguments {
-  #[clap(value_parser)]
-  /// Ip Address
-  pub ip: String,
+  #[clap(value_parser, required_unless_present_any(&["input-file", "list-interfaces"]))]
+  /// Ip Address, hostname, CIDR block or octet range. Separate multiple targets with commas.
+  pub ip: Option<String>,
 
-  #[clap(short, long, help = "Ports to scan. Example: 1-1024, 1,2,3,4\n[default: 1-65535]")]
+  #[clap(short, long)]
+  /// Read targets from a file, one or more per line.
+  pub input_file: Option<String>,
+
+  #[clap(long, default_value_if("all-addresses", Some("false"), Some("true")), min_values(0))]
+  /// Scan every address a hostname resolves to instead of only the first.
+  pub all_addresses: bool,
+
+  #[clap(short, long, allow_hyphen_values(true), help = "Ports to scan. Example: 1-1024, 22,80,8000-8100, -1024, 60000-, ssh,http\n[default: 1-65535]")]
   /// The Port(s) you want to scan.
   pub ports: Option<String>,
 
+  #[clap(long, conflicts_with("ports"), value_parser = clap::value_parser!(u16).range(1..=1000))]
+  /// Scan the N most frequently open ports.
+  pub top_ports: Option<u16>,
+
+  #[clap(short = 'F', long, conflicts_with_all(&["ports", "top-ports"]))]
+  /// Scan the 100 most frequently open ports.
+  pub fast: bool,
+
+  #[clap(long)]
+  /// Hosts that must never be scanned. Accepts addresses, hostnames, CIDR blocks and octet ranges.
+  pub exclude: Option<String>,
+
+  #[clap(long)]
+  /// Read hosts that must never be scanned from a file.
+  pub exclude_file: Option<String>,
+
+  #[clap(long, allow_hyphen_values(true))]
+  /// Ports that must never be scanned.
+  pub exclude_ports: Option<String>,
+
+  #[clap(long, default_value_if("skip-discovery", Some("false"), Some("true")), min_values(0))]
+  /// Scan every host without checking that it is up first.
+  pub skip_discovery: bool,
+
+  #[clap(long)]
+  /// Ports that receive a tcp syn ping during host discovery. Turns on discovery for a single host.
+  pub ping_syn_ports: Option<String>,
+
+  #[clap(long)]
+  /// Ports that receive a tcp ack ping during host discovery. Turns on discovery for a single host.
+  pub ping_ack_ports: Option<String>,
+
+  #[clap(short = 'e', long)]
+  /// Send every probe through this network interface.
+  pub interface: Option<String>,
+
+  #[clap(short = 'S', long)]
+  /// Send every probe from this address.
+  pub source_ip: Option<IpAddr>,
+
+  #[clap(long, default_value_if("list-interfaces", Some("false"), Some("true")), min_values(0))]
+  /// List the network interfaces of this machine and exit.
+  pub list_interfaces: bool,
+
+  #[clap(long, value_enum, default_value = "connect")]
+  /// The type of scan to run.
+  pub scan_type: ScanType,
+
+  #[clap(long, value_parser = clap::value_parser!(u32).range(1..))]
+  /// The most probes to send each second across every thread.
+  pub max_rate: Option<u32>,
+
+  #[clap(long, value_parser = clap::value_parser!(u32).range(1..))]
+  /// The fewest probes to send each second across every thread.
+  pub min_rate: Option<u32>,
+
+  #[clap(long, default_value = "0")]
+  /// The time in ms to wait between probes.
+  pub scan_delay: u64,
+
+  #[clap(long, default_value = "0")]
+  /// Adds a random delay of up to N ms between probes.
+  pub jitter: u64,
+
+  #[clap(long, value_enum, use_value_delimiter(true), default_value = "open")]
+  /// The port states to display and write to the json output.
+  pub show_states: Vec<StateFilter>,
+
   #[clap(long, default_value_if("debug", Some("false"), Some("true")), min_values(0))]
   /// Display debug information.
   pub debug: bool,
@@ -44,20 +163,44 @@ pub struct Arguments {
   pub verbose: bool,
 
   #[clap(short, long, default_value = "300")]
-  /// The timeout in ms before a port is dropped
+  /// The longest time in ms to wait for a port to answer. Timeouts adapt to the measured round trip time below this
   pub timeout: u64,
 
-  #[clap(short = 'T', long, default_value = "650")]
-  /// TThe number of threads
-  pub threads: u32,
+  #[clap(long, default_value = "100")]
+  /// The shortest time in ms to wait for a port to answer
+  pub min_timeout: u64,
+
+  #[clap(long, default_value = "0")]
+  /// The number of times a port that never answered is probed again
+  pub retries: u32,
+
+  #[clap(short = 'T', long, alias = "threads", default_value = "1000")]
+  /// The number of connections in flight at once
+  pub concurrency: u32,
 
   #[clap(short, long, default_value_if("bannergrab", Some("false"), Some("true")), min_values(0))]
-  /// Make a get request on each port to grab the banner.
+  /// Grab a banner from each open port, nudging services that do not talk first.
   pub banner_grab: bool,
 
   #[clap(long, default_value = "256")]
-  /// Set the max length of a banner grab.
+  /// Set the max length of a banner grab in bytes.
   pub banner_len: u32,
+
+  #[clap(long, default_value_if("tls", Some("false"), Some("true")), min_values(0))]
+  /// Run a tls handshake with each open port and record the protocol, cipher, alpn and certificate chain.
+  pub tls: bool,
+
+  #[clap(long, default_value_if("tls-audit", Some("false"), Some("true")), min_values(0))]
+  /// Enumerate the tls versions and cipher suites each open port accepts and flag weak ones and certificate problems.
+  pub tls_audit: bool,
+
+  #[clap(long, default_value_if("service-version", Some("false"), Some("true")), min_values(0))]
+  /// Identify the product and version of the service on each open port.
+  pub service_version: bool,
+
+  #[clap(long)]
+  /// Read the version detection probes from a file in the nmap-service-probes format instead of using the built in set.
+  pub service_probes: Option<String>,
 }
 
 // Displays help information.
@@ -90,22 +233,48 @@ pub fn display_help(bin: String) -> () {
     {} <IP> [OPTIONS]
 
 {}:
-    <IP>    IP Address
+    <IP>    Targets separated by commas. Accepts IPv4 and IPv6 addresses, hostnames, CIDR blocks and octet ranges.
+            Example: 10.0.0.1, ::1, [fe80::1%eth0], db01.internal, 10.0.0.0/24, 10.0.0.1-50
 
 {}:
+        --{}                  Scans every address a hostname resolves to instead of only the first
         --{}                          Displays debug information
+        --{}      <HOSTS>           Hosts that are never scanned. Accepts the same formats as <IP>
+        --{} <FILE>            Reads hosts that are never scanned from a file
+        --{} <PORTS>          Ports that are never scanned. Example: 9100, 502,8000-8100
+    -F, --{}                           Scans the 100 most frequently open ports
     -h, --{}                           Displays help information
-    -b, --{}                    Sends a GET request to the port and records the response
-        --{}   <LEN>             Sets the maxium response length for a banner grab [default: 256]
+    -e, --{}    <NAME>            Sends every probe through this network interface
+    -i, --{}   <FILE>            Reads targets from a file, one or more per line
+        --{}       <MS>              Adds a random delay of up to MS between probes [default: 0]
+        --{}                Lists the network interfaces with their addresses, MTU and state
+    -b, --{}                    Records the greeting of each open port, nudging services that do not talk first
+        --{}   <LEN>             Sets the maxium response length in bytes for a banner grab [default: 256]
+        --{}     <RATE>            The most probes to send each second across every thread
+        --{}     <RATE>            The fewest probes to send each second across every thread
     -o, --{}       <OUTPUT>          Exports open ports to a json file
-    -p, --{}        <PORTS>           Ports to scan. Example: 1-1024, 1,2,3,4 [default: 1-65535]
-    -t, --{}      <TIMEOUT>         The timeout in ms before a port is dropped [default: 300]
-    -T, --{}      <THREADS>         The number of threads [default: 650]
+        --{} <PORTS>         Ports that receive a tcp ack ping during host discovery, also on a single host [default: 80]
+        --{} <PORTS>         Ports that receive a tcp syn ping during host discovery, also on a single host [default: 443]
+        --{}    <TYPE>            connect: full tcp handshake, syn: half open scan using raw sockets, udp: udp scan [default: connect]
+        --{}   <MS>              The time to wait between probes [default: 0]
+        --{}      <N>               Probes ports that never answered N more times, waiting longer each time [default: 0]
+        --{}                 Scans every host without checking that it is up first
+    -S, --{}    <IP>              Sends every probe from this address
+    -p, --{}        <PORTS>           Ports to scan. Example: 1-1024, 22,80,8000-8100, -1024, 60000-, ssh,http [default: 1-65535]
+        --{}    <N>               Scans the N most frequently open ports [max: 1000]
+        --{}                            Records the tls version, cipher, alpn and certificate chain of each open port
+        --{}                      Lists the tls versions and ciphers each open port accepts and flags weak ones
+        --{}  <STATES>          States to display. open, closed, filtered, open-filtered, error or all [default: open]
+        --{}                Identifies the product and version of the service on each open port
+        --{} <FILE>          Reads version detection probes in the nmap-service-probes format instead of the built in set
+        --{}  <TIMEOUT>         The shortest time in ms to wait for a port to answer [default: 100]
+    -t, --{}      <TIMEOUT>         The longest time in ms to wait for a port to answer. Adapts to the round trip time below this [default: 300]
+    -T, --{}  <CONCURRENCY>     The number of connections in flight at once [default: 1000]
         --{}                        Display verbose information about the port scan", 
   style("lethal_potato").red().bright(), style(VERSION).yellow().bright(), style(AUTHOR).yellow().bright(), 
-  style("USAGE").yellow(), bin_name, style("ARGS").yellow(), style("OPTIONS").yellow(), style("debug").cyan(), 
-  style("help").cyan(), style("banner-grab").cyan(), style("banner-len").cyan(), style("output").cyan(), 
-  style("ports").cyan(), style("timeout").cyan(), style("threads").cyan(), style("verbose").cyan()
+  style("USAGE").yellow(), bin_name, style("ARGS").yellow(), style("OPTIONS").yellow(), style("all-addresses").cyan(), style("debug").cyan(), 
+  style("exclude").cyan(), style("exclude-file").cyan(), style("exclude-ports").cyan(), style("fast").cyan(), style("help").cyan(), style("interface").cyan(), style("input-file").cyan(), style("jitter").cyan(), style("list-interfaces").cyan(), style("banner-grab").cyan(), style("banner-len").cyan(), 
+  style("max-rate").cyan(), style("min-rate").cyan(), style("output").cyan(), style("ping-ack-ports").cyan(), style("ping-syn-ports").cyan(), style("scan-type").cyan(), style("scan-delay").cyan(), style("retries").cyan(), style("skip-discovery").cyan(), style("source-ip").cyan(), style("ports").cyan(), style("top-ports").cyan(), style("tls").cyan(), style("tls-audit").cyan(), style("show-states").cyan(), style("service-version").cyan(), style("service-probes").cyan(), style("min-timeout").cyan(), style("timeout").cyan(), style("concurrency").cyan(), style("verbose").cyan()
   );
 }
 
@@ -137,10 +306,10 @@ impl Arguments {
   /**Function the ip address of the target and discovered ports and writes the output to json file.
    * Params:
    *  &mut self
-   *   target: FileOutput {Contains the ip address and open ports found on the target}
+   *   target: Vec<FileOutput> {Contains the ip address and open ports found on each scanned address}
    * Returns nothing. 
    */
-  pub fn write_output(&self, os: OperatingSystem, target: FileOutput) -> () {
+  pub fn write_output(&self, os: OperatingSystem, target: Vec<FileOutput>) -> () {
     let mut path = String::new();                               // Stores the path specified by the user.
     let mut c_path = String::new();                             
     let mut json_output = String::new();                        // Stores the output of the created json object.
@@ -238,7 +407,7 @@ impl Arguments {
       out
     };
     
-    // Turns the FileOutput structure into a json object.
+    // Turns the FileOutput structures into a json array.
     match serde_json::to_string_pretty(&target) {
       Ok(s) => {
         json_output = s;
@@ -293,723 +462,1340 @@ impl Arguments {
     valid_path
   }
   
-  /**Function parses u8 values.
+  /**Function resolves a hostname into the ipv4 and ipv6 addresses it points to.
    * Params:
-   *  value: &str {The value to parse as a slice}
-   * Returns u8.
+   *  name: &str {The hostname to resolve}
+   * Returns Result<Vec<IpAddr>, String>
    */
-  pub fn parse_u8(value: &str) -> u8 {
-    let mut out: u8 = 0;
+  pub fn resolve_host(name: &str) -> Result<Vec<IpAddr>, String> {
+    let mut out: Vec<IpAddr> = Default::default();
+
+    match (name, 0).to_socket_addrs() {
+      Ok(addresses) => {
+        for i in addresses {
+          if out.contains(&i.ip()) == false {
+            out.push(i.ip());
+          }
+        }
+      },
 
-    match value.parse::<u8>() {
-      Ok(s) => { out = s; },
       Err(e) => {
-        fmt::f_error("unable to parse", value.to_string().as_str(), format!("{}", e).as_str());
-        exit(1);
+        return Err(format!("{}", e));
       }
     }
 
-    out
+    Ok(out)
   }
 
-  /**Function parses u16 values.
+  /**Function resolves a hostname target and returns the addresses that should be scanned.
    * Params:
-   *  value: &str {The value to parse as a slice}
-   * Returns u8.
+   *  &self
+   *  name: &str {The hostname typed by the user}
+   * Returns Vec<IpAddr>.
    */
-  pub fn parse_u32(value: &str) -> u32 {
-    let mut out: u32 = 0;
-
-    match value.parse::<u32>() {
-      Ok(s) => { out = s; },
+  pub fn resolve_target(&self, name: &str) -> Vec<IpAddr> {
+    let mut resolved = match Self::resolve_host(name) {
+      Ok(s) => { s },
       Err(e) => {
-        fmt::f_error("unable to parse", value.to_string().as_str(), format!("{}", e).as_str());
+        fmt::f_error("unable to resolve host", name, e.as_str());
         exit(1);
       }
+    };
+
+    if resolved.len() < 1 {
+      fmt::f_error("unable to resolve host", name, "no addresses found");
+      exit(1);
     }
 
-    out
+    if self.verbose == true || self.debug == true {
+      let list: Vec<String> = resolved.iter().map(|i| i.to_string()).collect();
+      println!("{}: {} resolved to {}", style("Info").yellow().bright(), style(name).cyan(), style(list.join(", ")).cyan());
+    }
+
+    // Only the first address is scanned unless the user asked for all of them.
+    if self.all_addresses == false {
+      resolved.truncate(1);
+    }
+
+    resolved
   }
 
-  /**Function works out ports should be generated and what ports should be scanned.
+  /**Function parses every target and the ports and returns a struct for each address that will be scanned.
+   * Targets can be addresses, hostnames, CIDR blocks or octet ranges and may come from the command line or an input file.
    * Params:
-   *  text: String {The port string}
-   * Returns Pattern.
+   *  &self
+   * Returns Vec<IpData>.
    */
-  pub fn find_pattern(text: String) -> Pattern {
-    let out = Pattern::Unknown;
-    
-    let c_text = text.clone();
-    let mut split_text: Vec<&str> = c_text.split("-").collect();
+  pub fn create_address(&self) -> Vec<IpData> {
+    let ports = self.create_ports();
+    let mut targets: Vec<String> = Default::default();
+    let mut seen: HashSet<SocketAddr> = Default::default();
+    let mut out: Vec<IpData> = Default::default();
+
+    if let Some(ip) = self.ip.clone() {
+      targets.append(&mut split_targets(ip.as_str()));
+    }
 
-    if split_text.len() == 2 {
-      return Pattern::Range;
+    if let Some(path) = self.input_file.clone() {
+      match read_target_file(path.as_str()) {
+        Ok(mut s) => { targets.append(&mut s); },
+        Err(e) => {
+          fmt::f_error("unable to read input file", path.as_str(), e.as_str());
+          exit(1);
+        }
+      }
     }
 
-    split_text = c_text.split(",").collect();
-    if split_text.len() >= 2 {
-      return Pattern::Csv;
+    if targets.len() < 1 {
+      fmt::f_error("No targets to scan", "", "");
+      exit(1);
     }
 
-    if split_text.len() == 1 {
-      match split_text[0].parse::<usize>() {
-        Ok(_) => { return Pattern::Single; },
+    for target in targets {
+      let spec = match TargetSpec::parse(target.as_str()) {
+        Ok(s) => { s },
         Err(e) => {
-          fmt::f_error("unable to parse port", split_text[0], format!("{}", e).as_str());
+          println!("{}: Invalid ip address [{}] - {}", style("Error").red(), style(target).cyan(), style(e).red());
           exit(1);
         }
+      };
+
+      // Hostnames are resolved while every other target is expanded into its addresses.
+      let addresses: Vec<(IpAddr, u32)> = match spec.clone() {
+        TargetSpec::Hostname(name) => {
+          self.resolve_target(name.as_str()).into_iter().map(|i| (i, 0)).collect()
+        }
+
+        _ => {
+          match spec.expand() {
+            Ok(s) => { s },
+            Err(e) => {
+              fmt::f_error("unable to expand target", target.as_str(), e.as_str());
+              exit(1);
+            }
+          }
+        }
+      };
+
+      for (ip, scope_id) in addresses {
+        let mut address = IpData::new();
+        address.ip = ip;
+        address.scope_id = scope_id;
+        address.ports = ports.clone();
+
+        // The name is only kept for targets that point to exactly one host.
+        match spec {
+          TargetSpec::Address(_, _) | TargetSpec::Hostname(_) => { address.name = target.clone(); }
+          _ =>                                                   { address.name = address.display_ip(); }
+        }
+
+        if seen.insert(address.socket_addr(0)) == true {
+          out.push(address);
+        }
+      }
+
+      if out.len() as u128 > MAX_TARGET_HOSTS {
+        fmt::f_error("Too many hosts to scan", format!("{}", out.len()).as_str(), "");
+        exit(1);
       }
     }
 
     out
   }
 
-  /**Function parses each octet in the network byte address as well as the port and returns a struct with the information.
+  /**Function generates the ports that will be scanned from the port string.
    * Params:
    *  &self
-   * Returns IpData.
+   * Returns Vec<u16>.
    */
-  pub fn create_address(&self) -> IpData {
-    let clone_ip = self.ip.clone();
-    let ip_str: Vec<&str> = clone_ip.split(".").collect();
-    let mut port_string = String::new();
-    let mut address = IpData::new();
-
-    // Check if ip is valid.
-    if ip_str.len() < 4 || ip_str.len() > 4 {
-      println!("{}: Invalid ip address [{}]", style("Error").red(), style(clone_ip).cyan());
-      exit(1);
-    }
-
-    address.a = Self::parse_u8(ip_str[0]);
-    address.b = Self::parse_u8(ip_str[1]);
-    address.c = Self::parse_u8(ip_str[2]);
-    address.d = Self::parse_u8(ip_str[3]);
+  pub fn create_ports(&self) -> Vec<u16> {
+    let mut ports: Vec<u16> = Default::default();
 
     // Get the port string if it exists.
     if let Some(port) = self.ports.clone() {
-      port_string.push_str(port.as_str());
+      return Self::parse_ports(port);
     }
-    
-    // We will scan all ports if no port string exists.
-    else {
-      for i in MIN_PORT..MAX_PORT+1 {
-        address.ports.push(i as u16);
-      }
 
-      return address;
-    }
+    // The most common ports are scanned first when the user asks for a top-n scan.
+    if let Some(count) = self.top_ports {
+      if self.scan_type == ScanType::Udp {
+        if count as usize > TOP_UDP_PORTS.len() {
+          println!("{}: only the {} most common udp ports are known, scanning those", style("Info").yellow().bright(), style(TOP_UDP_PORTS.len()).cyan());
+        }
 
-    // Check if port is using the '-' for range.
-    let c_port = port_string.clone();
-    let pattern = Self::find_pattern(c_port);
+        return top_udp_ports(count as usize);
+      }
 
-    // Ports are generated and pushed to the vec based on the range set by the user.
-    if pattern == Pattern::Range {
-      let clone_port = port_string.clone();
-      let split_port: Vec<&str> = clone_port.split("-").collect();
-      
-      let port_start = Self::parse_u32(split_port[0]);
-      let port_end = Self::parse_u32(split_port[1]);
+      return top_ports(count as usize);
+    }
 
-      for i in port_start..port_end+1 {
-        address.ports.push(i as u16);
+    if self.fast == true {
+      if self.scan_type == ScanType::Udp {
+        return top_udp_ports(FAST_PORT_COUNT);
       }
+
+      return top_ports(FAST_PORT_COUNT);
     }
     
-    // Ports are generated and pushed into the vec based on comma separated values.
-    else if pattern == Pattern::Csv {
-      let clone_port = port_string.clone();
-      let split_port: Vec<&str> = clone_port.split(",").collect();
-
-      for i in split_port {
-        match i.parse::<u16>() {
-          Ok(s) => {
-            address.ports.push(s);
-          },
-          Err(e) => {
-            fmt::f_error("unable to parse port", i.to_string().as_str(), format!("{}", e).as_str());
-            exit(1);
-          }
-        }
-      }
+    // We will scan all ports if no port string exists.
+    for i in MIN_PORT..MAX_PORT+1 {
+      ports.push(i as u16);
     }
 
-    // A single value is pushed into the vec.
-    else if pattern == Pattern::Single {
-      let clone_port = port_string.clone();
-      match clone_port.parse::<u16>() {
-        Ok(s) => { address.ports.push(s); },
-        Err(e) => {
-          fmt::f_error("unable to parse port", port_string.as_str(), format!("{}", e).as_str());
-          exit(1);
-        }
-      }
-    }
+    ports
+  }
 
-    else if pattern == Pattern::Unknown {
-      fmt::f_error("Invalid port syntax", port_string.as_str(), "");
-      exit(1);
+  /**Function parses a port string and generates every port it describes.
+   * Params:
+   *  port_string: String {The port string}
+   * Returns Vec<u16>.
+   */
+  pub fn parse_ports(port_string: String) -> Vec<u16> {
+    match parse_port_spec(port_string.as_str()) {
+      Ok(s) => { s },
+      Err(e) => {
+        fmt::f_spec_error("Invalid port syntax", port_string.as_str(), e.position, e.message.as_str());
+        exit(1);
+      }
     }
-
-    address
   }
 
-  /**Function begins the port scan.
+  /**Function parses the hosts the user never wants scanned from --exclude and --exclude-file.
+   * Hostnames are resolved and every address they point to is excluded.
    * Params:
    *  &self
-   * Returns nothing.
+   * Returns Vec<TargetSpec>.
    */
-  pub fn begin_scan(&self, settings: ArgumentSettings) -> () {
-    // We prepare our network information here.
-    let ip = self.create_address();
-    println!("{} Starting scan on host {} over {} ports", style("Potato =>").red().bright(),
-    style(format!("[{}.{}.{}.{}]", ip.a, ip.b, ip.c, ip.d)).cyan(), style(format!("[{}]", ip.ports.clone().len())).cyan());
-    println!("");
-
-    let mut file_output = FileOutput::new();
-    let mut write_ports: Vec<u16> = Default::default();
-    let mut banner_resp: Vec<BannerResponse> = Default::default();
-    let mut address = SocketAddr::new(IpAddr::V4(Ipv4Addr::new(ip.a, ip.b, ip.c, ip.d)), 1);
-    let ports = ip.ports.clone();
-
-    // start_time will be used to generated the elasped time at the end of the scan.
-    let start_time = std::time::Instant::now();
-    
-    if self.threads == 0 || self.threads == 1 || self.threads > ip.ports.len() as u32 {
-
-      for i in ports {
-        address.set_port(i);
-        self.standard_port_scan(address, &mut write_ports, &mut banner_resp);
-      }
+  pub fn create_exclusions(&self) -> Vec<TargetSpec> {
+    let mut items: Vec<String> = Default::default();
+    let mut out: Vec<TargetSpec> = Default::default();
 
-      write_ports.sort();
-      fmt::f_display_port(&write_ports);
+    if let Some(exclude) = self.exclude.clone() {
+      items.append(&mut split_targets(exclude.as_str()));
+    }
 
-      if self.banner_grab == true {
-        println!("");
-        for i in banner_resp.clone() {
-          fmt::f_display_banner(i);
+    if let Some(path) = self.exclude_file.clone() {
+      match read_target_file(path.as_str()) {
+        Ok(mut s) => { items.append(&mut s); },
+        Err(e) => {
+          fmt::f_error("unable to read exclude file", path.as_str(), e.as_str());
+          exit(1);
         }
       }
-
-      if settings.is_valid_output_path == true {
-        file_output.host = address.ip().to_string();
-        file_output.ports = write_ports;
-        file_output.banner_response = banner_resp;
-
-        self.write_output(settings.os.clone(), file_output);
-      }
-
-      println!("{}: Scan completed in {:?}", style("OK").yellow().bright(), style(start_time.elapsed()).cyan())
     }
 
-    else if self.threads > 1 {
-      self.init_threads(ip, &mut write_ports, &mut banner_resp);
-      
-      if settings.is_valid_output_path == true {       // Checks that output will be written to a valid directory before writing to the disk.
-        file_output.host = address.ip().to_string();   // Data structure will be used for creating the json object.
-        file_output.ports = write_ports;
-        file_output.banner_response = banner_resp;
+    for i in items {
+      match TargetSpec::parse(i.as_str()) {
+        Ok(TargetSpec::Hostname(name)) => {
+          match Self::resolve_host(name.as_str()) {
+            Ok(addresses) => {
+              for ip in addresses {
+                out.push(TargetSpec::Address(ip, 0));
+              }
+            },
+
+            Err(e) => {
+              fmt::f_error("unable to resolve excluded host", name.as_str(), e.as_str());
+              exit(1);
+            }
+          }
+        },
 
-        self.write_output(settings.os.clone(), file_output);
+        Ok(s) => { out.push(s); },
+        Err(e) => {
+          println!("{}: Invalid excluded address [{}] - {}", style("Error").red(), style(i).cyan(), style(e).red());
+          exit(1);
+        }
       }
-      
-      println!("\n{}: Scan completed in {:?}", style("OK").yellow().bright(), style(start_time.elapsed()).cyan())
     }
+
+    out
   }
 
-  /**function scans a port and displays whether the port was open or closed. 
+  /**Function removes the excluded hosts and ports from the list of targets.
    * Params:
    *  &self
-   *  address: SocketAddr {The ip address and port that will be passed to the connect_timeout function}
-   * Returns nothing.
-  */
-  pub fn standard_port_scan(&self, address: SocketAddr, write_ports: &mut Vec<u16>, banner_resp: &mut Vec<BannerResponse>) -> () {
-    match TcpStream::connect_timeout(&address, Duration::from_millis(self.timeout)) {
-      Ok(_) => {
-        write_ports.push(address.port());
+   *  hosts: Vec<IpData> {The expanded list of targets}
+   * Returns Vec<IpData>.
+   */
+  pub fn apply_exclusions(&self, hosts: Vec<IpData>) -> Vec<IpData> {
+    let exclusions = self.create_exclusions();
+    let mut excluded_ports: HashSet<u16> = Default::default();
+    let mut out: Vec<IpData> = Default::default();
 
-        if let Some(data) = Self::get_banner(&address, self.timeout, self.debug, self.banner_len) {
-          let mut banner = BannerResponse::new();
-          banner.port = address.port();
-          banner.data = data;
+    if let Some(ports) = self.exclude_ports.clone() {
+      excluded_ports = Self::parse_ports(ports).into_iter().collect();
+    }
 
-          banner_resp.push(banner);
+    for mut ip in hosts {
+      if exclusions.iter().any(|i| i.contains(&ip.ip)) {
+        if self.verbose == true || self.debug == true {
+          println!("{}: excluding host {}", style("Info").yellow().bright(), style(ip.display_ip()).cyan());
         }
-      },
 
-      Err(_) => {
-        if self.verbose == true {
-          println!("{}: {}", style(format!("{}/tcp", address.port())).yellow().bright(), style("closed").red().bright());
-        }
+        continue;
       }
+
+      ip.ports.retain(|i| excluded_ports.contains(i) == false);
+      out.push(ip);
+    }
+
+    if out.len() < 1 || out[0].ports.len() < 1 {
+      fmt::f_error("Nothing left to scan after applying exclusions", "", "");
+      exit(1);
     }
+
+    out
   }
 
-  /**Function sets up the stage and scans multiple ports using the specified number of threads by the user.
+  /**Function begins the port scan.
    * Params:
    *  &self
-   *  ip:   IpData {The structure that holds the ip address and ports to be scanned}
    * Returns nothing.
    */
-  pub fn init_threads(&self, ip: IpData, write_ports: &mut Vec<u16>, banner_resp: &mut Vec<BannerResponse>) -> () {
-    let mut flags = Flags::new();
-    flags.set_flags(
-      self.debug.clone(), 
-      self.timeout.clone(), 
-      self.verbose.clone(), 
-      self.banner_grab.clone(),
-      self.banner_len.clone()
-    );
-
-    let scanable_ports = ip.ports.clone();
-    // let mut writable_ports: Vec<u16> = Default::default();
-    let (th_sender, main_recv) = unbounded::<String>();
+  pub fn begin_scan(&self, settings: ArgumentSettings) -> () {
+    if self.list_interfaces == true {
+      fmt::f_display_interfaces(&pnet_datalink::interfaces());
+      return;
+    }
 
-    // The ports per chunk for each thread is calculated.
-    let total_ports = ip.ports.len() as u16;
-    let mut port_chunk = total_ports / self.threads.clone() as u16;
-    let remainder_chunk = total_ports % self.threads.clone() as u16;
+    // We prepare our network information here.
+    let mut hosts = self.apply_exclusions(self.create_address());
 
-    if self.debug == true {
-      fmt::f_debug("Ports allocated per thread", format!("{}", port_chunk).as_str());
+    // A sequential scan opens one connection at a time, so it has no way to catch up with a minimum rate.
+    if self.min_rate.is_some() && self.scan_type == ScanType::Connect && self.concurrency <= 1 {
+      fmt::f_error("Invalid scan pacing", "", "--min-rate needs a --concurrency above 1 for connect scans");
+      exit(1);
     }
-    
-    // let port_chunks: Vec<u16> = Default::default();
-    let mut handles: Vec<std::thread::JoinHandle<()>> = Default::default();
-    let address = SocketAddr::new(IpAddr::V4(Ipv4Addr::new(ip.a, ip.b, ip.c, ip.d)), 1);
-
-    // Sets the boundries for each port vec to be generated.
-    let mut port_counter: u16 = 0;
-    let mut thread_counter = self.threads.clone();     
-    let mut port_chunk_start: u16 = 0;
-    let mut port_chunk_end: u16 = port_chunk;
 
-    while thread_counter > 0  {
+    // Every probe of the scan is paced by the same limiter.
+    let limiter = match RateLimiter::new(self.max_rate, self.min_rate, self.scan_delay, self.jitter) {
+      Ok(s) => { Arc::new(s) },
+      Err(e) => {
+        fmt::f_error("Invalid scan pacing", "", e.as_str());
+        exit(1);
+      }
+    };
 
-      // Tempoarily stores ports to hand off to each thread.
-      let mut temp_ports: Vec<u16> = Default::default();
+    // Timeouts are worked out from the round trip time to each host, up to the timeout set by the user.
+    let rtt = Arc::new(RttTable::new(self.min_timeout, self.timeout));
 
-      // Segements the overall port vec into a smaller vec.
-      for i in port_chunk_start..port_chunk_end {
-        temp_ports.push(scanable_ports[i as usize]);
-        
-        if port_counter as usize +1 > MAX_PORT as usize {
-          port_counter = MAX_PORT as u16;
-        }
-        
-        else {
-          port_counter += 1;
-        }
+    // Every probe leaves from the interface and address picked by the user.
+    let binding = match SourceBinding::new(self.interface.clone(), self.source_ip) {
+      Ok(s) => { Arc::new(s) },
+      Err(e) => {
+        fmt::f_error("Invalid source", "", e.as_str());
+        exit(1);
       }
+    };
 
-      let th_port_chunk = temp_ports.clone();
-      let mut ip_clone = address.clone();
-      let c_flags = flags.clone();
-      let sender_clone = th_sender.clone();
-      
-      // The thread is pushed and stores in a vec of handles upon creation.
-      handles.push(thread::spawn(move || {
-        Self::thread_run_scan(&mut ip_clone, th_port_chunk, c_flags, sender_clone);
-      }));      
+    // Version detection probes are read before the scan so a broken probe file is reported straight away.
+    let probes = match self.service_version == true || self.service_probes.is_some() {
+      true =>  { Some(self.load_probes()) },
+      false => { None }
+    };
 
-      if port_chunk_start as usize + port_chunk as usize > MAX_PORT as usize {
-        port_chunk_start = MAX_PORT as u16 - port_chunk;
-      }
+    // start_time will be used to generated the elasped time at the end of the scan.
+    let start_time = std::time::Instant::now();
 
-      else {
-        port_chunk_start += port_chunk;
+    // Hosts that do not answer any ping are left out of the port scan. A single host is scanned straight away unless
+    // the user asked for pings, the same way it was before discovery existed.
+    let mut macs: HashMap<IpAddr, MacAddr> = Default::default();
+    let explicit_ping = self.ping_syn_ports.is_some() || self.ping_ack_ports.is_some();
+    if wants_discovery(hosts.len(), self.skip_discovery, explicit_ping) == true {
+      let total_hosts = hosts.len();
+      (hosts, macs) = self.discover_hosts(hosts, &limiter, &rtt, &binding);
+
+      println!("{} {} of {} hosts are up", style("Potato =>").red().bright(), style(hosts.len()).cyan(), style(total_hosts).cyan());
+      if hosts.len() < 1 {
+        println!("{}: Hosts that block pings can still be scanned with --skip-discovery", style("Info").yellow().bright());
+        return;
       }
+    }
 
-      if port_chunk_end as usize + port_chunk as usize > MAX_PORT as usize {
-        port_chunk_end = MAX_PORT as u16;
-      }
+    let total_jobs: usize = hosts.iter().map(|i| i.ports.len()).sum();
 
-      else {
-        port_chunk_end += port_chunk;
+    if hosts.len() == 1 {
+      let ip = &hosts[0];
+      let mut target = ip.display_ip();
+      if ip.name != ip.display_ip() {
+        target = format!("{} ({})", ip.name, ip.display_ip());
       }
 
-      temp_ports.clear();
-      thread_counter -= 1;
+      println!("{} Starting scan on host {} over {} ports", style("Potato =>").red().bright(),
+      style(format!("[{}]", target)).cyan(), style(format!("[{}]", ip.ports.len())).cyan());
     }
 
-    // Code block deals with the reaminder ports that are left over from a calcuation
-    // that results in a float instead of an integer.
-    if remainder_chunk > 0 {
-      
-      if self.debug == true {
-        println!("{} {} {} {}", style("debug =>").red().bright(),
-        style("Found").yellow(), style(remainder_chunk).cyan(), style("remaining ports. Allocating to one or multiple threads").yellow());
-      
-        println!("{} {}={} {}={} {}={}", style("Debug =>").red().bright(), 
-        style("Start").yellow(), style(port_chunk_start).cyan(), style("End").yellow(), style(port_chunk_end).cyan(),
-        style("chunk").yellow(), style(port_chunk).cyan());
-      }
+    else {
+      println!("{} Starting scan on {} hosts over {} ports", style("Potato =>").red().bright(),
+      style(format!("[{}]", hosts.len())).cyan(), style(format!("[{}]", total_jobs)).cyan());
+    }
 
-      // Assign the correct starting and end chunk values to scan remaining ports.
-      port_chunk_end = port_chunk_start + remainder_chunk;
-      port_chunk_start = port_chunk_end- remainder_chunk;
-      
-      if self.debug == true {
-        fmt::f_debug("Correcting port range", "");
-        
-        println!("{} {}={} {}={}", style("Debug =>").red().bright(), 
-        style("Start").yellow(), style(port_chunk_start).cyan(), style("End").yellow(), style(port_chunk_end).cyan());
+    println!("");
+
+    // Data structures will be used for creating the json object.
+    let mut outputs: Vec<FileOutput> = hosts.iter().map(|i| FileOutput::from_ip(i)).collect();
+    if self.scan_type == ScanType::Udp {
+      for i in outputs.iter_mut() {
+        i.protocol = String::from("UDP");
       }
+    }
 
-      if port_chunk > remainder_chunk {
-        port_chunk = ((remainder_chunk as f32) / 2 as f32) as u16;
+    for (idx, i) in outputs.iter_mut().enumerate() {
+      if let Some(mac) = macs.get(&hosts[idx].ip) {
+        i.host.mac = Some(mac.to_string());
+        i.host.vendor = mac_vendor(mac);
       }
+    }
 
-      // Push remaining ports into a vec.
-      let mut ip_clone = address.clone();
-      let mut remainder_ports: Vec<u16> = Default::default();
+    self.run_pass(&hosts, &mut outputs, &limiter, &rtt, &binding);
 
-      // Calculate the number of threads that should be created for each remainder divided by the size of each port chunk.
-      let mut remaining_port_loops = ((remainder_chunk as f32) / port_chunk as f32).ceil() as u16;
-      let mut port_chunk_pos = port_chunk_start.clone()+port_chunk;
+    // Ports that never answered are probed again, waiting a little longer each time.
+    for attempt in 1..=self.retries {
+      let retry_hosts: Vec<IpData> = hosts.iter().enumerate().map(|(idx, ip)| {
+        let mut host = ip.clone();
+        host.ports = std::mem::take(&mut outputs[idx].unanswered);
+        host
+      }).collect();
 
-      while remaining_port_loops > 0 {
-        
-        // Push remaining ports into a temporary vec.
-        for i in port_chunk_start as u32..port_chunk_pos as u32 +1 as u32 {
-          remainder_ports.push(i as u16);
-        }
+      let retry_jobs: usize = retry_hosts.iter().map(|i| i.ports.len()).sum();
+      if retry_jobs < 1 {
+        break;
+      }
 
-        // Create the thread and clear the port vec through each iteration.
-        let th_remainder_ports = remainder_ports.clone();
-        let c_flags = flags.clone();
-        let sender_clone = th_sender.clone();
+      if self.verbose == true || self.debug == true {
+        println!("{}: retrying {} unanswered probes (attempt {} of {})", style("Info").yellow().bright(), style(retry_jobs).cyan(), 
+        style(attempt).cyan(), style(self.retries).cyan());
+      }
 
-        handles.push(thread::spawn(move || {
-          Self::thread_run_scan(&mut ip_clone, th_remainder_ports, c_flags, sender_clone);
-        }));
-        
-        remainder_ports.clear();
-        
-        if port_chunk_start as u32 + port_chunk as u32 > port_chunk_end as u32 {
-          port_chunk_start += remaining_port_loops;
-        }
+      rtt.set_backoff(1 << attempt.min(16));
+      thread::sleep(Duration::from_millis(self.min_timeout.saturating_mul(1 << (attempt - 1).min(16))));
+      self.run_pass(&retry_hosts, &mut outputs, &limiter, &rtt, &binding);
+    }
 
-        else {
-          port_chunk_start += port_chunk;
-        }
+    // Whatever is still unanswered is recorded with its final state. Silent udp ports may still answer a service probe.
+    for (idx, i) in outputs.iter_mut().enumerate() {
+      for port in std::mem::take(&mut i.unanswered) {
+        let state = self.unanswered_state();
 
-        if port_chunk_pos as u32 + port_chunk as u32 > port_chunk_end as u32 {
-          port_chunk_pos = port_chunk_end;
+        if state == PortState::OpenFiltered {
+          i.candidates.push(port);
         }
 
-        else {
-          port_chunk_pos += port_chunk;
+        if self.verbose == true {
+          println!("{}: {}", style(format!("{}/{}", hosts[idx].socket_addr(port), i.protocol.to_lowercase())).yellow().bright(), style(state.name()).red().bright());
         }
 
-        if self.debug == true {
-          // println!("port_chunk_start={} port_chunk_end_pos={}", port_chunk_start, port_chunk_end_pos);
-          println!("{} ch_st={} ch={} ch_pos={} ch_end={} p_lps={} rem_ch={}", style("Debug =>").red().bright(), 
-          style(port_chunk_start).cyan(), style(port_chunk).cyan(), style(port_chunk_pos).cyan(), 
-          style(port_chunk_end).cyan(), style(remaining_port_loops).cyan(), style(remainder_chunk).cyan());
+        if self.show_state(&state) == true {
+          i.ports.push(PortResult::new(port, state));
         }
-
-        remaining_port_loops -= 1;
       }
     }
 
-      
-    let mut timeout_counter = 0;
-    while timeout_counter < 10 {
-      
-      // We will loop here and wait any incoming messages from worker thread about open ports
-      match main_recv.recv_timeout(Duration::from_millis(self.timeout)) {
-        Ok(s) => {
-          let str_clone = s.clone();
-          let mut split_msg: Vec<&str> = Default::default();
-
-          // If we receive a message with a single 0, we do nothing.
-          // However, if we receive  "number open" we will push the u16 value to a vec.
-          match s.as_str() {
-            "0" => {}
-            _ => {
-              split_msg = str_clone.split("[_]").collect();
-
-              if split_msg.len() > 1 {
-
-                let th_message = Self::validate_thread_message(split_msg[0]);
-                if th_message == ThreadMessage::OpenPort {
-                  if self.debug == true {
-                    println!("added port {}", split_msg[1]);
-                    fmt::f_debug("added port to output vec", split_msg[1]);
-                  }
-                  
-                  let port = Self::parse_u32(split_msg[1]);
-                  if port > 0{
-                    write_ports.push(port as u16);
-                    println!("{} port(s) found", style(write_ports.len()).cyan());
-                  }
-                }
-
-                else if th_message == ThreadMessage::Banner {
-                  if self.debug == true {
-                    println!("found banner response");
-                    fmt::f_debug("Banner response found for port", format!("{} {}", split_msg[1], split_msg[2]).as_str());
-                  }
+    if self.debug == true {
+      for i in hosts.iter() {
+        if let Some(srtt) = rtt.srtt(&i.ip) {
+          fmt::f_debug("Measured round trip time", format!("{} {:?} (timeout {:?})", i.display_ip(), srtt, rtt.timeout(&i.ip)).as_str());
+        }
+      }
+    }
 
-                  let mut banner = BannerResponse::new();
-                  let port = Self::parse_u32(split_msg[1]);
+    // Banners are grabbed from the open ports once the scan is done, udp scans keep the answer to their probe instead.
+    if self.banner_grab == true && self.scan_type != ScanType::Udp {
+      self.init_banner_grab(&hosts, &mut outputs, &binding);
+    }
 
-                  banner.port = port as u16;
-                  banner.data = format!("{}", split_msg[2]);
-                  banner_resp.push(banner);
-                }
-              }
-            }
-          }
-        },
+    // The audit reuses the certificate chain from the tls handshake.
+    if (self.tls == true || self.tls_audit == true) && self.scan_type != ScanType::Udp {
+      self.init_tls_inspection(&hosts, &mut outputs, &binding);
+    }
 
-        Err(e) => {
-          if e == RecvTimeoutError::Timeout {
-            timeout_counter += 1;   // This determines how long main_recv will wait before we join each thread.
-            
-            if self.debug == true {
-              fmt::f_debug("Time before channel is dropped", format!("{}", timeout_counter).as_str());
-            }
-          }
+    if self.tls_audit == true && self.scan_type != ScanType::Udp {
+      self.init_tls_audit(&hosts, &mut outputs, &binding);
+    }
 
-          if e == RecvTimeoutError::Disconnected {
-            break;
-          }
-        }
-      }
+    if let Some(probes) = probes.as_ref() {
+      self.init_service_detection(&hosts, &mut outputs, probes, &binding);
     }
 
-    // Closes and drops the main channel sender and receiver from memory.
-    drop(main_recv);
-    drop(th_sender);
+    // Sorts the ports found on each host and displays them to the screen.
+    // Hosts without open ports are left out when more than one host was scanned.
+    for i in outputs.iter_mut() {
+      i.ports.sort_by_key(|p| p.port);
+      i.banner_response.sort_by_key(|b| b.port);
+      i.tls.sort_by_key(|t| t.port);
 
-    std::thread::sleep(Duration::from_secs(2));
-    // Thread handles are joined the main thread here.
-    for i in handles {
-      
-      let id = i.thread().id();
-      if let Ok(_) = i.join() {
-        if self.debug.clone() == true {
-          fmt::f_debug("joined thread to main with id", format!("{:?}", id).as_str());
-        }
+      if hosts.len() > 1 && i.ports.len() < 1 {
+        continue;
       }
-    }
-
-    // Sorts the ports received by the main threads and displays them to the screen.
-    println!("");
-    write_ports.sort();
-    fmt::f_display_port(&write_ports);
 
-    if self.banner_grab == true {
       println!("");
-      for i in banner_resp.clone() {
-        println!("Port: {}\nbanner: {}\n", style(i.port).cyan(), style(i.data).cyan());
-      }
+      fmt::f_display_host(i, self.banner_grab);
     }
-  }
 
-  /**Function works out what kind of message was to the main thread and returns with the corresponding thread message enum.
-   * Params:
-   *  slice: &str {The message that was ennt}
-   * Returns ThreadMessage
-   */
-  pub fn validate_thread_message(slice: &str) -> ThreadMessage {
-    let mut out = ThreadMessage::KeepAlive;
+    if hosts.len() > 1 {
+      let up = outputs.iter().filter(|i| i.has_open_ports()).count();
+      println!("\n{} of {} hosts have open ports", style(up).cyan(), style(hosts.len()).cyan());
+    }
 
-    match slice {
-      "PORT" =>     { out = ThreadMessage::OpenPort }
-      "BANNER" =>   { out = ThreadMessage::Banner }
-      _ =>          {}
+    // Checks that output will be written to a valid directory before writing to the disk.
+    if settings.is_valid_output_path == true {
+      self.write_output(settings.os.clone(), outputs);
     }
 
-    out
+    println!("\n{}: Scan completed in {:?}", style("OK").yellow().bright(), style(start_time.elapsed()).cyan())
   }
 
-
-  /**Function is called by each thread and scans the assigned port chunk.
+  /**Function pings every host and returns the ones that answered.
+   * Hosts on a directly connected segment are found with arp. Every other host is pinged with raw icmp and tcp
+   * packets when possible, otherwise tcp connections are used instead.
    * Params:
-   *  ip_clone: &mut SocketAddr {The ip address and port structure}
-   *  ports:    Vec<u16>        {A vec that contains a list of ports to scan}
-   *  f:        Flags           {Setting we want to apply to the worker threads}
-   *  send:     Sender<String>  {}
-   * Returns nothing.
+   *  &self
+   *  hosts:   Vec<IpData>         {The structures that hold each ip address and the ports to be scanned}
+   *  limiter: &Arc<RateLimiter>   {Paces the pings}
+   *  rtt:     &Arc<RttTable>      {Measures each host and decides how long to wait for answers}
+   *  binding: &Arc<SourceBinding> {The interface and address picked by the user}
+   * Returns (Vec<IpData>, HashMap<IpAddr, MacAddr>) (The hosts that answered and the mac address of those found with arp)
    */
-  pub fn thread_run_scan(ip_clone: &mut SocketAddr, ports: Vec<u16>, f: Flags, send: Sender<String>) -> () {
-    let th_debug = f.debug.clone();
-    let th_timeout = f.timeout.clone();
-    let th_verbose = f.verbose.clone();
-    let th_banner_req = f.banner_grab.clone();
-    let th_banner_len = f.banner_len.clone();
-    let mut address = ip_clone.clone();
+  pub fn discover_hosts(&self, hosts: Vec<IpData>, limiter: &Arc<RateLimiter>, rtt: &Arc<RttTable>, binding: &Arc<SourceBinding>) -> (Vec<IpData>, HashMap<IpAddr, MacAddr>) {
+    let syn_ports = Self::parse_ports(self.ping_syn_ports.clone().unwrap_or(String::from(DEFAULT_PING_SYN_PORTS)));
+    let ack_ports = Self::parse_ports(self.ping_ack_ports.clone().unwrap_or(String::from(DEFAULT_PING_ACK_PORTS)));
+    let addresses: Vec<SocketAddr> = hosts.iter().map(|i| i.socket_addr(0)).collect();
 
-    if th_debug == true {
-      let port_range: (u16, u16) = (ports[0], ports[ports.len() as usize -1]);
+    let (macs, remaining) = arp_discovery(&addresses, rtt, limiter, binding, self.debug);
+    let mut alive: HashSet<IpAddr> = macs.keys().cloned().collect();
 
-      fmt::f_debug(format!("Starting thread with {:?} and will be scanning ports ",
-      thread::current().id()).as_str(), format!("[{}-{}]", port_range.0, port_range.1).as_str());
-    }
+    if remaining.len() > 0 {
+      let pinged = match raw_discovery(&remaining, &syn_ports, &ack_ports, rtt.clone(), limiter, binding, self.debug) {
+        Ok(s) => { s },
+        Err(e) => {
+          if self.debug == true {
+            fmt::f_debug("Raw pings are unavailable, using tcp connections instead", format!("{}", e).as_str());
+          }
 
-    // Ports are scanned here.
-    for i in ports {
-      address.set_port(i);
-      
-      match TcpStream::connect_timeout(&address, Duration::from_millis(th_timeout)) {
-        Ok(_) => {
-
-          // If a port was successfully found to be open, the thread will rescan the port just to be sure.
-          if let Ok(_) = Self::quick_scan(&address, th_timeout.clone()) {
-            
-            // A message is sent to the main thread containing the open port.
-            Self::thread_send_message(send.clone(), address.port(), String::new(), ThreadMessage::OpenPort);
-            
-            // This line sends a banner request to the main thread if it gets a response.
-            if let Some(data) = Self::get_banner(&address, th_timeout, th_debug, th_banner_len) {
-              if data.len() > 0 && th_banner_req == true {
-                Self::thread_send_message(send.clone(), address.port(), data, ThreadMessage::Banner);
-              }
+          let mut ports = syn_ports.clone();
+          for i in ack_ports {
+            if ports.contains(&i) == false {
+              ports.push(i);
             }
           }
 
-          else {
-            Self::thread_send_message(send.clone(), address.port(), String::new(), ThreadMessage::KeepAlive);
-          }
-        },
+          connect_discovery(&remaining, &ports, rtt.clone(), limiter.clone(), binding.clone(), self.concurrency)
+        }
+      };
 
-        Err(_) => {
-          Self::thread_send_message(send.clone(), address.port(), String::new(), ThreadMessage::KeepAlive);
+      alive.extend(pinged);
+    }
 
-          if th_verbose == true {
-            println!("{}: {}", style(format!("{}/tcp", address.port())).yellow().bright(), style("Closed").red().bright());
-          }
+    (hosts.into_iter().filter(|i| alive.contains(&i.ip)).collect(), macs)
+  }
+
+  /**Function probes every port of every host once with the scan type chosen by the user.
+   * Params:
+   *  &self
+   *  hosts:   &Vec<IpData>         {The structures that hold each ip address and the ports to be scanned}
+   *  results: &mut Vec<FileOutput> {The results for each host, in the same order as hosts}
+   *  limiter: &Arc<RateLimiter>    {Paces the probes}
+   *  rtt:     &Arc<RttTable>       {Measures each host and decides how long to wait for answers}
+   *  binding: &Arc<SourceBinding>  {The interface and address picked by the user}
+   * Returns nothing.
+   */
+  pub fn run_pass(&self, hosts: &Vec<IpData>, results: &mut Vec<FileOutput>, limiter: &Arc<RateLimiter>, rtt: &Arc<RttTable>, binding: &Arc<SourceBinding>) -> () {
+    if self.scan_type == ScanType::Syn {
+      self.init_syn_scan(hosts, results, limiter, rtt.clone(), binding);
+    }
+
+    else if self.scan_type == ScanType::Udp {
+      self.init_udp_scan(hosts, results, limiter.clone(), rtt.clone(), binding.clone());
+    }
+
+    else if self.concurrency == 0 || self.concurrency == 1 {
+      for (idx, ip) in hosts.iter().enumerate() {
+        for i in ip.ports.iter() {
+          limiter.wait();
+          self.standard_port_scan(ip.socket_addr(*i), &mut results[idx], rtt, binding);
         }
       }
     }
+
+    else {
+      self.init_engine(hosts, results, limiter.clone(), rtt.clone(), binding.clone());
+    }
   }
 
-  // pub fn display_port(port: u16) -> () {
-  //   if let Some(port_name) = service_map(port) {
-  //     println!("{}: {} - {}", style(format!("{}/tcp", port)).yellow().bright(), style("Open").green().bright(),
-  //     style(port_name).cyan());
-  //   }
+  /**function scans a port and displays whether the port was open or closed. 
+   * Params:
+   *  &self
+   *  address: SocketAddr      {The ip address and port that will be passed to the connect_timeout function}
+   *  target:  &mut FileOutput {The results for the host that owns the address}
+   *  rtt:     &RttTable       {Measures the host and decides how long to wait}
+   *  binding: &SourceBinding  {The interface and address picked by the user}
+   * Returns nothing.
+  */
+  pub fn standard_port_scan(&self, address: SocketAddr, target: &mut FileOutput, rtt: &RttTable, binding: &SourceBinding) -> () {
+    let started = std::time::Instant::now();
+    let state = match binding.connect_timeout(&address, rtt.timeout(&address.ip())) {
+      Ok(_) =>  { PortState::Open },
+      Err(e) => { PortState::from_error(&e) }
+    };
 
-  //   else {
-  //     println!("{}: {}", style(format!("{}/tcp", port)).yellow().bright(), style("Open").green().bright())
-  //   };
-  // }
+    // Accepted and refused connections both tell us how far away the host is.
+    if state == PortState::Open || state == PortState::Closed {
+      rtt.sample(address.ip(), started.elapsed());
+    }
+
+    self.record_port(target, address, state);
+  }
 
-  /**Function sends messages from worker thread to the main thread via channels with open ports
+  /**Function checks if the user asked to see ports in the given state.
    * Params:
-   *  send:            Sender<String> {The sender channel used to send messages to the main thread}
-   *  port:            u16            {The port that was found}
-   *  data:            String         {The banner response}
-   *  thread_flag:     ThreadMessage  {The type of message to send to the main thread}
+   *  &self
+   *  state: &PortState {The state of the port}
+   * Returns bool.
+   */
+  pub fn show_state(&self, state: &PortState) -> bool {
+    self.show_states.contains(&StateFilter::All) || self.show_states.contains(&state.filter())
+  }
+
+  // Returns the state of a port that never answered, silent udp ports may still be open.
+  pub fn unanswered_state(&self) -> PortState {
+    match self.scan_type {
+      ScanType::Udp => { PortState::OpenFiltered }
+      _ =>             { PortState::Filtered }
+    }
+  }
+
+  /**Function records the state of a port on its host when the user asked to see that state.
+   * Params:
+   *  &self
+   *  target:  &mut FileOutput {The results for the host that owns the address}
+   *  address: SocketAddr      {The address and port that was scanned}
+   *  state:   PortState       {The state of the port}
    * Returns nothing.
    */
-  pub fn thread_send_message(send: Sender<String>, port: u16, data: String, thread_flag: ThreadMessage) -> () {
-    let mut msg = String::new();
+  pub fn record_port(&self, target: &mut FileOutput, address: SocketAddr, state: PortState) -> () {
+    // Probes that were never answered are kept aside until every retry has been made.
+    if state == self.unanswered_state() {
+      target.unanswered.push(address.port());
+      return;
+    }
 
-    // Send "PORT number" for open ports.
-    if thread_flag == ThreadMessage::OpenPort {
-      msg = format!("PORT[_]{}", port);
+    if self.verbose == true && state != PortState::Open {
+      println!("{}: {}", style(format!("{}/{}", address, target.protocol.to_lowercase())).yellow().bright(), style(state.name()).red().bright());
     }
 
-    // Send "BANNER number banner_response" for responses longer than 0 bytes.
-    else if thread_flag == ThreadMessage::Banner {
-      msg = format!("BANNER[_]{}[_]{}", port, data);
-      // println!("msg = {}", msg);
+    if state == PortState::Open {
+      target.candidates.push(address.port());
     }
-    
-    // Value is meaningless.
-    // This line is used to keep the channel alive.
-    else if thread_flag == ThreadMessage::KeepAlive {
-      msg.push('0');
+
+    if self.show_state(&state) == true {
+      target.ports.push(PortResult::new(address.port(), state));
     }
-    
-    // Sends a message to the main thread every 50ms.
-    match send.send_timeout(msg, Duration::from_millis(50)) {
-      Ok(_) => {},
-      Err(_) => {}
+  }
+
+  /**Function adds every address and port pair to a list of jobs and indexes each host so that
+   * results can be matched to the correct host.
+   * Params:
+   *  hosts: &Vec<IpData> {The structures that hold each ip address and the ports to be scanned}
+   * Returns (Vec<SocketAddr>, HashMap<SocketAddr, usize>) (The jobs and the index of each host with its port set to 0)
+   */
+  pub fn create_jobs(hosts: &Vec<IpData>) -> (Vec<SocketAddr>, HashMap<SocketAddr, usize>) {
+    let mut jobs: Vec<SocketAddr> = Default::default();
+
+    for ip in hosts.iter() {
+      for i in ip.ports.iter() {
+        jobs.push(ip.socket_addr(*i));
+      }
     }
+
+    (jobs, Self::index_hosts(hosts))
   }
 
+  /**Function indexes each host by its address so that results can be matched to the correct host.
+   * Params:
+   *  hosts: &Vec<IpData> {The structures that hold each ip address}
+   * Returns HashMap<SocketAddr, usize> (The index of each host with its port set to 0)
+   */
+  pub fn index_hosts(hosts: &Vec<IpData>) -> HashMap<SocketAddr, usize> {
+    let mut host_index: HashMap<SocketAddr, usize> = Default::default();
+
+    for (idx, ip) in hosts.iter().enumerate() {
+      host_index.insert(ip.socket_addr(0), idx);
+    }
+
+    host_index
+  }
 
-  /**Function does a quick scan of a single port number and returns whether it was a success or failure.
+  /**Function scans every host and port with tcp syn packets using raw sockets.
    * Params:
-   *  address: &SocketAddr {The ip address and port of the service}
-   *  timeout: u64         {The socket timeout}
-   * Returns Result<TcpStream, std::io::Error>
+   *  &self
+   *  hosts:   &Vec<IpData>         {The structures that hold each ip address and the ports to be scanned}
+   *  results: &mut Vec<FileOutput> {The results for each host, in the same order as hosts}
+   *  limiter: &RateLimiter         {Paces the syn packets}
+   *  rtt:     Arc<RttTable>        {Measures each host and decides how long to wait for replies}
+   *  binding: &SourceBinding       {The interface and address picked by the user}
+   * Returns nothing.
    */
-  pub fn quick_scan(address: &SocketAddr, timeout: u64) -> Result<TcpStream, std::io::Error> {
-    match TcpStream::connect_timeout(&address, Duration::from_millis(timeout*2)) {
-      Ok(s) => { Ok(s) },
-      Err(e) => { Err(e) }
+  pub fn init_syn_scan(&self, hosts: &Vec<IpData>, results: &mut Vec<FileOutput>, limiter: &RateLimiter, rtt: Arc<RttTable>, binding: &SourceBinding) -> () {
+    let (jobs, host_index) = Self::create_jobs(hosts);
+
+    let replies = match syn_scan(&jobs, rtt, self.debug, limiter, binding) {
+      Ok(s) => { s },
+      Err(e) => {
+        if e.kind() == ErrorKind::PermissionDenied {
+          fmt::f_error("SYN scans need raw socket access. Run as root or grant the binary CAP_NET_RAW", "", format!("{}", e).as_str());
+        }
+
+        else {
+          fmt::f_error("unable to start syn scan", "", format!("{}", e).as_str());
+        }
+
+        exit(1);
+      }
+    };
+
+    for address in jobs {
+      let mut key = address;
+      key.set_port(0);
+
+      let idx = match host_index.get(&key) {
+        Some(i) => { *i },
+        None =>    { continue; }
+      };
+
+      match replies.get(&address) {
+        Some(SynReply::SynAck) => {
+          self.record_port(&mut results[idx], address, PortState::Open);
+        },
+
+        Some(SynReply::Reset) => {
+          self.record_port(&mut results[idx], address, PortState::Closed);
+        },
+
+        None => {
+          self.record_port(&mut results[idx], address, PortState::Filtered);
+        }
+      }
     }
   }
 
-  /**Function makes a get request and returns the response.
+  /**Function scans every host and port with udp datagrams.
    * Params:
-   *  address:    &SocketAddr {The ip address and port of the service to contact}
-   *  timeout:    u64         {The socket timeout}
-   *  debug:      bool        {Shows debug messages}
-   *  banner_len: u32         {The max response length that the function will return}
-   * Returns Option<String>
+   *  &self
+   *  hosts:   &Vec<IpData>         {The structures that hold each ip address and the ports to be scanned}
+   *  results: &mut Vec<FileOutput> {The results for each host, in the same order as hosts}
+   *  limiter: Arc<RateLimiter>     {Paces the udp probes of every thread}
+   *  rtt:     Arc<RttTable>        {Measures each host and decides how long to wait for answers}
+   *  binding: Arc<SourceBinding>   {The interface and address picked by the user}
+   * Returns nothing.
    */
-  pub fn get_banner(address: &SocketAddr, timeout: u64, debug: bool, banner_len: u32) -> Option<String> {
-    let builder = reqwest::blocking::ClientBuilder::new();
-    let client_timeout = builder.timeout(Duration::from_millis(timeout));
-    let url = format!("http://{}:{}/", address.ip().to_string(), address.port());
-    let mut out = String::new();
+  pub fn init_udp_scan(&self, hosts: &Vec<IpData>, results: &mut Vec<FileOutput>, limiter: Arc<RateLimiter>, rtt: Arc<RttTable>, binding: Arc<SourceBinding>) -> () {
+    let (jobs, host_index) = Self::create_jobs(hosts);
+    let replies = udp_scan(&jobs, rtt, self.concurrency, self.debug, limiter, binding);
+
+    for address in jobs {
+      let mut key = address;
+      key.set_port(0);
+
+      let idx = match host_index.get(&key) {
+        Some(i) => { *i },
+        None =>    { continue; }
+      };
+
+      let (state, data) = match replies.get(&address) {
+        Some(s) => { s },
+        None =>    { continue; }
+      };
+
+      self.record_port(&mut results[idx], address, *state);
+
+      // The answer to our probe is kept as the banner of an open port.
+      if *state == PortState::Open && self.banner_grab == true && data.len() > 0 {
+        let banner = BannerResponse::from_bytes(address.port(), &data[..data.len().min(self.banner_len as usize)]);
+        results[idx].banner_response.push(banner);
+      }
+    }
+  }
 
-    match client_timeout.build() {
-      Ok(client) => {
-        match client.get(url.as_str()).send() {
-          Ok(s) => {
+  /**Function scans every host and port with the non blocking connect engine.
+   * Params:
+   *  &self
+   *  hosts:   &Vec<IpData>         {The structures that hold each ip address and the ports to be scanned}
+   *  results: &mut Vec<FileOutput> {The results for each host, in the same order as hosts}
+   *  limiter: Arc<RateLimiter>     {Paces the probes of every event loop}
+   *  rtt:     Arc<RttTable>        {Measures each host and decides how long to wait for each connection}
+   *  binding: Arc<SourceBinding>   {The interface and address picked by the user}
+   * Returns nothing.
+   */
+  pub fn init_engine(&self, hosts: &Vec<IpData>, results: &mut Vec<FileOutput>, limiter: Arc<RateLimiter>, rtt: Arc<RttTable>, binding: Arc<SourceBinding>) -> () {
+    let mut flags = Flags::new();
+    flags.set_flags(
+      self.debug.clone(), 
+      self.timeout.clone(), 
+      self.verbose.clone(), 
+      self.banner_grab.clone(),
+      self.banner_len.clone()
+    );
+
+    let (th_sender, main_recv) = unbounded::<ThreadMessage>();
+    let (jobs, host_index) = Self::create_jobs(hosts);
+    let total_jobs = jobs.len();
+
+    // Every connection in flight needs a file descriptor, so the open file limit caps the concurrency.
+    // Falling behind the minimum rate lets the event loops open up to twice as many connections.
+    let burst = limiter.burst();
+    let concurrency = raise_file_limit(self.concurrency as usize * burst) / burst;
+    if concurrency < self.concurrency as usize && (self.verbose == true || self.debug == true) {
+      println!("{}: the open file limit only allows {} connections in flight", style("Info").yellow().bright(), style(concurrency).cyan());
+    }
+
+    // Every address and port is added to a queue that the event loops pull from until it is empty.
+    let (job_sender, job_recv) = unbounded::<SocketAddr>();
+    for i in jobs {
+      let _ = job_sender.send(i);
+    }
+
+    drop(job_sender);
+
+    let worker_count = engine_threads(concurrency.min(total_jobs));
+    let slots = concurrency.div_ceil(worker_count);
+
+    if self.debug == true {
+      fmt::f_debug("Starting event loops", format!("{} with {} connections each", worker_count, slots).as_str());
+    }
+
+    let mut handles: Vec<std::thread::JoinHandle<()>> = Default::default();
+    for _ in 0..worker_count {
+      let th_jobs = job_recv.clone();
+      let c_flags = flags.clone();
+      let sender_clone = th_sender.clone();
+      let th_limiter = limiter.clone();
+      let th_rtt = rtt.clone();
+      let th_binding = binding.clone();
+
+      handles.push(thread::spawn(move || {
+        if let Err(e) = run_engine(th_jobs, slots, c_flags, th_limiter, th_rtt, th_binding, sender_clone.clone()) {
+          fmt::f_error("scan engine stopped", "", format!("{}", e).as_str());
+        }
+
+        let _ = sender_clone.send(ThreadMessage::Finished);
+      }));
+    }
+
+    // Only the workers hold a sender now, the channel disconnects if every worker is gone.
+    drop(th_sender);
+
+    self.receive_messages(main_recv, handles.len(), &host_index, results, total_jobs);
+    Self::join_workers(handles, self.debug);
+  }
+
+  /**Function grabs the banner of every open port once the scan has finished.
+   * Params:
+   *  &self
+   *  hosts:   &Vec<IpData>         {The structures that hold each ip address and the ports that were scanned}
+   *  results: &mut Vec<FileOutput> {The results for each host, in the same order as hosts}
+   *  binding: &Arc<SourceBinding>  {The interface and address picked by the user}
+   * Returns nothing.
+   */
+  pub fn init_banner_grab(&self, hosts: &Vec<IpData>, results: &mut Vec<FileOutput>, binding: &Arc<SourceBinding>) -> () {
+    let host_index = Self::index_hosts(hosts);
+    let (th_sender, main_recv) = unbounded::<ThreadMessage>();
+    let (job_sender, job_recv) = unbounded::<(SocketAddr, String)>();
+    let mut total_jobs: usize = 0;
+
+    // Web servers are fingerprinted instead of keeping their raw response, https ones and starttls upgrades need tls for that.
+    let config = inspection_config(false).ok();
+
+    // The candidates are used instead of the ports on display, so banners are grabbed even when open ports are hidden.
+    for (idx, ip) in hosts.iter().enumerate() {
+      for i in results[idx].candidates.iter() {
+        let _ = job_sender.send((ip.socket_addr(*i), ip.name.clone()));
+        total_jobs += 1;
+      }
+    }
+
+    drop(job_sender);
+
+    if total_jobs < 1 {
+      return;
+    }
+
+    let worker_count = (self.concurrency.max(1) as usize).min(MAX_BANNER_THREADS).min(total_jobs);
+    let mut handles: Vec<std::thread::JoinHandle<()>> = Default::default();
+
+    for _ in 0..worker_count {
+      let th_jobs = job_recv.clone();
+      let sender_clone = th_sender.clone();
+      let (timeout, debug, banner_len) = (Duration::from_millis(self.timeout), self.debug, self.banner_len as usize);
+      let th_binding = binding.clone();
+      let th_config = config.clone();
 
-            if let Ok(text) = s.text() {
-              if text.len() > 0 {
-                out.push_str(text.as_str());
+      handles.push(thread::spawn(move || {
+        while let Ok((address, name)) = th_jobs.recv() {
+          match grab_banner(&address, timeout, banner_len, &th_binding) {
+            Ok(data) => {
+              match fingerprint_http(&address, &name, &data, th_config.as_ref(), timeout, &th_binding) {
+                Some(fingerprint) => {
+                  let _ = sender_clone.send(ThreadMessage::Http(address, fingerprint));
+                },
+
+                None => {
+                  let protocol = inspect_protocol(&address, &data, timeout, &th_binding);
+                  let upgrade = starttls_protocol(&address, &data);
+
+                  if data.len() > 0 {
+                    let _ = sender_clone.send(ThreadMessage::Banner(address, data));
+                  }
+
+                  if let Some(info) = protocol {
+                    let _ = sender_clone.send(ThreadMessage::Protocol(address, info));
+                  }
+
+                  // Mail and directory services only show their certificate once the connection is upgraded.
+                  if let (Some(protocol), Some(config)) = (upgrade, th_config.as_ref()) {
+                    match starttls_handshake(&address, &name, protocol, config, timeout, &th_binding) {
+                      Ok(report) => {
+                        let _ = sender_clone.send(ThreadMessage::Tls(address, report));
+                      },
+
+                      Err(e) => {
+                        if debug == true {
+                          fmt::f_debug("No starttls handshake with", format!("{} - {}", address, e).as_str());
+                        }
+                      }
+                    }
+                  }
+                }
+              }
+            },
+
+            Err(e) => {
+              if debug == true {
+                fmt::f_error("unable to grab banner from", address.to_string().as_str(), format!("{}", e).as_str());
               }
             }
+          }
 
-          },
+          let _ = sender_clone.send(ThreadMessage::Progress(1));
+        }
+
+        let _ = sender_clone.send(ThreadMessage::Finished);
+      }));
+    }
+
+    drop(th_sender);
+
+    self.receive_messages(main_recv, handles.len(), &host_index, results, total_jobs);
+    Self::join_workers(handles, self.debug);
+  }
+
+  /**Function runs a tls handshake with every open port once the scan is done.
+   * Params:
+   *  &self
+   *  hosts:   &Vec<IpData>          {The structures that hold each ip address and the ports that were scanned}
+   *  results: &mut Vec<FileOutput>  {The results for each host, in the same order as hosts}
+   *  binding: &Arc<SourceBinding>   {The interface and address picked by the user}
+   * Returns nothing.
+   */
+  pub fn init_tls_inspection(&self, hosts: &Vec<IpData>, results: &mut Vec<FileOutput>, binding: &Arc<SourceBinding>) -> () {
+    let configs = match (inspection_config(true), inspection_config(false)) {
+      (Ok(s), Ok(n)) => { Arc::new((s, n)) },
+      (Err(e), _) | (_, Err(e)) => {
+        fmt::f_error("Unable to set up tls", "", format!("{}", e).as_str());
+        return;
+      }
+    };
+
+    let host_index = Self::index_hosts(hosts);
+    let (th_sender, main_recv) = unbounded::<ThreadMessage>();
+    let (job_sender, job_recv) = unbounded::<(SocketAddr, String)>();
+    let mut total_jobs: usize = 0;
+
+    // The candidates are used instead of the ports on display, so hidden open ports are still inspected.
+    for (idx, ip) in hosts.iter().enumerate() {
+      for i in results[idx].candidates.iter() {
+        let _ = job_sender.send((ip.socket_addr(*i), ip.name.clone()));
+        total_jobs += 1;
+      }
+    }
+
+    drop(job_sender);
+
+    if total_jobs < 1 {
+      return;
+    }
+
+    let worker_count = (self.concurrency.max(1) as usize).min(MAX_BANNER_THREADS).min(total_jobs);
+    let mut handles: Vec<std::thread::JoinHandle<()>> = Default::default();
+
+    for _ in 0..worker_count {
+      let th_jobs = job_recv.clone();
+      let sender_clone = th_sender.clone();
+      let (timeout, debug) = (Duration::from_millis(self.timeout), self.debug);
+      let th_configs = configs.clone();
+      let th_binding = binding.clone();
+
+      handles.push(thread::spawn(move || {
+        while let Ok((address, name)) = th_jobs.recv() {
+          match inspect_tls(&address, &name, &th_configs, timeout, &th_binding) {
+            Ok(report) => {
+              let _ = sender_clone.send(ThreadMessage::Tls(address, report));
+            },
 
-          Err(e) => {
-            if debug == true {
-              fmt::f_error("unable to get response from request", url.as_str(), format!("{}", e).as_str());
+            Err(e) => {
+              if debug == true {
+                fmt::f_debug("No tls handshake with", format!("{} - {}", address, e).as_str());
+              }
             }
           }
-        }  
-      },
 
-      Err(e) => {
-        if debug == true {
-          println!("{}: unable to build client request {}ms - {}", style("Error").red(), style(timeout).cyan(), style(e).red());
+          let _ = sender_clone.send(ThreadMessage::Progress(1));
         }
+
+        let _ = sender_clone.send(ThreadMessage::Finished);
+      }));
+    }
+
+    drop(th_sender);
+
+    self.receive_messages(main_recv, handles.len(), &host_index, results, total_jobs);
+    Self::join_workers(handles, self.debug);
+  }
+
+  /**Function enumerates the tls versions and cipher suites every open port accepts and flags the weak ones.
+   * Params:
+   *  &self
+   *  hosts:   &Vec<IpData>          {The structures that hold each ip address and the ports that were scanned}
+   *  results: &mut Vec<FileOutput>  {The results for each host, in the same order as hosts}
+   *  binding: &Arc<SourceBinding>   {The interface and address picked by the user}
+   * Returns nothing.
+   */
+  pub fn init_tls_audit(&self, hosts: &Vec<IpData>, results: &mut Vec<FileOutput>, binding: &Arc<SourceBinding>) -> () {
+    let host_index = Self::index_hosts(hosts);
+    let (th_sender, main_recv) = unbounded::<ThreadMessage>();
+    let (job_sender, job_recv) = unbounded::<(SocketAddr, String, Option<TlsReport>)>();
+    let mut total_jobs: usize = 0;
+
+    // The candidates are used instead of the ports on display, so hidden open ports are still audited.
+    for (idx, ip) in hosts.iter().enumerate() {
+      for i in results[idx].candidates.iter() {
+        // Certificates found through starttls are left out, the audit only speaks tls from the first byte.
+        let report = results[idx].tls.iter().find(|t| t.port == *i && t.starttls.is_none()).cloned();
+        let _ = job_sender.send((ip.socket_addr(*i), ip.name.clone(), report));
+        total_jobs += 1;
       }
     }
 
-    if out.len() > 0 {
-      let string_bytes = out.as_str();
-      let mut banner = String::new();
+    drop(job_sender);
+
+    if total_jobs < 1 {
+      return;
+    }
+
+    let worker_count = (self.concurrency.max(1) as usize).min(MAX_BANNER_THREADS).min(total_jobs);
+    let mut handles: Vec<std::thread::JoinHandle<()>> = Default::default();
+
+    for _ in 0..worker_count {
+      let th_jobs = job_recv.clone();
+      let sender_clone = th_sender.clone();
+      let (timeout, debug) = (Duration::from_millis(self.timeout), self.debug);
+      let th_binding = binding.clone();
+
+      handles.push(thread::spawn(move || {
+        while let Ok((address, name, report)) = th_jobs.recv() {
+          match audit_tls(&address, &name, report.as_ref(), timeout, &th_binding) {
+            Some(audit) => {
+              let _ = sender_clone.send(ThreadMessage::TlsAudit(address, audit));
+            },
+
+            None => {
+              if debug == true {
+                fmt::f_debug("No tls audit for", format!("{}", address).as_str());
+              }
+            }
+          }
 
-      let mut counter: u32 = 0;
-      for i in string_bytes.chars() {
-        if counter > banner_len {
-          break;  
+          let _ = sender_clone.send(ThreadMessage::Progress(1));
         }
 
-        banner.push(i);
-        counter += 1;
+        let _ = sender_clone.send(ThreadMessage::Finished);
+      }));
+    }
+
+    drop(th_sender);
+
+    self.receive_messages(main_recv, handles.len(), &host_index, results, total_jobs);
+    Self::join_workers(handles, self.debug);
+  }
+
+  /**Function reads the version detection probes from the file picked by the user or the built in set.
+   * Params:
+   *  &self
+   * Returns Arc<ProbeDatabase>
+   */
+  pub fn load_probes(&self) -> Arc<ProbeDatabase> {
+    let probes = match self.service_probes.as_ref() {
+      Some(path) => { ProbeDatabase::from_file(path) },
+      None =>       { ProbeDatabase::builtin() }
+    };
+
+    match probes {
+      Ok(s) => {
+        if self.debug == true {
+          fmt::f_debug("Loaded service probes", format!("{} probes, {} rules skipped", s.probes.len(), s.skipped).as_str());
+        }
+
+        Arc::new(s)
+      },
+
+      Err(e) => {
+        fmt::f_error("Invalid service probes", self.service_probes.clone().unwrap_or_default().as_str(), e.as_str());
+        exit(1);
       }
+    }
+  }
 
-      Some(banner)
+  /**Function identifies the service on every open port once the scan is done.
+   * Udp ports that never answered may still be open, so they are probed too.
+   * Params:
+   *  &self
+   *  hosts:   &Vec<IpData>          {The structures that hold each ip address and the ports that were scanned}
+   *  results: &mut Vec<FileOutput>  {The results for each host, in the same order as hosts}
+   *  probes:  &Arc<ProbeDatabase>   {The probes and the rules that recognise their answers}
+   *  binding: &Arc<SourceBinding>   {The interface and address picked by the user}
+   * Returns nothing.
+   */
+  pub fn init_service_detection(&self, hosts: &Vec<IpData>, results: &mut Vec<FileOutput>, probes: &Arc<ProbeDatabase>, binding: &Arc<SourceBinding>) -> () {
+    let host_index = Self::index_hosts(hosts);
+    let (th_sender, main_recv) = unbounded::<ThreadMessage>();
+    let (job_sender, job_recv) = unbounded::<SocketAddr>();
+    let udp = self.scan_type == ScanType::Udp;
+    let mut total_jobs: usize = 0;
+
+    // The candidates are used instead of the ports on display, so hidden states are still probed.
+    for (idx, ip) in hosts.iter().enumerate() {
+      for i in results[idx].candidates.iter() {
+        let _ = job_sender.send(ip.socket_addr(*i));
+        total_jobs += 1;
+      }
     }
 
-    else {
-      None
+    drop(job_sender);
+
+    if total_jobs < 1 {
+      return;
+    }
+
+    // Ports that answer with tls are probed again over tls with the same settings as the inspection.
+    let config = inspection_config(false).ok();
+
+    let worker_count = (self.concurrency.max(1) as usize).min(MAX_BANNER_THREADS).min(total_jobs);
+    let mut handles: Vec<std::thread::JoinHandle<()>> = Default::default();
+
+    for _ in 0..worker_count {
+      let th_jobs = job_recv.clone();
+      let sender_clone = th_sender.clone();
+      let timeout = Duration::from_millis(self.timeout);
+      let th_probes = probes.clone();
+      let th_binding = binding.clone();
+      let th_config = config.clone();
+
+      handles.push(thread::spawn(move || {
+        while let Ok(address) = th_jobs.recv() {
+          if let Some(info) = th_probes.detect(&address, udp, th_config.as_ref(), timeout, &th_binding) {
+            let _ = sender_clone.send(ThreadMessage::Service(address, info));
+          }
+
+          let _ = sender_clone.send(ThreadMessage::Progress(1));
+        }
+
+        let _ = sender_clone.send(ThreadMessage::Finished);
+      }));
     }
+
+    drop(th_sender);
+
+    self.receive_messages(main_recv, handles.len(), &host_index, results, total_jobs);
+    Self::join_workers(handles, self.debug);
   }
+
+  /**Function records the messages sent by the worker threads until every one of them has finished.
+   * Params:
+   *  &self
+   *  recv:       Receiver<ThreadMessage>     {The channel the workers send their results to}
+   *  workers:    usize                       {The number of workers that were started}
+   *  host_index: &HashMap<SocketAddr, usize> {The index of each host with its port set to 0}
+   *  results:    &mut Vec<FileOutput>        {The results for each host, in the same order as hosts}
+   *  total_jobs: usize                       {The number of probes the workers were given}
+   * Returns nothing.
+   */
+  pub fn receive_messages(&self, recv: Receiver<ThreadMessage>, workers: usize, host_index: &HashMap<SocketAddr, usize>, results: &mut Vec<FileOutput>, total_jobs: usize) -> () {
+    let mut found_ports: usize = 0;
+    let mut finished_probes: usize = 0;
+    let mut finished_workers: usize = 0;
+
+    // We wait for messages from the worker threads until every one of them has finished.
+    while finished_workers < workers {
+      let message = match recv.recv() {
+        Ok(s) => { s },
+        Err(_) => { break; }
+      };
+
+      match message {
+        ThreadMessage::OpenPort(address) => {
+          if let Some(idx) = Self::host_of(host_index, &address) {
+            if self.debug == true {
+              fmt::f_debug("added port to output vec", format!("{}", address).as_str());
+            }
+
+            self.record_port(&mut results[idx], address, PortState::Open);
+            found_ports += 1;
+            println!("{} port(s) found", style(found_ports).cyan());
+          }
+        },
+
+        ThreadMessage::Banner(address, data) => {
+          if let Some(idx) = Self::host_of(host_index, &address) {
+            let banner = BannerResponse::from_bytes(address.port(), &data);
+
+            if self.debug == true {
+              fmt::f_debug("Banner response found for port", format!("{} {}", address, banner.data).as_str());
+            }
+
+            results[idx].banner_response.push(banner);
+          }
+        },
+
+        ThreadMessage::Http(address, fingerprint) => {
+          if let Some(idx) = Self::host_of(host_index, &address) {
+            if self.debug == true {
+              fmt::f_debug("Web server found on port", format!("{} {}", address, fingerprint.status_line).as_str());
+            }
+
+            results[idx].banner_response.push(BannerResponse::from_http(address.port(), fingerprint));
+          }
+        },
+
+        ThreadMessage::Protocol(address, info) => {
+          if let Some(idx) = Self::host_of(host_index, &address) {
+            if self.debug == true {
+              fmt::f_debug("Parsed the handshake of port", format!("{} {}", address, info.name()).as_str());
+            }
+
+            // Services that stay quiet still get a banner entry to hold what the parser learned.
+            let banners = &mut results[idx].banner_response;
+            if banners.iter().any(|b| b.port == address.port()) == false {
+              banners.push(BannerResponse::from_bytes(address.port(), &[]));
+            }
+
+            if let Some(banner) = banners.iter_mut().find(|b| b.port == address.port()) {
+              banner.protocol = Some(info);
+            }
+          }
+        },
+
+        ThreadMessage::Service(address, info) => {
+          if let Some(idx) = Self::host_of(host_index, &address) {
+            if self.debug == true {
+              fmt::f_debug("Service identified on port", format!("{} {} {}", address, info.service, info.product.clone().unwrap_or_default()).as_str());
+            }
+
+            // A udp port whose service answered a probe is open, and is shown when it was hidden as open|filtered.
+            if results[idx].ports.iter().any(|p| p.port == address.port()) == false && self.show_state(&PortState::Open) == true {
+              results[idx].ports.push(PortResult::new(address.port(), PortState::Open));
+            }
+
+            if let Some(port) = results[idx].ports.iter_mut().find(|p| p.port == address.port()) {
+              if port.state == PortState::OpenFiltered {
+                port.state = PortState::Open;
+              }
+
+              port.service = Some(info);
+            }
+          }
+        },
+
+        ThreadMessage::Tls(address, report) => {
+          if let Some(idx) = Self::host_of(host_index, &address) {
+            if self.debug == true {
+              fmt::f_debug("Tls handshake completed with", format!("{} {} {}", address, report.version, report.cipher).as_str());
+            }
+
+            results[idx].tls.push(report);
+          }
+        },
+
+        ThreadMessage::TlsAudit(address, audit) => {
+          if let Some(idx) = Self::host_of(host_index, &address) {
+            if self.debug == true {
+              fmt::f_debug("Tls audit completed with", format!("{} {} findings", address, audit.findings.len()).as_str());
+            }
+
+            // Like version detection, an open port hidden by --show-states is not added back to hold the audit.
+            results[idx].add_tls_audit(address.port(), audit, self.show_state(&PortState::Open));
+          }
+        },
+
+        ThreadMessage::State(address, state) => {
+          if let Some(idx) = Self::host_of(host_index, &address) {
+            self.record_port(&mut results[idx], address, state);
+          }
+        },
+
+        ThreadMessage::Progress(count) => {
+          finished_probes += count;
+
+          if self.debug == true {
+            fmt::f_debug("Probes completed", format!("{}/{}", finished_probes, total_jobs).as_str());
+          }
+        },
+
+        ThreadMessage::Finished => {
+          finished_workers += 1;
+        }
+      }
+    }
+  }
+
+  /**Function joins every worker thread to the main thread.
+   * Params:
+   *  handles: Vec<JoinHandle<()>> {The handles of the worker threads}
+   *  debug:   bool                {Shows debug messages}
+   * Returns nothing.
+   */
+  pub fn join_workers(handles: Vec<std::thread::JoinHandle<()>>, debug: bool) -> () {
+    for i in handles {
+      
+      let id = i.thread().id();
+      if let Ok(_) = i.join() {
+        if debug == true {
+          fmt::f_debug("joined thread to main with id", format!("{:?}", id).as_str());
+        }
+      }
+    }
+  }
+
+  /**Function finds the host that owns an address.
+   * Params:
+   *  host_index: &HashMap<SocketAddr, usize> {The index of each host with its port set to 0}
+   *  address:    &SocketAddr                 {The address and port that was scanned}
+   * Returns Option<usize>
+   */
+  pub fn host_of(host_index: &HashMap<SocketAddr, usize>, address: &SocketAddr) -> Option<usize> {
+    let mut key = *address;
+    key.set_port(0);
+
+    host_index.get(&key).cloned()
+  }
+
+  // pub fn display_port(port: u16) -> () {
+  //   if let Some(port_name) = service_map(port) {
+  //     println!("{}: {} - {}", style(format!("{}/tcp", port)).yellow().bright(), style("Open").green().bright(),
+  //     style(port_name).cyan());
+  //   }
+
+  //   else {
+  //     println!("{}: {}", style(format!("{}/tcp", port)).yellow().bright(), style("Open").green().bright())
+  //   };
+  // }
 }
\ No newline at end of file
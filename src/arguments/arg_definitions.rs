@@ -1,10 +1,14 @@
-use serde::Serialize;
+use serde::{Serialize, Serializer};
+use std::io::ErrorKind;
+use std::net::{IpAddr, Ipv4Addr, SocketAddr, SocketAddrV4, SocketAddrV6};
+use super::{HttpFingerprint, ProtocolInfo, ServiceInfo, TlsAudit, TlsReport};
 
 // Module contains simple functions used for displaying different types of messages.
 pub mod fmt {
-  use crate::arguments::service_map;
+  use crate::arguments::{interface_mtu, service_map, Severity};
+  use pnet_datalink::NetworkInterface;
   use console::style;
-  use super::BannerResponse;
+  use super::{BannerResponse, FileOutput, PortResult, PortState, TlsAudit, TlsReport};
   use comfy_table::{Cell, Color};
 
   // Function prints errors in the format [Error: {message} {value} {enum}]
@@ -12,6 +16,13 @@ pub mod fmt {
     println!("{}: {} {} - {}", style("Error").red().bright(), msg, style(value).cyan(), style(error_enum).red());
   }
 
+  // Function prints errors in a string the user typed and points to the position of the problem.
+  pub fn f_spec_error(msg: &str, spec: &str, position: usize, reason: &str) -> () {
+    println!("{}: {} - {}", style("Error").red().bright(), msg, style(reason).red());
+    println!("    {}", style(spec).cyan());
+    println!("    {}{}", " ".repeat(position), style("^").red().bright());
+  }
+
   // Function prints dbg messages in the format [debug: {message} {value}]
   pub fn f_debug(msg: &str, value: &str) -> () {
     println!("{} {} {}", style("Debug =>").red().bright(), style(msg).yellow(), style(value).cyan());
@@ -19,33 +30,229 @@ pub mod fmt {
 
   // Function displays banners in format [{port} {content}]
   pub fn f_display_banner(banner: BannerResponse) -> () {
-    println!("Port: {}\nbanner: {}\n", style(banner.port).cyan(), style(banner.data).cyan());
+    let http = match banner.http {
+      Some(s) => { s },
+      None => {
+        println!("Port: {}\nbanner: {}", style(banner.port).cyan(), style(banner.data).cyan());
+
+        if let Some(protocol) = banner.protocol {
+          println!("{}:", protocol.name());
+
+          for (name, value) in protocol.details() {
+            println!("  {:<14}{}", format!("{}:", name), value);
+          }
+        }
+
+        println!("");
+        return;
+      }
+    };
+
+    println!("Port: {}\n{}: {}", style(banner.port).cyan(), http.scheme, style(&http.status_line).cyan());
+
+    for (name, value) in [("Server", &http.server), ("Powered by", &http.powered_by), ("Title", &http.title)] {
+      if let Some(value) = value {
+        println!("  {:<14}{}", format!("{}:", name), value);
+      }
+    }
+
+    for i in http.redirects.iter() {
+      println!("  {:<14}{}", "Redirect:", i);
+    }
+
+    println!("  {:<14}{} bytes of {}", "Content:", http.content_length, http.content_type.unwrap_or(String::from("unknown type")));
+
+    if let Some(hash) = http.favicon_hash {
+      println!("  {:<14}{}", "Favicon:", hash);
+    }
+
+    println!("");
+  }
+
+  /**Function displays the results for a single host.
+   * Params:
+   *  target:  &FileOutput {The results of the host}
+   *  banners: bool        {Displays banner responses when true}
+   * Returns nothing.
+   */
+  pub fn f_display_host(target: &FileOutput, banners: bool) -> () {
+    let mut name = target.host.address.clone();
+    if target.host.name != target.host.address {
+      name = format!("{} ({})", target.host.name, target.host.address);
+    }
+
+    println!("{} {}", style("Host:").yellow().bright(), style(name).cyan());
+    if let Some(mac) = target.host.mac.clone() {
+      let vendor = target.host.vendor.clone().unwrap_or(String::from("Unknown vendor"));
+      println!("{} {} ({})", style("MAC:").yellow().bright(), style(mac).cyan(), vendor);
+    }
+
+    f_display_port(&target.ports, target.protocol.to_lowercase().as_str());
+
+    if banners == true {
+      println!("");
+      for i in target.banner_response.clone() {
+        f_display_banner(i);
+      }
+    }
+
+    for i in target.tls.iter() {
+      f_display_tls(i, target.protocol.to_lowercase().as_str());
+    }
+
+    for i in target.ports.iter() {
+      if let Some(audit) = i.tls_audit.as_ref() {
+        f_display_tls_audit(i.port, audit, target.protocol.to_lowercase().as_str());
+      }
+    }
+  }
+
+  /**Function displays the tls versions and ciphers a port accepts, followed by every weakness found.
+   * Params:
+   *  port:     u16       {The port that was audited}
+   *  audit:    &TlsAudit {The results of the audit}
+   *  protocol: &str      {The protocol of the port}
+   * Returns nothing.
+   */
+  pub fn f_display_tls_audit(port: u16, audit: &TlsAudit, protocol: &str) -> () {
+    println!("\n{} {}", style("TLS audit:").yellow().bright(), style(format!("{}/{}", port, protocol)).cyan());
+
+    for i in audit.protocols.iter() {
+      println!("  {}", style(&i.version).yellow());
+
+      for cipher in i.ciphers.iter() {
+        println!("    {}", cipher);
+      }
+    }
+
+    for i in audit.findings.iter() {
+      let severity = match i.severity {
+        Severity::High =>   { style(i.severity.name()).red().bright() },
+        Severity::Medium => { style(i.severity.name()).yellow() },
+        Severity::Low =>    { style(i.severity.name()).cyan() }
+      };
+
+      println!("  [{}] {}", severity, i.issue);
+    }
+  }
+
+  /**Function displays what the tls handshake with a port told us, followed by each certificate of the chain.
+   * Params:
+   *  report:   &TlsReport {The results of the handshake}
+   *  protocol: &str       {The protocol of the port}
+   * Returns nothing.
+   */
+  pub fn f_display_tls(report: &TlsReport, protocol: &str) -> () {
+    let mut negotiated = format!("{} {}", report.version, report.cipher);
+    if let Some(alpn) = report.alpn.as_ref() {
+      negotiated.push_str(format!(" (alpn {})", alpn).as_str());
+    }
+
+    if let Some(protocol) = report.starttls.as_ref() {
+      negotiated.push_str(format!(" (starttls {})", protocol).as_str());
+    }
+
+    println!("\n{} {} {}", style("TLS:").yellow().bright(), style(format!("{}/{}", report.port, protocol)).cyan(), negotiated);
+
+    for (idx, i) in report.certificates.iter().enumerate() {
+      println!("  {}", style(format!("Certificate {}", idx)).yellow());
+      println!("    Subject:  {}", style(&i.subject).cyan());
+
+      if i.sans.len() > 0 {
+        println!("    SANs:     {}", i.sans.join(", "));
+      }
+
+      println!("    Issuer:   {}", i.issuer);
+      println!("    Valid:    {} to {}", i.not_before, i.not_after);
+      println!("    Key:      {} {} bits", i.key_type, i.key_bits);
+      println!("    SHA-256:  {}", i.sha256);
+    }
+  }
+
+  /**Function displays every network interface with its addresses, mtu and state in a table.
+   * Params:
+   *  interfaces: &Vec<NetworkInterface> {The interfaces of this machine}
+   * Returns nothing.
+   */
+  pub fn f_display_interfaces(interfaces: &Vec<NetworkInterface>) -> () {
+    let mut table = comfy_table::Table::new();
+    table.set_header(vec![
+      Cell::new("Interface").fg(Color::Red),
+      Cell::new("State").fg(Color::Red),
+      Cell::new("MAC").fg(Color::Red),
+      Cell::new("MTU").fg(Color::Red),
+      Cell::new("Addresses").fg(Color::Red)
+    ]);
+
+    for i in interfaces {
+      let state = match i.is_up() {
+        true =>  { Cell::new("up").fg(Color::Green) }
+        false => { Cell::new("down").fg(Color::Yellow) }
+      };
+
+      let mac = i.mac.map(|m| m.to_string()).unwrap_or_default();
+      let mtu = interface_mtu(i.name.as_str()).map(|m| m.to_string()).unwrap_or_default();
+      let addresses: Vec<String> = i.ips.iter().map(|a| a.to_string()).collect();
+
+      table.add_row(vec![
+        Cell::new(i.name.as_str()),
+        state,
+        Cell::new(mac),
+        Cell::new(mtu),
+        Cell::new(addresses.join("\n"))
+      ]);
+    }
+
+    println!("{table}");
   }
 
   /**Function displays ports in a nicely formatted table
    * Params:
-   *  ports: &Vec<u16> {The ports to be displayed}
+   *  ports:    &Vec<PortResult> {The ports and their states to be displayed}
+   *  protocol: &str             {The protocol the ports were scanned with}
    * Returns nothing.
    */
-  pub fn f_display_port(ports: &Vec<u16>) -> () {
+  pub fn f_display_port(ports: &Vec<PortResult>, protocol: &str) -> () {
+    // The product and version columns are only shown once version detection has identified a service.
+    let versions = ports.iter().any(|i| i.service.is_some());
+
     // Creates a new table and adds the header columns
     let mut table = comfy_table::Table::new();
-    table.set_header(vec![
+    let mut header = vec![
       Cell::new("Port").fg(Color::Red), 
       Cell::new("State").fg(Color::Red), 
       Cell::new("Service").fg(Color::Red)
-    ]);
+    ];
+
+    if versions == true {
+      header.push(Cell::new("Product").fg(Color::Red));
+      header.push(Cell::new("Version").fg(Color::Red));
+    }
+
+    table.set_header(header);
     
     let mut port_string = String::new();
     let mut state_string = String::new();
     let mut svc_string = String::new();
+    let mut product_string = String::new();
+    let mut version_string = String::new();
 
     // Forms the structure of each of column.
     for i in ports {
-      port_string.push_str(format!("{i}/tcp\n").as_str());
-      state_string.push_str("Open\n");
+      let port = i.port;
+      port_string.push_str(format!("{port}/{protocol}\n").as_str());
+      state_string.push_str(format!("{}\n", i.state.name()).as_str());
       
-      if let Some(result) = service_map(i.clone()) {
+      // The service found by version detection is shown over the one guessed from the port number.
+      if let Some(service) = i.service.as_ref() {
+        svc_string.push_str(service.service.as_str());
+        svc_string.push('\n');
+        product_string.push_str(format!("{}\n", service.product.clone().unwrap_or_default()).as_str());
+        version_string.push_str(format!("{}\n", service.display_version()).as_str());
+        continue;
+      }
+
+      if let Some(result) = service_map(port) {
         svc_string.push_str(result);
         svc_string.push('\n');
       }
@@ -53,19 +260,37 @@ pub mod fmt {
       else {
         svc_string.push('\n');
       }
+
+      product_string.push('\n');
+      version_string.push('\n');
     }
 
     // Pops the last newline off the end of each string.
     port_string.pop();
     state_string.pop();
     svc_string.pop();
+    product_string.pop();
+    version_string.pop();
+
+    // The state column is only green when every port in the table is open.
+    let mut state_color = Color::Green;
+    if ports.iter().any(|i| i.state != PortState::Open) {
+      state_color = Color::Yellow;
+    }
 
     // Adds the contents to the table.
-    table.add_row(vec![
+    let mut row = vec![
       Cell::new(port_string).fg(Color::Yellow),
-      Cell::new(state_string).fg(Color::Green),
+      Cell::new(state_string).fg(state_color),
       Cell::new(svc_string).fg(Color::DarkCyan)
-    ]);
+    ];
+
+    if versions == true {
+      row.push(Cell::new(product_string).fg(Color::Green));
+      row.push(Cell::new(version_string).fg(Color::Cyan));
+    }
+
+    table.add_row(row);
 
     println!("{table}");
   }
@@ -109,6 +334,80 @@ pub enum OperatingSystem {
   Unknown,
 }
 
+// Tells the code how ports should be probed.
+#[derive(Debug, Clone, Copy, PartialEq, clap::ValueEnum)]
+pub enum ScanType {
+  Connect,    // Completes the full tcp handshake using the operating system.
+  Syn,        // Sends raw syn packets and never completes the handshake.
+  Udp,        // Sends udp datagrams and listens for answers or icmp unreachable messages.
+}
+
+// Describes what we learned about a port.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum PortState {
+  Open,               // The target accepted the connection or answered the probe.
+  Closed,             // The target refused the connection.
+  Filtered,           // Nothing answered before the timeout or an icmp unreachable message came back.
+  OpenFiltered,       // A udp port stayed silent, it is either open or filtered.
+  Error(ErrorKind),   // The probe failed for any other reason.
+}
+
+impl PortState {
+
+  /**Function works out the state of a port from the error returned by a connection attempt.
+   * Params:
+   *  error: &std::io::Error {The error returned by the socket}
+   * Returns PortState.
+   */
+  pub fn from_error(error: &std::io::Error) -> PortState {
+    match error.kind() {
+      ErrorKind::ConnectionRefused =>                 { PortState::Closed }
+      ErrorKind::TimedOut | ErrorKind::WouldBlock =>  { PortState::Filtered }
+      kind =>                                         { PortState::Error(kind) }
+    }
+  }
+
+  // Returns the state as it is displayed to the user and written to the json output.
+  pub fn name(&self) -> String {
+    match self {
+      PortState::Open =>          { String::from("open") }
+      PortState::Closed =>        { String::from("closed") }
+      PortState::Filtered =>      { String::from("filtered") }
+      PortState::OpenFiltered =>  { String::from("open|filtered") }
+      PortState::Error(kind) =>   { format!("error ({})", kind) }
+    }
+  }
+
+  // Returns the filter that shows or hides the state.
+  pub fn filter(&self) -> StateFilter {
+    match self {
+      PortState::Open =>          { StateFilter::Open }
+      PortState::Closed =>        { StateFilter::Closed }
+      PortState::Filtered =>      { StateFilter::Filtered }
+      PortState::OpenFiltered =>  { StateFilter::OpenFiltered }
+      PortState::Error(_) =>      { StateFilter::Error }
+    }
+  }
+}
+
+// States are written to the json output the same way they are displayed.
+impl Serialize for PortState {
+  fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+    serializer.serialize_str(self.name().as_str())
+  }
+}
+
+// The port states the user can choose to show or hide.
+#[derive(Debug, Clone, Copy, PartialEq, clap::ValueEnum)]
+pub enum StateFilter {
+  Open,
+  Closed,
+  Filtered,
+  OpenFiltered,
+  Error,
+  All,        // Shows every state.
+}
+
 // Stores the application settings.
 #[derive(Debug, Clone)]
 pub struct ArgumentSettings {
@@ -142,25 +441,104 @@ impl ArgumentSettings {
   }
 }
 
-// Tells the thread what message was received and how to deal with it.
+// Messages the worker threads send to the main thread.
 #[derive(Debug, Clone, PartialEq)]
 pub enum ThreadMessage {
-  OpenPort,
-  Banner,
-  KeepAlive,
+  OpenPort(SocketAddr),
+  Banner(SocketAddr, Vec<u8>),      // The raw response to the banner grab.
+  Http(SocketAddr, HttpFingerprint),  // What the web server on the port told us about itself.
+  Protocol(SocketAddr, ProtocolInfo), // What the protocol parser learned from the handshake of the service.
+  Service(SocketAddr, ServiceInfo), // What version detection learned about the service on the port.
+  Tls(SocketAddr, TlsReport),       // What the tls handshake with the port told us.
+  TlsAudit(SocketAddr, TlsAudit),   // The tls versions and ciphers the port accepts and what is weak about them.
+  State(SocketAddr, PortState),     // The port is not open, the message carries its state.
+  Progress(usize),                  // The number of probes finished since the last progress message.
+  Finished,                         // The worker has scanned every target it was given.
 }
 
+// Stores what a service sent us. The text is escaped so binary greetings are not mangled.
 #[derive(Debug, Clone, Serialize)]
 pub struct BannerResponse {
   pub port: u16,
-  pub data: String
+  pub data: String,       // The banner with every non printable byte escaped.
+  pub hex: String,        // The raw bytes of the banner as hex.
+
+  #[serde(skip_serializing_if = "Option::is_none")]
+  pub http: Option<HttpFingerprint>,  // Only filled in for web servers, which keep their status line as the banner.
+
+  #[serde(flatten, skip_serializing_if = "Option::is_none")]
+  pub protocol: Option<ProtocolInfo>, // Written under the name of the protocol, such as ssh or smb.
 }
 
 impl BannerResponse {
-  pub fn new() -> BannerResponse {
+
+  // Creates the banner from the raw bytes a service sent.
+  pub fn from_bytes(port: u16, data: &[u8]) -> BannerResponse {
     BannerResponse {
-      port: 0,
-      data: String::new(),
+      port,
+      data: super::escape_banner(data),
+      hex: super::hex_banner(data),
+      http: None,
+      protocol: None,
+    }
+  }
+
+  // Creates the banner of a web server from its fingerprint instead of the body of its response.
+  pub fn from_http(port: u16, fingerprint: HttpFingerprint) -> BannerResponse {
+    BannerResponse {
+      port,
+      data: fingerprint.status_line.clone(),
+      hex: super::hex_banner(fingerprint.status_line.as_bytes()),
+      http: Some(fingerprint),
+      protocol: None,
+    }
+  }
+}
+
+// Stores a scanned port and what we learned about it.
+#[derive(Debug, Clone, Serialize)]
+pub struct PortResult {
+  pub port: u16,
+  pub state: PortState,
+
+  #[serde(flatten, skip_serializing_if = "Option::is_none")]
+  pub service: Option<ServiceInfo>,     // Only known when version detection identified the service.
+
+  #[serde(skip_serializing_if = "Option::is_none")]
+  pub tls_audit: Option<TlsAudit>,      // Only filled in for ports that answered the tls audit.
+}
+
+impl PortResult {
+  pub fn new(port: u16, state: PortState) -> PortResult {
+    PortResult {
+      port,
+      state,
+      service: None,
+      tls_audit: None,
+    }
+  }
+}
+
+// Stores the target as it was typed by the user and the address that was actually scanned.
+#[derive(Debug, Clone, Serialize)]
+pub struct TargetHost {
+  pub name: String,
+  pub address: String,
+
+  #[serde(skip_serializing_if = "Option::is_none")]
+  pub mac: Option<String>,        // Only known for hosts found with arp.
+
+  #[serde(skip_serializing_if = "Option::is_none")]
+  pub vendor: Option<String>,     // The vendor that owns the first half of the mac address.
+}
+
+impl TargetHost {
+  pub fn new() -> TargetHost {
+    TargetHost {
+      name: String::new(),
+      address: String::new(),
+      mac: None,
+      vendor: None,
     }
   }
 }
@@ -168,52 +546,160 @@ impl BannerResponse {
 // Structure is used for writing output for json files.
 #[derive(Debug, Clone, Serialize)]
 pub struct FileOutput {
-  pub host: String,
+  pub host: TargetHost,
   pub ip: String,
   pub protocol: String,
-  pub ports: Vec<u16>,
-  pub banner_response: Vec<BannerResponse>
+  pub ports: Vec<PortResult>,
+  pub banner_response: Vec<BannerResponse>,
+
+  #[serde(skip_serializing_if = "Vec::is_empty")]
+  pub tls: Vec<TlsReport>,        // Only filled in for ports that completed a tls handshake.
+
+  #[serde(skip)]
+  pub unanswered: Vec<u16>,       // Ports that have not answered yet and may be probed again.
+
+  #[serde(skip)]
+  pub candidates: Vec<u16>,       // Ports worth version detection, kept even when --show-states hides them.
 }
 
 impl FileOutput {
   pub fn new() -> FileOutput {
     FileOutput {
-      host: String::new(),
+      host: TargetHost::new(),
       ip: String::from("V4"),
       protocol: String::from("TCP"), 
       ports: Default::default(),
       banner_response: Default::default(),
+      tls: Default::default(),
+      unanswered: Default::default(),
+      candidates: Default::default(),
+    }
+  }
+
+  // Creates an empty set of results for the address that is about to be scanned.
+  pub fn from_ip(ip: &IpData) -> FileOutput {
+    let mut out = FileOutput::new();
+    out.host.name = ip.name.clone();
+    out.host.address = ip.display_ip();
+    out.ip = String::from(ip.version());
+
+    out
+  }
+
+  // Returns true when at least one port on the host is open.
+  pub fn has_open_ports(&self) -> bool {
+    self.ports.iter().any(|i| i.state == PortState::Open)
+  }
+
+  /**Function attaches the results of a tls audit to its port.
+   * Only open ports are audited, so a port that was left out is added back when open ports are on display.
+   * Params:
+   *  &mut self
+   *  port:      u16      {The port that was audited}
+   *  audit:     TlsAudit {The results of the audit}
+   *  show_open: bool     {True when --show-states shows open ports}
+   * Returns nothing.
+   */
+  pub fn add_tls_audit(&mut self, port: u16, audit: TlsAudit, show_open: bool) -> () {
+    if self.ports.iter().any(|i| i.port == port) == false && show_open == true {
+      self.ports.push(PortResult::new(port, PortState::Open));
+    }
+
+    if let Some(result) = self.ports.iter_mut().find(|i| i.port == port) {
+      result.tls_audit = Some(audit);
     }
   }
-}
-// Used to determine how ports should be generated.
-#[derive(Debug, Clone, PartialEq)]
-pub enum Pattern {
-  Range,
-  Csv,
-  Single,
-  Unknown,
 }
 
 // Struct stores the ip address information and a vec
 // containing all the ports to be scanned.
 #[derive(Debug, Clone)]
 pub struct IpData {
-  pub a: u8,
-  pub b: u8,
-  pub c: u8,
-  pub d: u8,
+  pub name: String,         // The target as it was typed by the user.
+  pub ip: IpAddr,
+  pub scope_id: u32,        // The zone index of a scoped ipv6 address such as fe80::1%eth0.
   pub ports: Vec<u16>,
 }
 
 impl IpData {
   pub fn new() -> IpData {
     IpData {
-      a: 0,
-      b: 0,
-      c: 0,
-      d: 0,
+      name: String::new(),
+      ip: IpAddr::V4(Ipv4Addr::UNSPECIFIED),
+      scope_id: 0,
       ports: Default::default(),
     }
   }
-}
\ No newline at end of file
+
+  /**Function builds the socket address used to connect to a port on the target.
+   * Params:
+   *  &self
+   *  port: u16 {The port number}
+   * Returns SocketAddr.
+   */
+  pub fn socket_addr(&self, port: u16) -> SocketAddr {
+    match self.ip {
+      IpAddr::V4(v4) => { SocketAddr::V4(SocketAddrV4::new(v4, port)) }
+      IpAddr::V6(v6) => { SocketAddr::V6(SocketAddrV6::new(v6, port, 0, self.scope_id)) }
+    }
+  }
+
+  // Returns the ip version as it is written to the json output.
+  pub fn version(&self) -> &'static str {
+    match self.ip {
+      IpAddr::V4(_) => { "V4" }
+      IpAddr::V6(_) => { "V6" }
+    }
+  }
+
+  // Returns the address as it should be displayed to the user, including the zone index if one was set.
+  pub fn display_ip(&self) -> String {
+    if self.ip.is_ipv6() && self.scope_id > 0 {
+      return format!("{}%{}", self.ip, self.scope_id);
+    }
+
+    self.ip.to_string()
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn add_tls_audit_attaches_to_shown_port() {
+    let mut out = FileOutput::new();
+    out.ports.push(PortResult::new(443, PortState::Open));
+    out.add_tls_audit(443, TlsAudit::default(), true);
+
+    assert_eq!(out.ports.len(), 1);
+    assert_eq!(out.ports[0].tls_audit, Some(TlsAudit::default()));
+  }
+
+  #[test]
+  fn add_tls_audit_adds_missing_open_port() {
+    let mut out = FileOutput::new();
+    out.candidates.push(443);
+    out.add_tls_audit(443, TlsAudit::default(), true);
+
+    let port = out.ports.iter().find(|i| i.port == 443).unwrap();
+    assert_eq!(port.state, PortState::Open);
+    assert_eq!(port.tls_audit, Some(TlsAudit::default()));
+
+    let json = serde_json::to_value(&out).unwrap();
+    assert!(json["ports"].as_array().unwrap().iter().any(|i| i["port"] == 443 && i.get("tls_audit").is_some()));
+  }
+
+  #[test]
+  fn add_tls_audit_respects_hidden_open_ports() {
+    // With --show-states closed the open port stays hidden, the same as it does for version detection.
+    let mut out = FileOutput::new();
+    out.ports.push(PortResult::new(22, PortState::Closed));
+    out.candidates.push(443);
+    out.add_tls_audit(443, TlsAudit::default(), false);
+
+    assert_eq!(out.ports.len(), 1);
+    assert_eq!(out.ports[0].port, 22);
+    assert_eq!(out.ports[0].tls_audit, None);
+  }
+}
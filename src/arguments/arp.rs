@@ -0,0 +1,307 @@
+use std::collections::{HashMap, HashSet};
+use std::io::{Error, ErrorKind};
+use std::net::{IpAddr, Ipv4Addr, SocketAddr};
+use std::sync::{Arc, Mutex, atomic::{AtomicBool, Ordering}};
+use std::thread;
+use std::time::{Duration, Instant};
+use pnet_datalink::{Channel, Config, MacAddr, NetworkInterface};
+use pnet_packet::{MutablePacket, Packet};
+use pnet_packet::arp::{ArpHardwareTypes, ArpOperations, ArpPacket, MutableArpPacket};
+use pnet_packet::ethernet::{EtherTypes, EthernetPacket, MutableEthernetPacket};
+use super::{RateLimiter, RttTable, SourceBinding};
+
+const ARP_FRAME_LEN: usize = 42;      // An ethernet header followed by an arp packet for ipv4.
+
+// An interface that is directly connected to the segment of a host and the address we send from.
+#[derive(Debug, Clone)]
+pub struct LocalSegment {
+  pub interface: NetworkInterface,
+  pub mac: MacAddr,
+  pub source: Ipv4Addr,
+}
+
+/**Function finds the interface that is directly connected to the segment an address is on.
+ * Addresses that belong to this machine are never treated as being on a segment.
+ * Params:
+ *  ip:         &Ipv4Addr              {The address of the host}
+ *  interfaces: &Vec<NetworkInterface> {Every interface on this machine}
+ * Returns Option<LocalSegment>
+ */
+pub fn local_segment(ip: &Ipv4Addr, interfaces: &Vec<NetworkInterface>) -> Option<LocalSegment> {
+  for i in interfaces {
+    if i.is_up() == false || i.is_loopback() == true {
+      continue;
+    }
+
+    let mac = match i.mac {
+      Some(s) if s != MacAddr::zero() => { s },
+      _ =>                               { continue; }
+    };
+
+    for net in i.ips.iter() {
+      if let IpAddr::V4(source) = net.ip() {
+        if source == *ip {
+          return None;
+        }
+
+        if net.prefix() < 32 && net.contains(IpAddr::V4(*ip)) == true {
+          return Some(LocalSegment { interface: i.clone(), mac, source });
+        }
+      }
+    }
+  }
+
+  None
+}
+
+/**Function builds an ethernet frame that asks every host on the segment who owns an address.
+ * Params:
+ *  segment: &LocalSegment {The interface the request is sent from}
+ *  target:  Ipv4Addr      {The address we want the mac address of}
+ * Returns Vec<u8>
+ */
+pub fn arp_request(segment: &LocalSegment, target: Ipv4Addr) -> Vec<u8> {
+  let mut out = vec![0u8; ARP_FRAME_LEN];
+
+  if let Some(mut ethernet) = MutableEthernetPacket::new(&mut out) {
+    ethernet.set_destination(MacAddr::broadcast());
+    ethernet.set_source(segment.mac);
+    ethernet.set_ethertype(EtherTypes::Arp);
+
+    if let Some(mut arp) = MutableArpPacket::new(ethernet.payload_mut()) {
+      arp.set_hardware_type(ArpHardwareTypes::Ethernet);
+      arp.set_protocol_type(EtherTypes::Ipv4);
+      arp.set_hw_addr_len(6);
+      arp.set_proto_addr_len(4);
+      arp.set_operation(ArpOperations::Request);
+      arp.set_sender_hw_addr(segment.mac);
+      arp.set_sender_proto_addr(segment.source);
+      arp.set_target_hw_addr(MacAddr::zero());
+      arp.set_target_proto_addr(target);
+    }
+  }
+
+  out
+}
+
+/**Function sends an arp request to each address on one segment and records the mac address of every host that answers.
+ * Params:
+ *  segment: &LocalSegment  {The interface connected to the segment}
+ *  targets: &Vec<Ipv4Addr> {The addresses on the segment}
+ *  rtt:     &Arc<RttTable> {Measures each host and decides how long to wait for replies}
+ *  limiter: &RateLimiter   {Paces the requests}
+ * Returns Result<HashMap<Ipv4Addr, MacAddr>, Error>
+ */
+pub fn arp_sweep(segment: &LocalSegment, targets: &Vec<Ipv4Addr>, rtt: &Arc<RttTable>, limiter: &RateLimiter) -> Result<HashMap<Ipv4Addr, MacAddr>, Error> {
+  // Replies are sent straight to our mac address, so the interface is left out of promiscuous mode.
+  let config = Config { read_timeout: Some(Duration::from_millis(50)), promiscuous: false, ..Default::default() };
+
+  let (mut tx, mut rx) = match pnet_datalink::channel(&segment.interface, config)? {
+    Channel::Ethernet(tx, rx) => { (tx, rx) },
+    _ => { return Err(Error::new(ErrorKind::Unsupported, "the interface is not an ethernet interface")); }
+  };
+
+  let wanted: HashSet<Ipv4Addr> = targets.iter().cloned().collect();
+  let sent: Arc<Mutex<HashMap<Ipv4Addr, Instant>>> = Default::default();
+  let replies: Arc<Mutex<HashMap<Ipv4Addr, MacAddr>>> = Default::default();
+  let running = Arc::new(AtomicBool::new(true));
+
+  let th_sent = sent.clone();
+  let th_replies = replies.clone();
+  let th_running = running.clone();
+  let th_rtt = rtt.clone();
+  let source = segment.source;
+
+  let handle = thread::spawn(move || {
+    while th_running.load(Ordering::Relaxed) == true {
+      let frame = match rx.next() {
+        Ok(s) => { s },
+        Err(_) => { continue; }
+      };
+
+      let ethernet = match EthernetPacket::new(frame) {
+        Some(s) if s.get_ethertype() == EtherTypes::Arp => { s },
+        _ => { continue; }
+      };
+
+      if let Some(arp) = ArpPacket::new(ethernet.payload()) {
+        let ip = arp.get_sender_proto_addr();
+        if arp.get_operation() != ArpOperations::Reply || arp.get_target_proto_addr() != source || wanted.contains(&ip) == false {
+          continue;
+        }
+
+        let mut is_first = false;
+        if let Ok(mut replies) = th_replies.lock() {
+          is_first = replies.insert(ip, arp.get_sender_hw_addr()).is_none();
+        }
+
+        if is_first == true {
+          if let Some(started) = th_sent.lock().ok().and_then(|i| i.get(&ip).cloned()) {
+            th_rtt.sample(IpAddr::V4(ip), started.elapsed());
+          }
+        }
+      }
+    }
+  });
+
+  for ip in targets {
+    limiter.wait();
+
+    if let Ok(mut sent) = sent.lock() {
+      sent.insert(*ip, Instant::now());
+    }
+
+    if let Some(Err(e)) = tx.send_to(&arp_request(segment, *ip), None) {
+      running.store(false, Ordering::Relaxed);
+      let _ = handle.join();
+      return Err(e);
+    }
+  }
+
+  // Replies are collected until every host answered or the slowest host runs out of time.
+  let timeout = targets.iter().map(|i| rtt.timeout(&IpAddr::V4(*i))).max().unwrap_or_default();
+  let wait_start = Instant::now();
+
+  while wait_start.elapsed() < timeout {
+    if let Ok(replies) = replies.lock() {
+      if replies.len() >= targets.len() {
+        break;
+      }
+    }
+
+    thread::sleep(Duration::from_millis(10));
+  }
+
+  running.store(false, Ordering::Relaxed);
+  let _ = handle.join();
+
+  let out = match replies.lock() {
+    Ok(s) => { s.clone() },
+    Err(_) => { Default::default() }
+  };
+
+  Ok(out)
+}
+
+/**Function sweeps every ipv4 host that is on a directly connected segment with arp.
+ * Hosts on a segment that do not answer are down. Every other host is returned so it can be pinged instead.
+ * Params:
+ *  hosts:   &Vec<SocketAddr> {The address of every host}
+ *  rtt:     &Arc<RttTable>   {Measures each host and decides how long to wait for replies}
+ *  limiter: &RateLimiter     {Paces the requests}
+ *  binding: &SourceBinding   {The interface and address picked by the user}
+ *  debug:   bool             {Shows debug messages}
+ * Returns (HashMap<IpAddr, MacAddr>, Vec<SocketAddr>) (The mac address of each host that answered and the hosts arp could not check)
+ */
+pub fn arp_discovery(hosts: &Vec<SocketAddr>, rtt: &Arc<RttTable>, limiter: &RateLimiter, binding: &SourceBinding, debug: bool) -> (HashMap<IpAddr, MacAddr>, Vec<SocketAddr>) {
+  // Only the segment of the interface picked by the user is swept.
+  let interfaces = match binding.interface.clone() {
+    Some(s) => { vec![s] },
+    None =>    { pnet_datalink::interfaces() }
+  };
+
+  let mut segments: HashMap<String, (LocalSegment, Vec<SocketAddr>)> = Default::default();
+  let mut remaining: Vec<SocketAddr> = Default::default();
+
+  for i in hosts {
+    let segment = match i.ip() {
+      IpAddr::V4(v4) => { local_segment(&v4, &interfaces) },
+      IpAddr::V6(_) =>  { None }
+    };
+
+    match segment {
+      Some(mut s) => {
+        if let Some(IpAddr::V4(source)) = binding.source_ip {
+          if s.interface.ips.iter().any(|i| i.ip() == IpAddr::V4(source)) == true {
+            s.source = source;
+          }
+        }
+
+        segments.entry(s.interface.name.clone()).or_insert((s, Default::default())).1.push(*i);
+      },
+
+      None => { remaining.push(*i); }
+    }
+  }
+
+  let mut out: HashMap<IpAddr, MacAddr> = Default::default();
+  for (name, (segment, addresses)) in segments {
+    let targets: Vec<Ipv4Addr> = addresses.iter().filter_map(|i| match i.ip() { IpAddr::V4(v4) => Some(v4), _ => None }).collect();
+
+    match arp_sweep(&segment, &targets, rtt, limiter) {
+      Ok(s) => {
+        for (ip, mac) in s {
+          out.insert(IpAddr::V4(ip), mac);
+        }
+      },
+
+      Err(e) => {
+        if debug == true {
+          super::fmt::f_debug("Unable to send arp requests on", format!("{} - {}", name, e).as_str());
+        }
+
+        remaining.extend(addresses);
+      }
+    }
+  }
+
+  (out, remaining)
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  const MAC: MacAddr = MacAddr(0x02, 0x00, 0x00, 0x00, 0x00, 0x01);
+
+  fn interface(name: &str, mac: Option<MacAddr>, ips: &[&str], flags: u32) -> NetworkInterface {
+    NetworkInterface {
+      name: name.to_string(),
+      description: String::new(),
+      index: 1,
+      mac,
+      ips: ips.iter().map(|i| i.parse().unwrap()).collect(),
+      flags: flags as _,
+    }
+  }
+
+  #[test]
+  fn arp_request_layout() {
+    let segment = LocalSegment { interface: interface("eth0", Some(MAC), &[], 0), mac: MAC, source: Ipv4Addr::new(192, 168, 1, 2) };
+    let frame = arp_request(&segment, Ipv4Addr::new(192, 168, 1, 20));
+    assert_eq!(frame.len(), ARP_FRAME_LEN);
+
+    let ethernet = EthernetPacket::new(&frame).unwrap();
+    assert_eq!(ethernet.get_destination(), MacAddr::broadcast());
+    assert_eq!(ethernet.get_source(), MAC);
+    assert_eq!(ethernet.get_ethertype().0, 0x0806);
+
+    let arp = ArpPacket::new(ethernet.payload()).unwrap();
+    assert_eq!(arp.get_operation().0, 1);
+    assert_eq!(arp.get_sender_hw_addr(), MAC);
+    assert_eq!(arp.get_sender_proto_addr(), Ipv4Addr::new(192, 168, 1, 2));
+    assert_eq!(arp.get_target_hw_addr(), MacAddr::zero());
+    assert_eq!(arp.get_target_proto_addr(), Ipv4Addr::new(192, 168, 1, 20));
+  }
+
+  #[cfg(unix)]
+  #[test]
+  fn local_segment_picks_connected_interface() {
+    let up = libc::IFF_UP as u32;
+    let interfaces = vec![
+      interface("lo", Some(MacAddr::zero()), &["127.0.0.1/8"], up | libc::IFF_LOOPBACK as u32),
+      interface("eth1", Some(MAC), &["10.0.0.2/24"], 0),
+      interface("eth0", Some(MAC), &["192.168.1.2/24"], up),
+    ];
+
+    let segment = local_segment(&Ipv4Addr::new(192, 168, 1, 20), &interfaces).unwrap();
+    assert_eq!(segment.interface.name, "eth0");
+    assert_eq!(segment.source, Ipv4Addr::new(192, 168, 1, 2));
+
+    // Interfaces that are down, loopback addresses, our own address and other networks are not on a segment.
+    assert!(local_segment(&Ipv4Addr::new(10, 0, 0, 20), &interfaces).is_none());
+    assert!(local_segment(&Ipv4Addr::new(127, 0, 0, 2), &interfaces).is_none());
+    assert!(local_segment(&Ipv4Addr::new(192, 168, 1, 2), &interfaces).is_none());
+    assert!(local_segment(&Ipv4Addr::new(172, 16, 0, 1), &interfaces).is_none());
+  }
+}
@@ -0,0 +1,142 @@
+use std::io::{Read, Write};
+use std::net::{SocketAddr, TcpStream};
+use std::time::Duration;
+use super::SourceBinding;
+
+const REST_WAIT: Duration = Duration::from_millis(150);     // How long to wait for the rest of a banner once it starts arriving.
+
+/**Function returns the bytes sent to a service that did not greet us first.
+ * Most quiet services speak http, so every port without a better nudge gets a get request.
+ * Params:
+ *  address: &SocketAddr {The address and port of the service}
+ * Returns Vec<u8>
+ */
+pub fn banner_nudge(address: &SocketAddr) -> Vec<u8> {
+  match address.port() {
+    6379 =>  { b"PING\r\n".to_vec() }
+    11211 => { b"version\r\n".to_vec() }
+    _ => {
+      format!("GET / HTTP/1.0\r\nHost: {}\r\nUser-Agent: lethal_potato\r\nAccept: */*\r\n\r\n", address).into_bytes()
+    }
+  }
+}
+
+/**Function reads what the service sends until it stops talking or the banner is long enough.
+ * Params:
+ *  stream:     &mut TcpStream {The connection to the service}
+ *  wait:       Duration       {How long to wait for the first bytes}
+ *  banner_len: usize          {The most bytes to keep}
+ * Returns Vec<u8>
+ */
+pub fn read_banner(stream: &mut TcpStream, wait: Duration, banner_len: usize) -> Vec<u8> {
+  let mut out: Vec<u8> = Default::default();
+  let mut buffer = [0u8; 4096];
+
+  if stream.set_read_timeout(Some(wait.max(Duration::from_millis(1)))).is_err() {
+    return out;
+  }
+
+  while out.len() < banner_len {
+    match stream.read(&mut buffer) {
+      Ok(0) => { break; },
+      Ok(size) => {
+        out.extend_from_slice(&buffer[..size]);
+
+        // Banners often arrive in more than one segment, but once the first one is here the rest follows quickly.
+        if stream.set_read_timeout(Some(REST_WAIT)).is_err() {
+          break;
+        }
+      },
+
+      Err(_) => { break; }
+    }
+  }
+
+  out.truncate(banner_len);
+  out
+}
+
+/**Function grabs the banner of a service over a plain tcp connection.
+ * Services that talk first such as ssh, smtp, ftp and mysql are given time to send their greeting. When nothing
+ * arrives a nudge that suits the port is sent and the answer is kept instead.
+ * Params:
+ *  address:    &SocketAddr    {The address and port of the service}
+ *  timeout:    Duration       {How long to wait for the connection and for each answer}
+ *  banner_len: usize          {The most bytes to keep}
+ *  binding:    &SourceBinding {The interface and address picked by the user}
+ * Returns Result<Vec<u8>, std::io::Error> (The raw bytes sent by the service)
+ */
+pub fn grab_banner(address: &SocketAddr, timeout: Duration, banner_len: usize, binding: &SourceBinding) -> Result<Vec<u8>, std::io::Error> {
+  let mut stream = binding.connect_timeout(address, timeout)?;
+
+  let greeting = read_banner(&mut stream, timeout, banner_len);
+  if !greeting.is_empty() {
+    return Ok(greeting);
+  }
+
+  stream.set_write_timeout(Some(timeout.max(Duration::from_millis(1))))?;
+  stream.write_all(&banner_nudge(address))?;
+
+  Ok(read_banner(&mut stream, timeout, banner_len))
+}
+
+/**Function turns raw bytes into printable text. Printable ascii is kept as it is and every other byte is escaped.
+ * Params:
+ *  data: &[u8] {The raw bytes}
+ * Returns String
+ */
+pub fn escape_banner(data: &[u8]) -> String {
+  let mut out = String::new();
+
+  for i in data {
+    match *i {
+      b'\r' =>        { out.push_str("\\r"); },
+      b'\n' =>        { out.push_str("\\n"); },
+      b'\t' =>        { out.push_str("\\t"); },
+      b'\\' =>        { out.push_str("\\\\"); },
+      0x20..=0x7e =>  { out.push(*i as char); },
+      _ =>            { out.push_str(format!("\\x{:02x}", i).as_str()); }
+    }
+  }
+
+  out
+}
+
+// Returns the bytes as a lowercase hex string.
+pub fn hex_banner(data: &[u8]) -> String {
+  data.iter().map(|i| format!("{:02x}", i)).collect()
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn escape_banner_escapes_control_bytes() {
+    assert_eq!(escape_banner(b"SSH-2.0-OpenSSH_9.6\r\n"), "SSH-2.0-OpenSSH_9.6\\r\\n");
+    assert_eq!(escape_banner(b"a\tb\\c"), "a\\tb\\\\c");
+    assert_eq!(escape_banner(b" ~Az09"), " ~Az09");
+    assert_eq!(escape_banner(&[0x00, 0x1f, 0x7f, 0xff]), "\\x00\\x1f\\x7f\\xff");
+    assert_eq!(escape_banner(&[]), "");
+  }
+
+  #[test]
+  fn hex_banner_is_lowercase() {
+    assert_eq!(hex_banner(&[0x00, 0x0a, 0xab, 0xff]), "000aabff");
+    assert_eq!(hex_banner(&[]), "");
+  }
+
+  #[test]
+  fn banner_nudge_suits_the_port() {
+    assert_eq!(banner_nudge(&"192.0.2.10:6379".parse().unwrap()), b"PING\r\n".to_vec());
+    assert_eq!(banner_nudge(&"192.0.2.10:11211".parse().unwrap()), b"version\r\n".to_vec());
+
+    let nudge = String::from_utf8(banner_nudge(&"192.0.2.10:8080".parse().unwrap())).unwrap();
+    assert!(nudge.starts_with("GET / HTTP/1.0\r\n"));
+    assert!(nudge.contains("\r\nHost: 192.0.2.10:8080\r\n"));
+    assert!(nudge.ends_with("\r\n\r\n"));
+
+    let nudge = String::from_utf8(banner_nudge(&"[2001:db8::1]:80".parse().unwrap())).unwrap();
+    assert!(nudge.contains("\r\nHost: [2001:db8::1]:80\r\n"));
+  }
+}
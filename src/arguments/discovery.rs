@@ -0,0 +1,498 @@
+use std::collections::{HashMap, HashSet};
+use std::io::{Error, ErrorKind};
+use std::net::{IpAddr, SocketAddr};
+use std::sync::{Arc, Mutex, atomic::{AtomicBool, Ordering}};
+use std::thread;
+use std::time::{Duration, Instant};
+use crossbeam::channel::unbounded;
+use pnet_packet::Packet;
+use pnet_packet::icmp::{self, IcmpPacket};
+use pnet_packet::icmpv6::{self, Icmpv6Packet};
+use pnet_packet::ip::IpNextHeaderProtocols;
+use pnet_packet::tcp::{TcpFlags, TcpPacket};
+use pnet_transport::{transport_channel, TransportChannelType, TransportProtocol, TransportSender, TransportReceiver};
+use rand::Rng;
+use super::syn_scan::{open_channel, send_tcp_packet, source_address};
+use super::{RateLimiter, RttTable, SourceBinding};
+
+const ICMP_ECHO_REQUEST: u8 = 8;
+const ICMP_ECHO_REPLY: u8 = 0;
+const ICMP_TIMESTAMP_REQUEST: u8 = 13;
+const ICMP_TIMESTAMP_REPLY: u8 = 14;
+const ICMPV6_ECHO_REQUEST: u8 = 128;
+const ICMPV6_ECHO_REPLY: u8 = 129;
+const MAX_PING_THREADS: usize = 256;     // Connect pings block, so they run on a limited number of threads.
+pub const DEFAULT_PING_SYN_PORTS: &str = "443";
+pub const DEFAULT_PING_ACK_PORTS: &str = "80";
+
+/**Function decides if the hosts should be pinged before the port scan.
+ * A single host the user named is scanned straight away, since without raw sockets only a couple of ports can be
+ * pinged and a host that filters them would be skipped. Passing ping ports asks for discovery anyway.
+ * Params:
+ *  total_hosts:    usize {The number of hosts the targets expanded to}
+ *  skip_discovery: bool  {True when the user passed --skip-discovery}
+ *  explicit_ping:  bool  {True when the user picked the ports to ping}
+ * Returns bool
+ */
+pub fn wants_discovery(total_hosts: usize, skip_discovery: bool, explicit_ping: bool) -> bool {
+  if skip_discovery == true {
+    return false;
+  }
+
+  total_hosts > 1 || explicit_ping == true
+}
+
+// Stores the values that are shared between the thread sending pings and the threads receiving replies.
+#[derive(Clone)]
+pub struct PingSession {
+  pub identifier: u16,                              // Every icmp ping carries this identifier.
+  pub source_port: u16,                             // Every tcp ping is sent from this port.
+  pub targets: Arc<HashSet<IpAddr>>,                // Every host being pinged.
+  pub sent: Arc<Mutex<HashMap<IpAddr, Instant>>>,   // The time the first ping was sent to each host.
+  pub alive: Arc<Mutex<HashSet<IpAddr>>>,           // The hosts that answered so far.
+  pub rtt: Arc<RttTable>,                           // Measures the round trip time to each host.
+  pub running: Arc<AtomicBool>,
+}
+
+impl PingSession {
+
+  // Records a host as up and measures how long it took to answer.
+  pub fn mark_alive(&self, ip: IpAddr) -> () {
+    if self.targets.contains(&ip) == false {
+      return;
+    }
+
+    let mut is_first = false;
+    if let Ok(mut alive) = self.alive.lock() {
+      is_first = alive.insert(ip);
+    }
+
+    if is_first == true {
+      if let Some(sent) = self.sent.lock().ok().and_then(|i| i.get(&ip).cloned()) {
+        self.rtt.sample(ip, sent.elapsed());
+      }
+    }
+  }
+}
+
+/**Function builds an icmp echo or timestamp request.
+ * Params:
+ *  icmp_type:  u8  {ICMP_ECHO_REQUEST or ICMP_TIMESTAMP_REQUEST}
+ *  identifier: u16 {The identifier of the scan}
+ *  sequence:   u16 {The sequence number}
+ * Returns Vec<u8>
+ */
+pub fn icmp_request(icmp_type: u8, identifier: u16, sequence: u16) -> Vec<u8> {
+  let mut out = vec![icmp_type, 0, 0, 0];
+  out.extend_from_slice(&identifier.to_be_bytes());
+  out.extend_from_slice(&sequence.to_be_bytes());
+
+  // Timestamp requests carry the originate, receive and transmit timestamps.
+  if icmp_type == ICMP_TIMESTAMP_REQUEST {
+    out.extend_from_slice(&[0u8; 12]);
+  }
+
+  if let Some(packet) = IcmpPacket::new(&out) {
+    let checksum = icmp::checksum(&packet);
+    out[2..4].copy_from_slice(&checksum.to_be_bytes());
+  }
+
+  out
+}
+
+/**Function builds an icmpv6 echo request. The checksum covers the source and destination addresses.
+ * Params:
+ *  identifier: u16    {The identifier of the scan}
+ *  sequence:   u16    {The sequence number}
+ *  source:     IpAddr {The local address}
+ *  target:     IpAddr {The address of the host}
+ * Returns Vec<u8>
+ */
+pub fn icmpv6_request(identifier: u16, sequence: u16, source: IpAddr, target: IpAddr) -> Vec<u8> {
+  let mut out = vec![ICMPV6_ECHO_REQUEST, 0, 0, 0];
+  out.extend_from_slice(&identifier.to_be_bytes());
+  out.extend_from_slice(&sequence.to_be_bytes());
+
+  if let (IpAddr::V6(src), IpAddr::V6(dst), Some(packet)) = (source, target, Icmpv6Packet::new(&out)) {
+    let checksum = icmpv6::checksum(&packet, &src, &dst);
+    out[2..4].copy_from_slice(&checksum.to_be_bytes());
+  }
+
+  out
+}
+
+/**Function opens the raw socket used to send and receive icmp messages for one ip version.
+ * Params:
+ *  is_v6:   bool           {Opens an icmpv6 socket when true}
+ *  binding: &SourceBinding {The interface and address picked by the user}
+ * Returns Result<(TransportSender, TransportReceiver), Error>
+ */
+pub fn open_ping_channel(is_v6: bool, binding: &SourceBinding) -> Result<(TransportSender, TransportReceiver), Error> {
+  let channel_type = match is_v6 {
+    false => { TransportChannelType::Layer4(TransportProtocol::Ipv4(IpNextHeaderProtocols::Icmp)) }
+    true =>  { TransportChannelType::Layer4(TransportProtocol::Ipv6(IpNextHeaderProtocols::Icmpv6)) }
+  };
+
+  let (tx, rx) = transport_channel(65535, channel_type)?;
+  binding.bind_raw(&tx, is_v6)?;
+
+  Ok((tx, rx))
+}
+
+/**Function records every host that answers an icmp ping until discovery stops running.
+ * Params:
+ *  session:  PingSession       {The values shared by the discovery}
+ *  receiver: TransportReceiver {The raw icmp socket}
+ *  is_v6:    bool              {True when the socket receives icmpv6}
+ * Returns nothing.
+ */
+#[cfg(unix)]
+pub fn receive_icmp_replies(session: PingSession, mut receiver: TransportReceiver, is_v6: bool) -> () {
+  let poll_interval = Duration::from_millis(50);
+  let identifier = session.identifier.to_be_bytes();
+
+  if is_v6 == true {
+    let mut iter = pnet_transport::icmpv6_packet_iter(&mut receiver);
+
+    while session.running.load(Ordering::Relaxed) == true {
+      if let Ok(Some((packet, from))) = iter.next_with_timeout(poll_interval) {
+        if packet.get_icmpv6_type().0 == ICMPV6_ECHO_REPLY && packet.payload().starts_with(&identifier) {
+          session.mark_alive(from);
+        }
+      }
+    }
+  }
+
+  else {
+    let mut iter = pnet_transport::icmp_packet_iter(&mut receiver);
+
+    while session.running.load(Ordering::Relaxed) == true {
+      if let Ok(Some((packet, from))) = iter.next_with_timeout(poll_interval) {
+        let icmp_type = packet.get_icmp_type().0;
+
+        if (icmp_type == ICMP_ECHO_REPLY || icmp_type == ICMP_TIMESTAMP_REPLY) && packet.payload().starts_with(&identifier) {
+          session.mark_alive(from);
+        }
+      }
+    }
+  }
+}
+
+#[cfg(not(unix))]
+pub fn receive_icmp_replies(_session: PingSession, _receiver: TransportReceiver, _is_v6: bool) -> () {}
+
+/**Function records every host that answers a tcp ping until discovery stops running.
+ * Any answer, even a reset, means the host is up.
+ * Params:
+ *  session:  PingSession       {The values shared by the discovery}
+ *  receiver: TransportReceiver {The raw tcp socket}
+ *  is_v6:    bool              {True when the socket receives ipv6 packets}
+ * Returns nothing.
+ */
+#[cfg(unix)]
+pub fn receive_tcp_replies(session: PingSession, mut receiver: TransportReceiver, is_v6: bool) -> () {
+  let poll_interval = Duration::from_millis(50);
+
+  if is_v6 == true {
+    let mut iter = pnet_transport::tcp_packet_iter(&mut receiver);
+
+    while session.running.load(Ordering::Relaxed) == true {
+      if let Ok(Some((packet, from))) = iter.next_with_timeout(poll_interval) {
+        if packet.get_destination() == session.source_port {
+          session.mark_alive(from);
+        }
+      }
+    }
+  }
+
+  else {
+    let mut iter = pnet_transport::ipv4_packet_iter(&mut receiver);
+
+    while session.running.load(Ordering::Relaxed) == true {
+      if let Ok(Some((packet, from))) = iter.next_with_timeout(poll_interval) {
+        if packet.get_next_level_protocol() != IpNextHeaderProtocols::Tcp {
+          continue;
+        }
+
+        if let Some(tcp_packet) = TcpPacket::new(packet.payload()) {
+          if tcp_packet.get_destination() == session.source_port {
+            session.mark_alive(from);
+          }
+        }
+      }
+    }
+  }
+}
+
+#[cfg(not(unix))]
+pub fn receive_tcp_replies(_session: PingSession, _receiver: TransportReceiver, _is_v6: bool) -> () {}
+
+/**Function pings every host with icmp echo and timestamp requests and with tcp syn and ack packets.
+ * Needs raw socket access.
+ * Params:
+ *  hosts:     &Vec<SocketAddr> {The address of every host}
+ *  syn_ports: &Vec<u16>        {The ports that receive tcp syn pings}
+ *  ack_ports: &Vec<u16>        {The ports that receive tcp ack pings}
+ *  rtt:       Arc<RttTable>    {Measures each host and decides how long to wait for replies}
+ *  limiter:   &RateLimiter     {Paces the pings}
+ *  binding:   &SourceBinding   {The interface and address picked by the user}
+ *  debug:     bool             {Shows debug messages}
+ * Returns Result<HashSet<IpAddr>, Error> (The hosts that answered)
+ */
+pub fn raw_discovery(hosts: &Vec<SocketAddr>, syn_ports: &Vec<u16>, ack_ports: &Vec<u16>, rtt: Arc<RttTable>, limiter: &RateLimiter, binding: &SourceBinding, debug: bool) -> Result<HashSet<IpAddr>, Error> {
+  if cfg!(unix) == false {
+    return Err(Error::new(ErrorKind::Unsupported, "raw pings are only supported on unix systems"));
+  }
+
+  let mut rng = rand::thread_rng();
+  let mut sources: HashMap<IpAddr, IpAddr> = Default::default();
+
+  // The local address for each host is worked out before any packets are sent.
+  for i in hosts {
+    let mut route = *i;
+    route.set_port(syn_ports.first().cloned().unwrap_or(80));
+    sources.insert(i.ip(), source_address(&route, binding)?);
+  }
+
+  let session = PingSession {
+    identifier: rng.gen(),
+    source_port: rng.gen_range(40000..60000),
+    targets: Arc::new(hosts.iter().map(|i| i.ip()).collect()),
+    sent: Arc::new(Mutex::new(Default::default())),
+    alive: Arc::new(Mutex::new(Default::default())),
+    rtt,
+    running: Arc::new(AtomicBool::new(true)),
+  };
+
+  // An icmp and a tcp socket is opened for each ip version we need. Every socket is opened before any receiver
+  // starts, so a socket that fails to open leaves no thread behind.
+  let mut icmp_senders: HashMap<bool, TransportSender> = Default::default();
+  let mut tcp_senders: HashMap<bool, Mutex<TransportSender>> = Default::default();
+  let mut handles: Vec<thread::JoinHandle<()>> = Default::default();
+  let mut channels: Vec<(bool, TransportReceiver, TransportReceiver)> = Default::default();
+
+  for is_v6 in [false, true] {
+    if hosts.iter().any(|i| i.is_ipv6() == is_v6) == false {
+      continue;
+    }
+
+    let (icmp_tx, icmp_rx) = open_ping_channel(is_v6, binding)?;
+    let (tcp_tx, tcp_rx) = open_channel(is_v6, binding)?;
+
+    icmp_senders.insert(is_v6, icmp_tx);
+    tcp_senders.insert(is_v6, Mutex::new(tcp_tx));
+    channels.push((is_v6, icmp_rx, tcp_rx));
+  }
+
+  for (is_v6, icmp_rx, tcp_rx) in channels {
+    let th_session = session.clone();
+    handles.push(thread::spawn(move || {
+      receive_icmp_replies(th_session, icmp_rx, is_v6);
+    }));
+
+    let th_session = session.clone();
+    handles.push(thread::spawn(move || {
+      receive_tcp_replies(th_session, tcp_rx, is_v6);
+    }));
+  }
+
+  if debug == true {
+    super::fmt::f_debug("Sending pings with identifier", format!("{} from port {}", session.identifier, session.source_port).as_str());
+  }
+
+  for (sequence, i) in hosts.iter().enumerate() {
+    let ip = i.ip();
+    let source = sources[&ip];
+    let sequence = sequence as u16;
+
+    if let Ok(mut sent) = session.sent.lock() {
+      sent.insert(ip, Instant::now());
+    }
+
+    let mut errors: Vec<Error> = Default::default();
+    if let Some(tx) = icmp_senders.get_mut(&i.is_ipv6()) {
+      limiter.wait();
+
+      if i.is_ipv6() == true {
+        let request = icmpv6_request(session.identifier, sequence, source, ip);
+        if let Some(packet) = Icmpv6Packet::new(&request) {
+          if let Err(e) = tx.send_to(packet, ip) {
+            errors.push(e);
+          }
+        }
+      }
+
+      else {
+        for icmp_type in [ICMP_ECHO_REQUEST, ICMP_TIMESTAMP_REQUEST] {
+          let request = icmp_request(icmp_type, session.identifier, sequence);
+          if let Some(packet) = IcmpPacket::new(&request) {
+            if let Err(e) = tx.send_to(packet, ip) {
+              errors.push(e);
+            }
+          }
+        }
+      }
+    }
+
+    if let Some(tx) = tcp_senders.get(&i.is_ipv6()) {
+      for port in syn_ports {
+        limiter.wait();
+        if let Err(e) = send_tcp_packet(tx, source, session.source_port, (ip, *port), rng.gen(), 0, TcpFlags::SYN) {
+          errors.push(e);
+        }
+      }
+
+      for port in ack_ports {
+        limiter.wait();
+        if let Err(e) = send_tcp_packet(tx, source, session.source_port, (ip, *port), rng.gen(), rng.gen(), TcpFlags::ACK) {
+          errors.push(e);
+        }
+      }
+    }
+
+    if debug == true {
+      for e in errors {
+        super::fmt::f_error("unable to send ping", ip.to_string().as_str(), format!("{}", e).as_str());
+      }
+    }
+  }
+
+  // Replies are collected until every host answered or the slowest host runs out of time.
+  let wait_start = Instant::now();
+  while wait_start.elapsed() < session.targets.iter().map(|i| session.rtt.timeout(i)).max().unwrap_or_default() {
+    if let Ok(alive) = session.alive.lock() {
+      if alive.len() >= session.targets.len() {
+        break;
+      }
+    }
+
+    thread::sleep(Duration::from_millis(10));
+  }
+
+  session.running.store(false, Ordering::Relaxed);
+  for i in handles {
+    let _ = i.join();
+  }
+
+  let alive = match session.alive.lock() {
+    Ok(s) => { s.clone() },
+    Err(_) => { Default::default() }
+  };
+
+  Ok(alive)
+}
+
+/**Function tries to open a tcp connection to each port until the host answers.
+ * An accepted or refused connection both mean the host is up.
+ * Params:
+ *  host:    &SocketAddr    {The address of the host}
+ *  ports:   &Vec<u16>      {The ports to try}
+ *  rtt:     &RttTable      {Measures the host and decides how long to wait}
+ *  binding: &SourceBinding {The interface and address picked by the user}
+ * Returns bool.
+ */
+pub fn connect_ping(host: &SocketAddr, ports: &Vec<u16>, rtt: &RttTable, binding: &SourceBinding) -> bool {
+  for port in ports {
+    let mut address = *host;
+    address.set_port(*port);
+
+    let started = Instant::now();
+    match binding.connect_timeout(&address, rtt.timeout(&address.ip())) {
+      Ok(_) => {
+        rtt.sample(address.ip(), started.elapsed());
+        return true;
+      },
+
+      Err(e) => {
+        if e.kind() == ErrorKind::ConnectionRefused {
+          rtt.sample(address.ip(), started.elapsed());
+          return true;
+        }
+      }
+    }
+  }
+
+  false
+}
+
+/**Function pings every host with tcp connections. Used when raw sockets are not available.
+ * Params:
+ *  hosts:   &Vec<SocketAddr>   {The address of every host}
+ *  ports:   &Vec<u16>          {The ports to try on each host}
+ *  rtt:     Arc<RttTable>      {Measures each host and decides how long to wait}
+ *  limiter: Arc<RateLimiter>   {Paces the pings}
+ *  binding: Arc<SourceBinding> {The interface and address picked by the user}
+ *  threads: u32                {The number of hosts to ping at once}
+ * Returns HashSet<IpAddr> (The hosts that answered)
+ */
+pub fn connect_discovery(hosts: &Vec<SocketAddr>, ports: &[u16], rtt: Arc<RttTable>, limiter: Arc<RateLimiter>, binding: Arc<SourceBinding>, threads: u32) -> HashSet<IpAddr> {
+  let (job_sender, job_recv) = unbounded::<SocketAddr>();
+  let (result_sender, result_recv) = unbounded::<IpAddr>();
+
+  for i in hosts {
+    let _ = job_sender.send(*i);
+  }
+
+  drop(job_sender);
+
+  // Workers past the usual count only ping while the scan is behind the minimum rate.
+  let workers = (threads.max(1) as usize).min(MAX_PING_THREADS).min(hosts.len());
+  let mut handles: Vec<thread::JoinHandle<()>> = Default::default();
+
+  for idx in 0..(workers * limiter.burst()).min(hosts.len()) {
+    let extra = idx >= workers;
+    let th_jobs = job_recv.clone();
+    let th_results = result_sender.clone();
+    let th_ports = ports.to_vec();
+    let th_rtt = rtt.clone();
+    let th_limiter = limiter.clone();
+    let th_binding = binding.clone();
+
+    handles.push(thread::spawn(move || {
+      while let Some(host) = th_limiter.next_job(&th_jobs, extra) {
+        th_limiter.wait();
+
+        if connect_ping(&host, &th_ports, &th_rtt, &th_binding) == true {
+          let _ = th_results.send(host.ip());
+        }
+      }
+    }));
+  }
+
+  drop(result_sender);
+
+  let mut out: HashSet<IpAddr> = Default::default();
+  while let Ok(ip) = result_recv.recv() {
+    out.insert(ip);
+  }
+
+  for i in handles {
+    let _ = i.join();
+  }
+
+  out
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn wants_discovery_skips_single_host() {
+    assert!(!wants_discovery(1, false, false));
+    assert!(!wants_discovery(0, false, false));
+  }
+
+  #[test]
+  fn wants_discovery_for_many_hosts_or_explicit_pings() {
+    assert!(wants_discovery(2, false, false));
+    assert!(wants_discovery(256, false, false));
+    assert!(wants_discovery(1, false, true));
+  }
+
+  #[test]
+  fn wants_discovery_honours_skip_discovery() {
+    assert!(!wants_discovery(256, true, false));
+    assert!(!wants_discovery(1, true, true));
+  }
+}
@@ -0,0 +1,280 @@
+use std::collections::{BTreeSet, HashMap};
+use std::io::{Error, ErrorKind};
+use std::net::SocketAddr;
+use std::sync::Arc;
+use std::thread;
+use std::time::Instant;
+use crossbeam::channel::{Receiver, Sender, TryRecvError};
+use mio::net::TcpStream;
+use mio::{Events, Interest, Poll, Token};
+use super::{Flags, PortState, RateLimiter, RttTable, SourceBinding, ThreadMessage, PROGRESS_INTERVAL};
+
+const MAX_ENGINE_THREADS: usize = 4;      // Each engine thread runs its own event loop.
+const RESERVED_FILES: u64 = 64;           // File descriptors kept free for everything that is not a probe.
+
+// A connection attempt that is waiting for the target to answer.
+struct Probe {
+  stream: TcpStream,
+  address: SocketAddr,
+  started: Instant,
+  deadline: Instant,
+}
+
+/**Function returns the number of event loops that should share the probes.
+ * Params:
+ *  concurrency: usize {The number of connections allowed in flight at once}
+ * Returns usize.
+ */
+pub fn engine_threads(concurrency: usize) -> usize {
+  let cores = thread::available_parallelism().map(|i| i.get()).unwrap_or(1);
+  cores.min(MAX_ENGINE_THREADS).min(concurrency).max(1)
+}
+
+/**Function raises the open file limit as far as the system allows and returns how many connections
+ * can be in flight at once without running out of file descriptors.
+ * Params:
+ *  concurrency: usize {The number of connections the user asked for}
+ * Returns usize.
+ */
+#[cfg(unix)]
+pub fn raise_file_limit(concurrency: usize) -> usize {
+  let mut limit = libc::rlimit { rlim_cur: 0, rlim_max: 0 };
+
+  unsafe {
+    if libc::getrlimit(libc::RLIMIT_NOFILE, &mut limit) != 0 {
+      return concurrency;
+    }
+
+    let wanted = (concurrency as u64 + RESERVED_FILES) as libc::rlim_t;
+    if limit.rlim_cur < wanted {
+      let mut raised = limit;
+      raised.rlim_cur = wanted.min(limit.rlim_max);
+
+      if libc::setrlimit(libc::RLIMIT_NOFILE, &raised) == 0 {
+        limit = raised;
+      }
+    }
+  }
+
+  let available = limit.rlim_cur.saturating_sub(RESERVED_FILES).max(1);
+  concurrency.min(available as usize)
+}
+
+#[cfg(not(unix))]
+pub fn raise_file_limit(concurrency: usize) -> usize {
+  concurrency
+}
+
+/**Function works out the state of a connection attempt once the socket reports an event.
+ * Params:
+ *  stream: &TcpStream {The socket of the connection attempt}
+ * Returns Option<PortState> (None when the connection is still in progress)
+ */
+pub fn connect_result(stream: &TcpStream) -> Option<PortState> {
+  match stream.take_error() {
+    Ok(Some(e)) | Err(e) => { return Some(PortState::from_error(&e)); },
+    Ok(None) => {}
+  }
+
+  // The socket only has a peer once the handshake has completed.
+  match stream.peer_addr() {
+    Ok(_) => { Some(PortState::Open) },
+    Err(e) if e.kind() == ErrorKind::NotConnected => { None },
+    Err(e) => { Some(PortState::from_error(&e)) }
+  }
+}
+
+/**Function runs an event loop that keeps up to `slots` non blocking connection attempts in flight and
+ * pulls new targets from the job queue until it is empty.
+ * When the scan falls behind the minimum rate the loop may open up to twice as many connections.
+ * Results are sent to the main thread with the same messages the worker threads use.
+ * Params:
+ *  jobs:    Receiver<SocketAddr>  {The queue of every address and port left to scan}
+ *  slots:   usize                 {The number of connections this loop may have in flight}
+ *  f:       Flags                 {Setting we want to apply to the scan}
+ *  limiter: Arc<RateLimiter>      {Paces the probes of every event loop}
+ *  rtt:     Arc<RttTable>         {Measures each host and decides how long to wait for each connection}
+ *  binding: Arc<SourceBinding>    {The interface and address picked by the user}
+ *  send:    Sender<ThreadMessage> {The channel used to send results to the main thread}
+ * Returns Result<(), Error>
+ */
+pub fn run_engine(jobs: Receiver<SocketAddr>, slots: usize, f: Flags, limiter: Arc<RateLimiter>, rtt: Arc<RttTable>, binding: Arc<SourceBinding>, send: Sender<ThreadMessage>) -> Result<(), Error> {
+  let mut poll = Poll::new()?;
+  let mut events = Events::with_capacity(1024);
+  let mut probes: HashMap<Token, Probe> = Default::default();
+  let mut deadlines: BTreeSet<(Instant, Token)> = Default::default();     // The probes in flight ordered by when they run out of time.
+  let mut next_token: usize = 0;
+  let mut drained = false;
+  let mut next_start: Option<Instant> = None;     // A slot reserved from the limiter that has not started yet.
+  let mut unreported_probes: usize = 0;
+
+  // Sends the state of a finished probe and reports progress in batches.
+  let mut finish = |address: SocketAddr, state: PortState| -> () {
+    if state == PortState::Open {
+      let _ = send.send(ThreadMessage::OpenPort(address));
+    }
+
+    else {
+      let _ = send.send(ThreadMessage::State(address, state));
+    }
+
+    unreported_probes += 1;
+    if unreported_probes >= PROGRESS_INTERVAL {
+      let _ = send.send(ThreadMessage::Progress(unreported_probes));
+      unreported_probes = 0;
+    }
+  };
+
+  // Returns true when another connection may be opened.
+  let has_room = |in_flight: usize| -> bool {
+    in_flight < slots || (in_flight < slots * 2 && limiter.behind_min_rate() == true)
+  };
+
+  loop {
+    // Free slots are filled with new connection attempts once the limiter allows them to start.
+    while drained == false && has_room(probes.len()) == true {
+      let start = match next_start.take() {
+        Some(s) => { s },
+        None =>    { limiter.reserve() }
+      };
+
+      if start > Instant::now() {
+        next_start = Some(start);
+        break;
+      }
+
+      let address = match jobs.try_recv() {
+        Ok(s) => { s },
+        Err(TryRecvError::Empty) | Err(TryRecvError::Disconnected) => {
+          drained = true;
+          break;
+        }
+      };
+
+      let mut stream = match binding.connect_nonblocking(&address) {
+        Ok(s) => { s },
+        Err(e) => {
+          if f.debug == true {
+            super::fmt::f_debug("unable to open connection to", format!("{} - {}", address, e).as_str());
+          }
+
+          finish(address, PortState::from_error(&e));
+          continue;
+        }
+      };
+
+      let token = Token(next_token);
+      next_token = next_token.wrapping_add(1);
+
+      if let Err(e) = poll.registry().register(&mut stream, token, Interest::WRITABLE) {
+        finish(address, PortState::from_error(&e));
+        continue;
+      }
+
+      let started = Instant::now();
+      let deadline = started + rtt.timeout(&address.ip());
+      deadlines.insert((deadline, token));
+      probes.insert(token, Probe { stream, address, started, deadline });
+    }
+
+    if drained == true && probes.is_empty() {
+      break;
+    }
+
+    // We only wait until the oldest connection attempt runs out of time or the next probe may start.
+    let now = Instant::now();
+    let mut wake = deadlines.first().map(|i| i.0);
+
+    if let Some(start) = next_start {
+      if has_room(probes.len()) == true {
+        wake = Some(wake.map_or(start, |i| i.min(start)));
+      }
+    }
+
+    let wait = wake.unwrap_or(now).saturating_duration_since(now);
+
+    if let Err(e) = poll.poll(&mut events, Some(wait)) {
+      if e.kind() != ErrorKind::Interrupted {
+        return Err(e);
+      }
+    }
+
+    for event in events.iter() {
+      let state = match probes.get(&event.token()) {
+        Some(probe) => { connect_result(&probe.stream) },
+        None =>        { continue; }
+      };
+
+      if let Some(s) = state {
+        if let Some(mut probe) = probes.remove(&event.token()) {
+          let _ = poll.registry().deregister(&mut probe.stream);
+          deadlines.remove(&(probe.deadline, event.token()));
+
+          // Accepted and refused connections both tell us how far away the host is.
+          if s == PortState::Open || s == PortState::Closed {
+            rtt.sample(probe.address.ip(), probe.started.elapsed());
+          }
+
+          finish(probe.address, s);
+        }
+      }
+    }
+
+    // Connection attempts that ran out of time are filtered.
+    let now = Instant::now();
+
+    while let Some(&(deadline, token)) = deadlines.first() {
+      if deadline > now {
+        break;
+      }
+
+      deadlines.remove(&(deadline, token));
+      if let Some(mut probe) = probes.remove(&token) {
+        let _ = poll.registry().deregister(&mut probe.stream);
+        finish(probe.address, PortState::Filtered);
+      }
+    }
+  }
+
+  if unreported_probes > 0 {
+    let _ = send.send(ThreadMessage::Progress(unreported_probes));
+  }
+
+  Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crossbeam::channel::unbounded;
+  use std::net::TcpListener;
+
+  #[test]
+  fn run_engine_reports_open_and_closed_ports() {
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let open = listener.local_addr().unwrap();
+
+    // A port that was just released has nothing listening on it, so the connection is refused.
+    let closed = TcpListener::bind("127.0.0.1:0").unwrap().local_addr().unwrap();
+
+    let (job_sender, job_recv) = unbounded::<SocketAddr>();
+    let (send, recv) = unbounded::<ThreadMessage>();
+    job_sender.send(open).unwrap();
+    job_sender.send(closed).unwrap();
+    drop(job_sender);
+
+    let limiter = Arc::new(RateLimiter::new(None, None, 0, 0).unwrap());
+    let rtt = Arc::new(RttTable::new(100, 2000));
+    let binding = Arc::new(SourceBinding::new(None, None).unwrap());
+
+    run_engine(job_recv, 4, Flags::new(), limiter, rtt, binding, send).unwrap();
+
+    // The engine dropped its sender when it returned, so the channel ends once every message is read.
+    let messages: Vec<ThreadMessage> = recv.iter().collect();
+
+    assert_eq!(messages.iter().filter(|i| **i == ThreadMessage::OpenPort(open)).count(), 1);
+    assert_eq!(messages.iter().filter(|i| **i == ThreadMessage::State(closed, PortState::Closed)).count(), 1);
+    assert_eq!(messages.last(), Some(&ThreadMessage::Progress(2)));
+    assert_eq!(messages.len(), 3);
+  }
+}
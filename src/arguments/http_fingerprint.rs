@@ -0,0 +1,475 @@
+use std::io::{Error, ErrorKind, Read, Write};
+use std::net::{IpAddr, SocketAddr};
+use std::sync::Arc;
+use std::time::Duration;
+use rustls::{ClientConfig, ClientConnection, StreamOwned};
+use rustls::pki_types::ServerName;
+use serde::Serialize;
+use super::{escape_banner, server_name, SourceBinding};
+
+const MAX_REDIRECTS: usize = 5;                 // The longest redirect chain we follow before giving up.
+const MAX_HTTP_RESPONSE: usize = 1048576;       // The most bytes of a response we read, headers included.
+const MAX_TITLE_LEN: usize = 256;               // Titles longer than this are cut short.
+const HTTPS_PORTS: [u16; 5] = [443, 4443, 8443, 9443, 10443];   // Ports that are tried with tls even when the banner gives no hint.
+
+// What the web server on a port told us about itself.
+#[derive(Debug, Clone, Default, PartialEq, Serialize)]
+pub struct HttpFingerprint {
+  pub scheme: String,           // http or https.
+  pub status: u16,
+  pub status_line: String,
+
+  #[serde(skip_serializing_if = "Option::is_none")]
+  pub server: Option<String>,
+
+  #[serde(skip_serializing_if = "Option::is_none")]
+  pub powered_by: Option<String>,
+
+  #[serde(skip_serializing_if = "Option::is_none")]
+  pub title: Option<String>,
+
+  #[serde(skip_serializing_if = "Vec::is_empty")]
+  pub redirects: Vec<String>,   // Every location we were sent to, in order.
+
+  #[serde(skip_serializing_if = "Option::is_none")]
+  pub content_type: Option<String>,
+
+  pub content_length: usize,    // The size of the body of the last response in bytes.
+
+  #[serde(skip_serializing_if = "Option::is_none")]
+  pub favicon_hash: Option<i32>,  // The murmur3 hash of /favicon.ico, computed the same way as shodan.
+}
+
+// A response split into its parts.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct HttpResponse {
+  pub status_line: String,
+  pub status: u16,
+  pub headers: Vec<(String, String)>,
+  pub body: Vec<u8>,
+}
+
+impl HttpResponse {
+
+  // Returns the value of the first header with the name, ignoring case.
+  pub fn header(&self, name: &str) -> Option<&str> {
+    self.headers.iter().find(|i| i.0.eq_ignore_ascii_case(name)).map(|i| i.1.as_str())
+  }
+}
+
+/**Function splits a response into its status line, headers and body.
+ * Params:
+ *  data: &[u8] {The raw response}
+ * Returns Option<HttpResponse> (None when the data is not an http response)
+ */
+pub fn parse_http_response(data: &[u8]) -> Option<HttpResponse> {
+  if data.starts_with(b"HTTP/") == false {
+    return None;
+  }
+
+  // Some servers end their lines with a bare newline.
+  let (head_end, body_start) = match data.windows(4).position(|i| i == b"\r\n\r\n") {
+    Some(s) => { (s, s + 4) },
+    None => {
+      match data.windows(2).position(|i| i == b"\n\n") {
+        Some(s) => { (s, s + 2) },
+        None =>    { (data.len(), data.len()) }
+      }
+    }
+  };
+
+  let head = String::from_utf8_lossy(&data[..head_end]).to_string();
+  let mut lines = head.lines();
+
+  let status_line = lines.next()?.trim().to_string();
+  let status = status_line.split_whitespace().nth(1)?.parse::<u16>().ok()?;
+
+  let mut out = HttpResponse { status_line, status, ..Default::default() };
+  for i in lines {
+    if let Some((name, value)) = i.split_once(':') {
+      out.headers.push((name.trim().to_string(), value.trim().to_string()));
+    }
+  }
+
+  out.body = data[body_start..].to_vec();
+  Some(out)
+}
+
+// Returns true when the bytes start with a tls record, which is how tls servers answer a plain text request.
+pub fn is_tls_banner(data: &[u8]) -> bool {
+  data.len() > 1 && (data[0] == 0x15 || data[0] == 0x16) && data[1] == 0x03
+}
+
+/**Function picks the value of the host header. Hostnames typed by the user are sent so virtual hosts answer,
+ * every other target is named by its address. The port is left out when it is the default for the scheme.
+ * Params:
+ *  name:    &str        {The target as it was typed by the user}
+ *  address: &SocketAddr {The address and port of the server}
+ *  tls:     bool        {The request is sent over tls}
+ * Returns String
+ */
+pub fn host_header(name: &str, address: &SocketAddr, tls: bool) -> String {
+  let host = match (ServerName::try_from(name), address.ip()) {
+    (Ok(ServerName::DnsName(_)), _) => { name.to_string() },
+    (_, IpAddr::V6(ip)) =>             { format!("[{}]", ip) },
+    (_, IpAddr::V4(ip)) =>             { ip.to_string() }
+  };
+
+  match (address.port(), tls) {
+    (80, false) | (443, true) => { host },
+    (port, _) =>                 { format!("{}:{}", host, port) }
+  }
+}
+
+/**Function sends a get request and reads the whole response.
+ * Params:
+ *  address: &SocketAddr               {The address and port of the server}
+ *  name:    &str                      {The target as it was typed by the user}
+ *  path:    &str                      {The path to request}
+ *  tls:     Option<&Arc<ClientConfig>> {The tls settings when the request is sent over tls}
+ *  timeout: Duration                  {How long to wait for the connection and for each read}
+ *  binding: &SourceBinding            {The interface and address picked by the user}
+ * Returns Result<Vec<u8>, Error>
+ */
+pub fn http_request(address: &SocketAddr, name: &str, path: &str, tls: Option<&Arc<ClientConfig>>, timeout: Duration, binding: &SourceBinding) -> Result<Vec<u8>, Error> {
+  let mut stream = binding.connect_timeout(address, timeout)?;
+  stream.set_read_timeout(Some(timeout.max(Duration::from_millis(1))))?;
+  stream.set_write_timeout(Some(timeout.max(Duration::from_millis(1))))?;
+
+  // Http 1.0 keeps servers from sending chunked bodies and closes the connection once the response is sent.
+  let request = format!(
+    "GET {} HTTP/1.0\r\nHost: {}\r\nUser-Agent: lethal_potato\r\nAccept: */*\r\nConnection: close\r\n\r\n",
+    path, host_header(name, address, tls.is_some())
+  );
+
+  match tls {
+    Some(config) => {
+      let connection = match ClientConnection::new(config.clone(), server_name(name, address)) {
+        Ok(s) => { s },
+        Err(e) => { return Err(Error::new(ErrorKind::InvalidInput, e)); }
+      };
+
+      let mut tls_stream = StreamOwned::new(connection, stream);
+      tls_stream.write_all(request.as_bytes())?;
+      Ok(read_response(&mut tls_stream))
+    },
+
+    None => {
+      stream.write_all(request.as_bytes())?;
+      Ok(read_response(&mut stream))
+    }
+  }
+}
+
+// Reads until the server closes the connection or the response is too long. Servers that close a tls
+// connection without saying so still leave us with what they sent.
+fn read_response<R: Read>(reader: &mut R) -> Vec<u8> {
+  let mut out: Vec<u8> = Default::default();
+  let mut buffer = [0u8; 8192];
+
+  while out.len() < MAX_HTTP_RESPONSE {
+    match reader.read(&mut buffer) {
+      Ok(0) =>    { break; },
+      Ok(size) => { out.extend_from_slice(&buffer[..size]); },
+      Err(_) =>   { break; }
+    }
+  }
+
+  out.truncate(MAX_HTTP_RESPONSE);
+  out
+}
+
+/**Function works out where a redirect sends us. Only redirects to the same host are followed, so a scan never
+ * wanders off to servers the user did not ask for.
+ * Params:
+ *  location: &str        {The value of the location header}
+ *  name:     &str        {The target as it was typed by the user}
+ *  address:  &SocketAddr {The address and port the redirect came from}
+ *  path:     &str        {The path the redirect came from}
+ *  tls:      bool        {The redirect came over tls}
+ * Returns Option<(SocketAddr, String, bool)> (The address, path and whether to use tls)
+ */
+pub fn follow_location(location: &str, name: &str, address: &SocketAddr, path: &str, tls: bool) -> Option<(SocketAddr, String, bool)> {
+  let (tls, rest) = match (location.strip_prefix("http://"), location.strip_prefix("https://")) {
+    (Some(s), _) => { (false, s) },
+    (_, Some(s)) => { (true, s) },
+    _ => {
+      if location.starts_with("//") {
+        return None;
+      }
+
+      // Relative locations are resolved against the directory of the current path.
+      let path = match location.starts_with('/') {
+        true =>  { location.to_string() },
+        false => { format!("{}{}", &path[..path.rfind('/').map(|i| i + 1).unwrap_or(0)], location) }
+      };
+
+      return Some((*address, path, tls));
+    }
+  };
+
+  let (authority, path) = match rest.find('/') {
+    Some(s) => { (&rest[..s], rest[s..].to_string()) },
+    None =>    { (rest, String::from("/")) }
+  };
+
+  let authority = authority.rsplit('@').next().unwrap_or(authority);
+  let default_port: u16 = if tls == true { 443 } else { 80 };
+
+  let (host, port) = match authority.strip_prefix('[') {
+    Some(s) => {
+      let (host, rest) = s.split_once(']')?;
+      (host, rest.strip_prefix(':').map(|i| i.parse::<u16>().ok()).unwrap_or(Some(default_port))?)
+    },
+
+    None => {
+      match authority.split_once(':') {
+        Some((host, port)) => { (host, port.parse::<u16>().ok()?) },
+        None =>               { (authority, default_port) }
+      }
+    }
+  };
+
+  if host.eq_ignore_ascii_case(name) == false && host != address.ip().to_string() {
+    return None;
+  }
+
+  Some((SocketAddr::new(address.ip(), port), path, tls))
+}
+
+/**Function reads the text of the title element of a page.
+ * Params:
+ *  body: &[u8] {The body of the page}
+ * Returns Option<String>
+ */
+pub fn html_title(body: &[u8]) -> Option<String> {
+  let text = String::from_utf8_lossy(body);
+
+  // Only ascii letters are lowercased, so the offsets found in the copy are the same in the text.
+  let lower = text.to_ascii_lowercase();
+
+  let open = lower.find("<title")?;
+  let start = open + lower[open..].find('>')? + 1;
+  let end = start + lower[start..].find("</title")?;
+
+  let title: String = text[start..end].split_whitespace().collect::<Vec<&str>>().join(" ");
+  match title.len() {
+    0 => { None },
+    _ => { Some(title.chars().take(MAX_TITLE_LEN).collect()) }
+  }
+}
+
+/**Function fingerprints the web server on a port. The banner decides whether the port speaks http, https or neither.
+ * Params:
+ *  address: &SocketAddr               {The address and port of the server}
+ *  name:    &str                      {The target as it was typed by the user}
+ *  banner:  &[u8]                     {What the banner grab got back}
+ *  config:  Option<&Arc<ClientConfig>> {The tls settings for https ports}
+ *  timeout: Duration                  {How long to wait for the connection and for each read}
+ *  binding: &SourceBinding            {The interface and address picked by the user}
+ * Returns Option<HttpFingerprint> (None when the port does not speak http)
+ */
+pub fn fingerprint_http(address: &SocketAddr, name: &str, banner: &[u8], config: Option<&Arc<ClientConfig>>, timeout: Duration, binding: &SourceBinding) -> Option<HttpFingerprint> {
+  let mut tls = match banner.starts_with(b"HTTP/") {
+    true =>  { false },
+    false => { is_tls_banner(banner) || banner.is_empty() || HTTPS_PORTS.contains(&address.port()) }
+  };
+
+  if banner.starts_with(b"HTTP/") == false && tls == false {
+    return None;
+  }
+
+  let (mut target, mut path) = (*address, String::from("/"));
+  let mut redirects: Vec<String> = Default::default();
+
+  let response = loop {
+    let settings = match tls {
+      true =>  { Some(config?) },
+      false => { None }
+    };
+
+    let response = parse_http_response(&http_request(&target, name, &path, settings, timeout, binding).ok()?)?;
+
+    if (300..400).contains(&response.status) == true && redirects.len() < MAX_REDIRECTS {
+      if let Some(location) = response.header("location") {
+        redirects.push(location.to_string());
+
+        if let Some((next_target, next_path, next_tls)) = follow_location(location, name, &target, &path, tls) {
+          (target, path, tls) = (next_target, next_path, next_tls);
+          continue;
+        }
+      }
+    }
+
+    break response;
+  };
+
+  let mut out = HttpFingerprint {
+    scheme: String::from(if tls == true { "https" } else { "http" }),
+    status: response.status,
+    status_line: escape_banner(response.status_line.as_bytes()),
+    server: response.header("server").map(|i| i.to_string()),
+    powered_by: response.header("x-powered-by").map(|i| i.to_string()),
+    title: html_title(&response.body),
+    redirects,
+    content_type: response.header("content-type").map(|i| i.to_string()),
+    content_length: response.body.len(),
+    favicon_hash: None,
+  };
+
+  let settings = match tls {
+    true =>  { config },
+    false => { None }
+  };
+
+  if let Ok(data) = http_request(&target, name, "/favicon.ico", settings, timeout, binding) {
+    match parse_http_response(&data) {
+      Some(favicon) if favicon.status == 200 && !favicon.body.is_empty() => {
+        out.favicon_hash = Some(favicon_hash(&favicon.body));
+      },
+
+      _ => {}
+    }
+  }
+
+  Some(out)
+}
+
+// Returns the hash shodan uses for favicons: murmur3 of the base64 of the icon, wrapped every 76 characters.
+pub fn favicon_hash(data: &[u8]) -> i32 {
+  murmur3_32(base64_lines(data).as_bytes(), 0) as i32
+}
+
+/**Function encodes bytes as base64 with a newline after every 76 characters and at the end.
+ * Params:
+ *  data: &[u8] {The raw bytes}
+ * Returns String
+ */
+pub fn base64_lines(data: &[u8]) -> String {
+  const ALPHABET: &[u8; 64] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+  let mut encoded = String::new();
+
+  for chunk in data.chunks(3) {
+    let value = (chunk[0] as u32) << 16 | (*chunk.get(1).unwrap_or(&0) as u32) << 8 | *chunk.get(2).unwrap_or(&0) as u32;
+
+    for i in 0..4 {
+      match i <= chunk.len() {
+        true =>  { encoded.push(ALPHABET[(value >> (18 - i * 6)) as usize & 0x3f] as char); },
+        false => { encoded.push('='); }
+      }
+    }
+  }
+
+  let mut out = String::new();
+  for line in encoded.as_bytes().chunks(76) {
+    out.push_str(String::from_utf8_lossy(line).as_ref());
+    out.push('\n');
+  }
+
+  out
+}
+
+/**Function hashes bytes with the 32 bit murmur3 algorithm.
+ * Params:
+ *  data: &[u8] {The bytes to hash}
+ *  seed: u32   {The starting value of the hash}
+ * Returns u32
+ */
+pub fn murmur3_32(data: &[u8], seed: u32) -> u32 {
+  const C1: u32 = 0xcc9e2d51;
+  const C2: u32 = 0x1b873593;
+
+  let mut hash = seed;
+  let chunks = data.chunks_exact(4);
+  let tail = chunks.remainder();
+
+  for i in chunks {
+    let k = u32::from_le_bytes([i[0], i[1], i[2], i[3]]).wrapping_mul(C1).rotate_left(15).wrapping_mul(C2);
+    hash ^= k;
+    hash = hash.rotate_left(13).wrapping_mul(5).wrapping_add(0xe6546b64);
+  }
+
+  if !tail.is_empty() {
+    let mut k: u32 = 0;
+    for (idx, i) in tail.iter().enumerate() {
+      k |= (*i as u32) << (8 * idx);
+    }
+
+    hash ^= k.wrapping_mul(C1).rotate_left(15).wrapping_mul(C2);
+  }
+
+  hash ^= data.len() as u32;
+  hash ^= hash >> 16;
+  hash = hash.wrapping_mul(0x85ebca6b);
+  hash ^= hash >> 13;
+  hash = hash.wrapping_mul(0xc2b2ae35);
+  hash ^= hash >> 16;
+
+  hash
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn html_title_ignores_case() {
+    assert_eq!(html_title(b"<html><TITLE lang=\"en\">\n  Router   Login </Title>"), Some(String::from("Router Login")));
+  }
+
+  #[test]
+  fn html_title_unicode_around_title() {
+    assert_eq!(html_title("\u{2126}<title>\u{e9}\u{e9}</title>\u{130}".as_bytes()), Some(String::from("\u{e9}\u{e9}")));
+    assert_eq!(html_title("\u{130}\u{130}<TITLE>\u{2126}</TITLE>".as_bytes()), Some(String::from("\u{2126}")));
+  }
+
+  #[test]
+  fn html_title_malformed() {
+    assert_eq!(html_title(b""), None);
+    assert_eq!(html_title(b"<title>"), None);
+    assert_eq!(html_title(b"<title"), None);
+    assert_eq!(html_title(b"<title>   </title>"), None);
+    assert_eq!(html_title(b"<title>\xff\xfe</title>"), Some(String::from("\u{fffd}\u{fffd}")));
+  }
+
+  #[test]
+  fn parse_http_response_splits_parts() {
+    let response = parse_http_response(b"HTTP/1.1 301 Moved\r\nServer: nginx\r\nlocation: /a\r\n\r\nbody").unwrap();
+
+    assert_eq!(response.status, 301);
+    assert_eq!(response.header("Location"), Some("/a"));
+    assert_eq!(response.header("server"), Some("nginx"));
+    assert_eq!(response.body, b"body");
+  }
+
+  #[test]
+  fn parse_http_response_malformed() {
+    assert_eq!(parse_http_response(b""), None);
+    assert_eq!(parse_http_response(b"HTTP/"), None);
+    assert_eq!(parse_http_response(b"HTTP/1.1 \xff\r\n\r\n"), None);
+    assert_eq!(parse_http_response(b"HTTP/1.0 200\n\n").map(|i| i.status), Some(200));
+  }
+
+  #[test]
+  fn follow_location_stays_on_target() {
+    let address: SocketAddr = "10.0.0.1:80".parse().unwrap();
+
+    assert_eq!(follow_location("login", "10.0.0.1", &address, "/admin/index", false), Some((address, String::from("/admin/login"), false)));
+    assert_eq!(follow_location("https://10.0.0.1/", "10.0.0.1", &address, "/", false), Some(("10.0.0.1:443".parse().unwrap(), String::from("/"), true)));
+    assert_eq!(follow_location("http://example.com/", "10.0.0.1", &address, "/", false), None);
+    assert_eq!(follow_location("//example.com/", "10.0.0.1", &address, "/", false), None);
+    assert_eq!(follow_location("http://10.0.0.1:x/", "10.0.0.1", &address, "/", false), None);
+  }
+
+  #[test]
+  fn favicon_hash_matches_reference() {
+    assert_eq!(base64_lines(b""), "");
+    assert_eq!(base64_lines(b"f"), "Zg==\n");
+    assert_eq!(base64_lines(b"foobar"), "Zm9vYmFy\n");
+    assert_eq!(base64_lines(&[0; 60]).lines().map(|i| i.len()).collect::<Vec<usize>>(), vec![76, 4]);
+
+    assert_eq!(murmur3_32(b"", 0), 0);
+    assert_eq!(murmur3_32(b"hello", 0), 0x248bfa47);
+    assert_eq!(murmur3_32(b"The quick brown fox jumps over the lazy dog", 0), 0x2e4ff723);
+  }
+}
@@ -0,0 +1,297 @@
+use std::io::{Error, ErrorKind};
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr, SocketAddrV6, TcpStream, UdpSocket};
+use std::time::Duration;
+use pnet_datalink::NetworkInterface;
+use pnet_transport::TransportSender;
+use socket2::{Domain, Protocol, SockAddr, Socket, Type};
+
+// The interface and address that every probe is sent from.
+#[derive(Debug, Clone, Default)]
+pub struct SourceBinding {
+  pub interface: Option<NetworkInterface>,    // Probes only leave through this interface.
+  pub source_ip: Option<IpAddr>,              // Probes are sent from this address.
+}
+
+impl SourceBinding {
+
+  /**Function checks the interface and source address picked by the user.
+   * Params:
+   *  interface: Option<String> {The name of the interface}
+   *  source_ip: Option<IpAddr> {The address to send from}
+   * Returns Result<SourceBinding, String>
+   */
+  pub fn new(interface: Option<String>, source_ip: Option<IpAddr>) -> Result<SourceBinding, String> {
+    let mut out = SourceBinding::default();
+
+    if let Some(name) = interface {
+      match pnet_datalink::interfaces().into_iter().find(|i| i.name == name) {
+        Some(s) => { out.interface = Some(s); },
+        None =>    { return Err(format!("there is no interface named {}", name)); }
+      }
+    }
+
+    if let (Some(ip), Some(interface)) = (source_ip, out.interface.as_ref()) {
+      if interface.ips.iter().any(|i| i.ip() == ip) == false {
+        return Err(format!("{} is not assigned to {}", ip, interface.name));
+      }
+    }
+
+    out.source_ip = source_ip;
+    Ok(out)
+  }
+
+  // Returns true when the user picked an interface or a source address.
+  pub fn is_set(&self) -> bool {
+    self.interface.is_some() || self.source_ip.is_some()
+  }
+
+  /**Function returns the address that probes to the target are sent from, if one was picked.
+   * The source address is only used for targets of the same ip version, otherwise an address of the interface is used.
+   * Params:
+   *  &self
+   *  target: &IpAddr {The address of the target}
+   * Returns Option<IpAddr>
+   */
+  pub fn source_for(&self, target: &IpAddr) -> Option<IpAddr> {
+    if let Some(ip) = self.source_ip {
+      if ip.is_ipv4() == target.is_ipv4() {
+        return Some(ip);
+      }
+    }
+
+    // Link local targets are reached from a link local address and every other target from a routable one.
+    let interface = self.interface.as_ref()?;
+    interface.ips.iter().map(|i| i.ip())
+    .filter(|i| i.is_ipv4() == target.is_ipv4())
+    .min_by_key(|i| (is_link_local(i) != is_link_local(target)) as u8)
+  }
+
+  /**Function binds a socket to the interface and source address before it is connected.
+   * Params:
+   *  &self
+   *  socket: &Socket     {The socket that is about to be used}
+   *  target: &SocketAddr {The address the socket will talk to}
+   * Returns Result<(), Error>
+   */
+  pub fn bind_socket(&self, socket: &Socket, target: &SocketAddr) -> Result<(), Error> {
+    #[cfg(any(target_os = "linux", target_os = "android"))]
+    if let Some(interface) = self.interface.as_ref() {
+      socket.bind_device(Some(interface.name.as_bytes()))?;
+    }
+
+    if let Some(ip) = self.source_for(&target.ip()) {
+      let mut source = SocketAddr::new(ip, 0);
+
+      // A link local address can only be bound together with the interface it belongs to.
+      if let (IpAddr::V6(v6), true) = (ip, is_link_local(&ip)) {
+        let scope_id = match (target, self.interface.as_ref()) {
+          (_, Some(interface)) =>                { interface.index },
+          (SocketAddr::V6(target_v6), None) =>   { target_v6.scope_id() },
+          _ =>                                   { 0 }
+        };
+
+        source = SocketAddr::V6(SocketAddrV6::new(v6, 0, 0, scope_id));
+      }
+
+      socket.bind(&SockAddr::from(source))?;
+    }
+
+    Ok(())
+  }
+
+  /**Function opens a tcp connection to the target from the picked interface and source address.
+   * Params:
+   *  &self
+   *  target:  &SocketAddr {The address and port of the target}
+   *  timeout: Duration    {How long to wait for the connection}
+   * Returns Result<TcpStream, Error>
+   */
+  pub fn connect_timeout(&self, target: &SocketAddr, timeout: Duration) -> Result<TcpStream, Error> {
+    if self.is_set() == false {
+      return TcpStream::connect_timeout(target, timeout);
+    }
+
+    let socket = Socket::new(Domain::for_address(*target), Type::STREAM, Some(Protocol::TCP))?;
+    self.bind_socket(&socket, target)?;
+    socket.connect_timeout(&SockAddr::from(*target), timeout)?;
+
+    Ok(socket.into())
+  }
+
+  /**Function starts a non blocking tcp connection to the target from the picked interface and source address.
+   * Params:
+   *  &self
+   *  target: &SocketAddr {The address and port of the target}
+   * Returns Result<mio::net::TcpStream, Error>
+   */
+  pub fn connect_nonblocking(&self, target: &SocketAddr) -> Result<mio::net::TcpStream, Error> {
+    if self.is_set() == false {
+      return mio::net::TcpStream::connect(*target);
+    }
+
+    let socket = Socket::new(Domain::for_address(*target), Type::STREAM, Some(Protocol::TCP))?;
+    socket.set_nonblocking(true)?;
+    self.bind_socket(&socket, target)?;
+
+    match socket.connect(&SockAddr::from(*target)) {
+      Ok(_) => {},
+      Err(e) if connect_in_progress(&e) == true => {},
+      Err(e) => { return Err(e); }
+    }
+
+    Ok(mio::net::TcpStream::from_std(socket.into()))
+  }
+
+  /**Function opens a udp socket that sends to the target from the picked interface and source address.
+   * Params:
+   *  &self
+   *  target: &SocketAddr {The address and port of the target}
+   * Returns Result<UdpSocket, Error>
+   */
+  pub fn udp_socket(&self, target: &SocketAddr) -> Result<UdpSocket, Error> {
+    let socket = Socket::new(Domain::for_address(*target), Type::DGRAM, Some(Protocol::UDP))?;
+    self.bind_socket(&socket, target)?;
+
+    // Sockets that were not given a source address still need a local port.
+    if self.source_for(&target.ip()).is_none() {
+      let any = match target {
+        SocketAddr::V4(_) => { SocketAddr::new(IpAddr::V4(Ipv4Addr::UNSPECIFIED), 0) }
+        SocketAddr::V6(_) => { SocketAddr::new(IpAddr::V6(Ipv6Addr::UNSPECIFIED), 0) }
+      };
+
+      socket.bind(&SockAddr::from(any))?;
+    }
+
+    Ok(socket.into())
+  }
+
+  /**Function binds a raw channel opened by pnet to the interface and source address.
+   * Both halves of the channel share one socket, so binding the sender binds the receiver too.
+   * Params:
+   *  &self
+   *  tx:    &TransportSender {The half that sends packets}
+   *  is_v6: bool             {True when the channel carries ipv6 packets}
+   * Returns Result<(), Error>
+   */
+  #[cfg(unix)]
+  pub fn bind_raw(&self, tx: &TransportSender, is_v6: bool) -> Result<(), Error> {
+    use std::os::unix::io::FromRawFd;
+
+    if self.is_set() == false {
+      return Ok(());
+    }
+
+    let unspecified = match is_v6 {
+      false => { SocketAddr::new(IpAddr::V4(Ipv4Addr::UNSPECIFIED), 0) }
+      true =>  { SocketAddr::new(IpAddr::V6(Ipv6Addr::UNSPECIFIED), 0) }
+    };
+
+    // The socket still belongs to pnet, so it must never be closed here.
+    let socket = std::mem::ManuallyDrop::new(unsafe { Socket::from_raw_fd(tx.socket.fd) });
+    self.bind_socket(&socket, &unspecified)
+  }
+
+  #[cfg(not(unix))]
+  pub fn bind_raw(&self, _tx: &TransportSender, _is_v6: bool) -> Result<(), Error> {
+    Ok(())
+  }
+}
+
+// Returns true when a non blocking connection was started and is waiting for the handshake.
+pub fn connect_in_progress(e: &Error) -> bool {
+  #[cfg(unix)]
+  if e.raw_os_error() == Some(libc::EINPROGRESS) {
+    return true;
+  }
+
+  e.kind() == ErrorKind::WouldBlock
+}
+
+// Returns true for ipv4 169.254.0.0/16 and ipv6 fe80::/10 addresses.
+pub fn is_link_local(ip: &IpAddr) -> bool {
+  match ip {
+    IpAddr::V4(v4) => { v4.is_link_local() }
+    IpAddr::V6(v6) => { (v6.segments()[0] & 0xffc0) == 0xfe80 }
+  }
+}
+
+/**Function reads the mtu of an interface.
+ * Params:
+ *  name: &str {The name of the interface}
+ * Returns Option<u32>
+ */
+#[cfg(target_os = "linux")]
+pub fn interface_mtu(name: &str) -> Option<u32> {
+  match std::fs::read_to_string(format!("/sys/class/net/{}/mtu", name)) {
+    Ok(s) => { s.trim().parse::<u32>().ok() },
+    Err(_) => { None }
+  }
+}
+
+#[cfg(not(target_os = "linux"))]
+pub fn interface_mtu(_name: &str) -> Option<u32> {
+  None
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  fn interface(ips: &[&str]) -> NetworkInterface {
+    NetworkInterface {
+      name: String::from("eth0"),
+      description: String::new(),
+      index: 1,
+      mac: None,
+      ips: ips.iter().map(|i| i.parse().unwrap()).collect(),
+      flags: 0,
+    }
+  }
+
+  #[test]
+  fn source_for_ignores_other_ip_version() {
+    let binding = SourceBinding { interface: None, source_ip: Some("192.0.2.1".parse().unwrap()) };
+
+    assert_eq!(binding.source_for(&"2001:db8::10".parse().unwrap()), None);
+    assert_eq!(binding.source_for(&"198.51.100.10".parse().unwrap()), Some("192.0.2.1".parse().unwrap()));
+    assert_eq!(SourceBinding::default().source_for(&"198.51.100.10".parse().unwrap()), None);
+  }
+
+  #[test]
+  fn source_for_picks_interface_address() {
+    let binding = SourceBinding {
+      interface: Some(interface(&["192.0.2.1/24", "fe80::1/64", "2001:db8::1/64"])),
+      source_ip: Some("192.0.2.1".parse().unwrap()),
+    };
+
+    assert_eq!(binding.source_for(&"fe80::20".parse().unwrap()), Some("fe80::1".parse().unwrap()));
+    assert_eq!(binding.source_for(&"2001:db8::20".parse().unwrap()), Some("2001:db8::1".parse().unwrap()));
+  }
+
+  #[test]
+  fn is_link_local_v4_and_v6() {
+    assert!(is_link_local(&"169.254.10.1".parse().unwrap()));
+    assert!(is_link_local(&"fe80::1".parse().unwrap()));
+    assert!(is_link_local(&"febf::1".parse().unwrap()));
+    assert!(!is_link_local(&"192.168.1.1".parse().unwrap()));
+    assert!(!is_link_local(&"fec0::1".parse().unwrap()));
+    assert!(!is_link_local(&"2001:db8::1".parse().unwrap()));
+  }
+
+  #[test]
+  fn connect_in_progress_kinds() {
+    assert!(connect_in_progress(&Error::from(ErrorKind::WouldBlock)));
+    assert!(!connect_in_progress(&Error::from(ErrorKind::ConnectionRefused)));
+
+    #[cfg(unix)]
+    assert!(connect_in_progress(&Error::from_raw_os_error(libc::EINPROGRESS)));
+  }
+
+  #[test]
+  fn new_rejects_unknown_interface() {
+    let result = SourceBinding::new(Some(String::from("no-such-interface0")), None);
+    assert_eq!(result.err(), Some(String::from("there is no interface named no-such-interface0")));
+
+    assert!(!SourceBinding::new(None, None).unwrap().is_set());
+  }
+}
@@ -0,0 +1,167 @@
+use pnet_datalink::MacAddr;
+
+/**Function returns the vendor that was assigned the first three bytes of a mac address.
+ * Params:
+ *  prefix: u32 {The organizationally unique identifier}
+ * Returns Option<&'static str> (The name of the vendor)
+ */
+pub fn vendor_map(prefix: u32) -> Option<&'static str> {
+  match prefix {
+    0x00000C => { Some("Cisco") }
+    0x000048 => { Some("Seiko Epson") }
+    0x000074 => { Some("Ricoh") }
+    0x0000AA => { Some("Xerox") }
+    0x000393 => { Some("Apple") }
+    0x0003BA => { Some("Sun Microsystems") }
+    0x0003FF => { Some("Microsoft") }
+    0x00044B => { Some("NVIDIA") }
+    0x0004F2 => { Some("Polycom") }
+    0x00055D => { Some("D-Link") }
+    0x000569 => { Some("VMware") }
+    0x000585 => { Some("Juniper Networks") }
+    0x00090F => { Some("Fortinet") }
+    0x00095B => { Some("Netgear") }
+    0x000A95 => { Some("Apple") }
+    0x000B82 => { Some("Grandstream") }
+    0x000B86 => { Some("Aruba Networks") }
+    0x000C29 => { Some("VMware") }
+    0x000C42 => { Some("MikroTik") }
+    0x000D3A => { Some("Microsoft") }
+    0x000D88 => { Some("D-Link") }
+    0x000DB9 => { Some("PC Engines") }
+    0x000E58 => { Some("Sonos") }
+    0x000FB5 => { Some("Netgear") }
+    0x001132 => { Some("Synology") }
+    0x00121E => { Some("Juniper Networks") }
+    0x00125A => { Some("Microsoft") }
+    0x001310 => { Some("Cisco-Linksys") }
+    0x001422 => { Some("Dell") }
+    0x00144F => { Some("Oracle") }
+    0x00146C => { Some("Netgear") }
+    0x001517 => { Some("Intel") }
+    0x00155D => { Some("Microsoft Hyper-V") }
+    0x001565 => { Some("Yealink") }
+    0x00163E => { Some("Xen") }
+    0x001676 => { Some("Intel") }
+    0x001788 => { Some("Philips Lighting") }
+    0x0017A4 => { Some("Hewlett Packard") }
+    0x0017F2 => { Some("Apple") }
+    0x001839 => { Some("Cisco-Linksys") }
+    0x001882 => { Some("Huawei") }
+    0x0019D1 => { Some("Intel") }
+    0x001A11 => { Some("Google") }
+    0x001AA1 => { Some("Cisco") }
+    0x001B21 => { Some("Intel") }
+    0x001B54 => { Some("Cisco") }
+    0x001B63 => { Some("Apple") }
+    0x001B78 => { Some("Hewlett Packard") }
+    0x001BFC => { Some("ASUSTek") }
+    0x001C14 => { Some("VMware") }
+    0x001C42 => { Some("Parallels") }
+    0x001C73 => { Some("Arista Networks") }
+    0x001CB3 => { Some("Apple") }
+    0x001D60 => { Some("ASUSTek") }
+    0x001D7E => { Some("Cisco-Linksys") }
+    0x001E0B => { Some("Hewlett Packard") }
+    0x001EC2 => { Some("Apple") }
+    0x001F33 => { Some("Netgear") }
+    0x001F5B => { Some("Apple") }
+    0x00219B => { Some("Dell") }
+    0x002248 => { Some("Microsoft") }
+    0x00248C => { Some("ASUSTek") }
+    0x002500 => { Some("Apple") }
+    0x002590 => { Some("Supermicro") }
+    0x00259E => { Some("Huawei") }
+    0x0026B9 => { Some("Dell") }
+    0x0026BB => { Some("Apple") }
+    0x002722 => { Some("Ubiquiti") }
+    0x003048 => { Some("Supermicro") }
+    0x00408C => { Some("Axis Communications") }
+    0x005056 => { Some("VMware") }
+    0x0050BA => { Some("D-Link") }
+    0x0050F2 => { Some("Microsoft") }
+    0x00A0C9 => { Some("Intel") }
+    0x00E018 => { Some("ASUSTek") }
+    0x00E04C => { Some("Realtek") }
+    0x00E0FC => { Some("Huawei") }
+    0x0418D6 => { Some("Ubiquiti") }
+    0x080027 => { Some("PCS Systemtechnik (VirtualBox)") }
+    0x0CC47A => { Some("Supermicro") }
+    0x14CC20 => { Some("TP-Link") }
+    0x18B430 => { Some("Nest Labs") }
+    0x24A43C => { Some("Ubiquiti") }
+    0x28CDC1 => { Some("Raspberry Pi") }
+    0x28CFE9 => { Some("Apple") }
+    0x2C6BF5 => { Some("Juniper Networks") }
+    0x3C0754 => { Some("Apple") }
+    0x3C5AB4 => { Some("Google") }
+    0x3CD92B => { Some("Hewlett Packard") }
+    0x3CFDFE => { Some("Intel") }
+    0x444CA8 => { Some("Arista Networks") }
+    0x44650D => { Some("Amazon") }
+    0x44D9E7 => { Some("Ubiquiti") }
+    0x48B02D => { Some("NVIDIA") }
+    0x4C5E0C => { Some("MikroTik") }
+    0x50C7BF => { Some("TP-Link") }
+    0x525400 => { Some("QEMU virtual NIC") }
+    0x546009 => { Some("Google") }
+    0x5CAAFD => { Some("Sonos") }
+    0x641666 => { Some("Nest Labs") }
+    0x6C3B6B => { Some("MikroTik") }
+    0x74C246 => { Some("Amazon") }
+    0x788A20 => { Some("Ubiquiti") }
+    0x949F3E => { Some("Sonos") }
+    0xA0369F => { Some("Intel") }
+    0xA040A0 => { Some("Netgear") }
+    0xAC1F6B => { Some("Supermicro") }
+    0xACBC32 => { Some("Apple") }
+    0xACCC8E => { Some("Axis Communications") }
+    0xB827EB => { Some("Raspberry Pi") }
+    0xB8AC6F => { Some("Dell") }
+    0xB8E937 => { Some("Sonos") }
+    0xD83ADD => { Some("Raspberry Pi") }
+    0xDCA632 => { Some("Raspberry Pi") }
+    0xE45F01 => { Some("Raspberry Pi") }
+    0xEC086B => { Some("TP-Link") }
+    0xF01898 => { Some("Apple") }
+    0xF09FC2 => { Some("Ubiquiti") }
+    0xF4F26D => { Some("TP-Link") }
+    0xF4F5D8 => { Some("Google") }
+    0xF8B156 => { Some("Dell") }
+    0xFC65DE => { Some("Amazon") }
+    _ =>        { None }
+  }
+}
+
+/**Function returns the vendor of the network card a mac address belongs to.
+ * Addresses with the locally administered bit set were never assigned to a vendor, so they have none.
+ * Params:
+ *  mac: &MacAddr {The mac address}
+ * Returns Option<String>
+ */
+pub fn mac_vendor(mac: &MacAddr) -> Option<String> {
+  if mac.0 & 0x02 != 0 {
+    return None;
+  }
+
+  let prefix = (mac.0 as u32) << 16 | (mac.1 as u32) << 8 | mac.2 as u32;
+  vendor_map(prefix).map(String::from)
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn mac_vendor_known_prefix() {
+    assert_eq!(mac_vendor(&MacAddr::new(0xdc, 0xa6, 0x32, 0x12, 0x34, 0x56)), Some(String::from("Raspberry Pi")));
+    assert_eq!(mac_vendor(&MacAddr::new(0xf0, 0x9f, 0xc2, 0x00, 0x00, 0x01)), Some(String::from("Ubiquiti")));
+  }
+
+  #[test]
+  fn mac_vendor_unknown_or_local() {
+    assert_eq!(mac_vendor(&MacAddr::new(0x00, 0x00, 0x00, 0x00, 0x00, 0x01)), None);
+    assert_eq!(mac_vendor(&MacAddr::new(0x02, 0x42, 0xac, 0x11, 0x00, 0x02)), None);
+    assert_eq!(mac_vendor(&MacAddr::new(0xde, 0xa6, 0x32, 0x12, 0x34, 0x56)), None);
+  }
+}
@@ -0,0 +1,151 @@
+use std::collections::BTreeSet;
+use super::services::service_ports;
+use super::{MIN_PORT, MAX_PORT};
+
+// Describes why a port string could not be parsed and where the problem starts.
+#[derive(Debug, Clone)]
+pub struct PortSpecError {
+  pub position: usize,      // The character position of the problem in the port string.
+  pub message: String,
+}
+
+impl PortSpecError {
+  pub fn new(position: usize, message: String) -> PortSpecError {
+    PortSpecError {
+      position,
+      message,
+    }
+  }
+}
+
+/**Function parses a port number and makes sure that it is within the valid port range.
+ * Params:
+ *  text:     &str  {The port number as a slice}
+ *  position: usize {The position of the number in the port string}
+ * Returns Result<u32, PortSpecError>
+ */
+fn parse_port_number(text: &str, position: usize) -> Result<u32, PortSpecError> {
+  if text.is_empty() || text.chars().all(|c| c.is_ascii_digit()) == false {
+    return Err(PortSpecError::new(position, format!("'{}' is not a port number", text)));
+  }
+
+  // Anything that does not fit into a u32 is well above the maximum port anyway.
+  let port = text.parse::<u32>().unwrap_or(u32::MAX);
+
+  if port < MIN_PORT {
+    return Err(PortSpecError::new(position, format!("port {} is below {}", port, MIN_PORT)));
+  }
+
+  if port > MAX_PORT {
+    return Err(PortSpecError::new(position, format!("port {} is above {}", text, MAX_PORT)));
+  }
+
+  Ok(port)
+}
+
+/**Function parses a single item of the port string and returns the first and last port it covers.
+ * Items can be a port (22), a range (1-1024), an open ended range (-1024, 60000-) or a service name (ssh).
+ * Params:
+ *  item:     &str  {The item as a slice}
+ *  position: usize {The position of the item in the port string}
+ * Returns Result<Vec<(u32, u32)>, PortSpecError>
+ */
+fn parse_port_item(item: &str, position: usize) -> Result<Vec<(u32, u32)>, PortSpecError> {
+  let mut out: Vec<(u32, u32)> = Default::default();
+
+  if item.is_empty() {
+    return Err(PortSpecError::new(position, String::from("expected a port, range or service name")));
+  }
+
+  // Service names always start with a letter which keeps them apart from ranges.
+  if item.chars().next().unwrap_or('0').is_ascii_alphabetic() {
+    let ports = service_ports(item);
+
+    if ports.is_empty() {
+      return Err(PortSpecError::new(position, format!("unknown service name '{}'", item)));
+    }
+
+    for i in ports {
+      out.push((i as u32, i as u32));
+    }
+
+    return Ok(out);
+  }
+
+  match item.split_once('-') {
+    Some((start, end)) => {
+      let port_start = if start.is_empty() { MIN_PORT } else { parse_port_number(start, position)? };
+      let port_end = if end.is_empty() { MAX_PORT } else { parse_port_number(end, position + start.len() + 1)? };
+
+      if port_start > port_end {
+        return Err(PortSpecError::new(position, format!("range {} starts after it ends", item)));
+      }
+
+      out.push((port_start, port_end));
+    },
+
+    None => {
+      let port = parse_port_number(item, position)?;
+      out.push((port, port));
+    }
+  }
+
+  Ok(out)
+}
+
+/**Function parses a port string made of comma separated items and returns every port it describes.
+ * The returned ports are sorted and contain no duplicates.
+ * Params:
+ *  spec: &str {The port string. Example: 22,80,8000-8100,-1024,60000-,ssh}
+ * Returns Result<Vec<u16>, PortSpecError>
+ */
+pub fn parse_port_spec(spec: &str) -> Result<Vec<u16>, PortSpecError> {
+  let mut ports: BTreeSet<u16> = Default::default();
+  let mut position: usize = 0;
+
+  for raw_item in spec.split(',') {
+    let item = raw_item.trim();
+    let item_position = position + (raw_item.len() - raw_item.trim_start().len());
+
+    for (start, end) in parse_port_item(item, item_position)? {
+      for i in start..=end {
+        ports.insert(i as u16);
+      }
+    }
+
+    position += raw_item.len() + 1;
+  }
+
+  Ok(ports.into_iter().collect())
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn parse_port_spec_mixed_items() {
+    assert_eq!(parse_port_spec("80").unwrap(), vec![80]);
+    assert_eq!(parse_port_spec("443, 22,80-82,22").unwrap(), vec![22, 80, 81, 82, 443]);
+    assert_eq!(parse_port_spec("-3").unwrap(), vec![1, 2, 3]);
+    assert_eq!(parse_port_spec("65534-").unwrap(), vec![65534, 65535]);
+    assert_eq!(parse_port_spec("-").unwrap().len(), 65535);
+    assert!(parse_port_spec("SSH,http").unwrap().starts_with(&[22]));
+    assert!(parse_port_spec("ssh,http").unwrap().contains(&80));
+  }
+
+  #[test]
+  fn parse_port_spec_reports_position() {
+    let cases = [
+      ("", 0), ("80,", 3), ("80,,81", 3), ("0", 0), ("65536", 0), ("99999999999999", 0),
+      ("22, 90-80", 4), ("1-x", 2), ("80,notaservice", 3), ("8o", 0), ("\u{e9}", 0),
+    ];
+
+    for (spec, position) in cases {
+      match parse_port_spec(spec) {
+        Ok(s) =>  { panic!("{} parsed as {:?}", spec, s); },
+        Err(e) => { assert_eq!(e.position, position, "{}: {}", spec, e.message); }
+      }
+    }
+  }
+}
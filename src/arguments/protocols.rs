@@ -0,0 +1,240 @@
+use std::io::{Error, Read, Write};
+use std::net::{SocketAddr, TcpStream};
+use std::time::Duration;
+use serde::Serialize;
+use super::SourceBinding;
+
+mod ssh;
+mod smtp;
+mod ftp;
+mod mysql;
+mod postgres;
+mod redis;
+mod smb;
+pub use ssh::*;
+pub use smtp::*;
+pub use ftp::*;
+pub use mysql::*;
+pub use postgres::*;
+pub use redis::*;
+pub use smb::*;
+
+const MAX_REPLY_LEN: usize = 65536;       // The most bytes we read while waiting for a reply.
+
+// What a protocol parser learned about a service. Each variant is written to json under the name of its protocol.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ProtocolInfo {
+  Ssh(SshInfo),
+  Smtp(SmtpInfo),
+  Ftp(FtpInfo),
+  Mysql(MysqlInfo),
+  Postgres(PostgresInfo),
+  Redis(RedisInfo),
+  Smb(SmbInfo),
+}
+
+impl ProtocolInfo {
+
+  // Returns the name of the protocol.
+  pub fn name(&self) -> &'static str {
+    match self {
+      ProtocolInfo::Ssh(_) =>      { "ssh" }
+      ProtocolInfo::Smtp(_) =>     { "smtp" }
+      ProtocolInfo::Ftp(_) =>      { "ftp" }
+      ProtocolInfo::Mysql(_) =>    { "mysql" }
+      ProtocolInfo::Postgres(_) => { "postgres" }
+      ProtocolInfo::Redis(_) =>    { "redis" }
+      ProtocolInfo::Smb(_) =>      { "smb" }
+    }
+  }
+
+  // Returns the fields worth showing on screen as labels and values.
+  pub fn details(&self) -> Vec<(&'static str, String)> {
+    match self {
+      ProtocolInfo::Ssh(s) =>      { s.details() }
+      ProtocolInfo::Smtp(s) =>     { s.details() }
+      ProtocolInfo::Ftp(s) =>      { s.details() }
+      ProtocolInfo::Mysql(s) =>    { s.details() }
+      ProtocolInfo::Postgres(s) => { s.details() }
+      ProtocolInfo::Redis(s) =>    { s.details() }
+      ProtocolInfo::Smb(s) =>      { s.details() }
+    }
+  }
+}
+
+/**Function picks the parser that suits a service from its banner or port and runs it.
+ * Services that talk first are recognised by their greeting, quiet ones by their usual port.
+ * Params:
+ *  address: &SocketAddr    {The address and port of the service}
+ *  banner:  &[u8]          {What the banner grab got back}
+ *  timeout: Duration       {How long to wait for the connection and for each reply}
+ *  binding: &SourceBinding {The interface and address picked by the user}
+ * Returns Option<ProtocolInfo>
+ */
+pub fn inspect_protocol(address: &SocketAddr, banner: &[u8], timeout: Duration, binding: &SourceBinding) -> Option<ProtocolInfo> {
+  if banner.starts_with(b"SSH-") == true {
+    return inspect_ssh(address, timeout, binding).map(ProtocolInfo::Ssh);
+  }
+
+  if let Some(info) = parse_mysql_handshake(banner) {
+    return Some(ProtocolInfo::Mysql(info));
+  }
+
+  if is_redis_reply(banner) == true {
+    return inspect_redis(address, banner, timeout, binding).map(ProtocolInfo::Redis);
+  }
+
+  if banner.starts_with(b"220") == true {
+    let text = String::from_utf8_lossy(banner).to_lowercase();
+
+    if text.contains("ftp") == true || address.port() == 21 {
+      return inspect_ftp(address, timeout, binding).map(ProtocolInfo::Ftp);
+    }
+
+    if text.contains("smtp") == true || text.contains("mail") == true || [25, 465, 587, 2525].contains(&address.port()) {
+      return inspect_smtp(address, timeout, binding).map(ProtocolInfo::Smtp);
+    }
+  }
+
+  match address.port() {
+    5432 => { inspect_postgres(address, timeout, binding).map(ProtocolInfo::Postgres) },
+    445 =>  { inspect_smb(address, timeout, binding).map(ProtocolInfo::Smb) },
+    _ =>    { None }
+  }
+}
+
+/**Function connects to a service with the timeouts set for reading and writing.
+ * Params:
+ *  address: &SocketAddr    {The address and port of the service}
+ *  timeout: Duration       {How long to wait for the connection and for each reply}
+ *  binding: &SourceBinding {The interface and address picked by the user}
+ * Returns Result<TcpStream, Error>
+ */
+pub fn open_stream(address: &SocketAddr, timeout: Duration, binding: &SourceBinding) -> Result<TcpStream, Error> {
+  let stream = binding.connect_timeout(address, timeout)?;
+  stream.set_read_timeout(Some(timeout.max(Duration::from_millis(1))))?;
+  stream.set_write_timeout(Some(timeout.max(Duration::from_millis(1))))?;
+  Ok(stream)
+}
+
+/**Function keeps reading until the reply is complete, the service stops talking or the reply is too long.
+ * Params:
+ *  stream:   &mut R            {The connection to the service}
+ *  buffer:   &mut Vec<u8>      {Where the reply is collected, anything already in it counts}
+ *  complete: Fn(&[u8]) -> bool {Returns true once the reply is complete}
+ * Returns bool (true when the reply is complete)
+ */
+pub fn read_until<R: Read>(stream: &mut R, buffer: &mut Vec<u8>, complete: impl Fn(&[u8]) -> bool) -> bool {
+  let mut chunk = [0u8; 4096];
+
+  while complete(buffer) == false {
+    if buffer.len() >= MAX_REPLY_LEN {
+      return false;
+    }
+
+    match stream.read(&mut chunk) {
+      Ok(0) =>    { return false; },
+      Ok(size) => { buffer.extend_from_slice(&chunk[..size]); },
+      Err(_) =>   { return false; }
+    }
+  }
+
+  true
+}
+
+/**Function sends a command to a text protocol that answers with numbered replies and reads the whole reply.
+ * Params:
+ *  stream:  &mut TcpStream {The connection to the service}
+ *  command: &str           {The command without its line ending, nothing is sent when it is empty}
+ * Returns Option<(u16, Vec<String>)> (The reply code and each line of the reply without its code)
+ */
+pub fn text_command(stream: &mut TcpStream, command: &str) -> Option<(u16, Vec<String>)> {
+  if !command.is_empty() {
+    stream.write_all(format!("{}\r\n", command).as_bytes()).ok()?;
+  }
+
+  let mut buffer: Vec<u8> = Default::default();
+  if read_until(stream, &mut buffer, reply_complete) == false {
+    return None;
+  }
+
+  parse_reply(&buffer)
+}
+
+/**Function splits a numbered reply into its code and lines.
+ * Params:
+ *  data: &[u8] {The whole reply}
+ * Returns Option<(u16, Vec<String>)> (The reply code and each line of the reply without its code)
+ */
+pub fn parse_reply(data: &[u8]) -> Option<(u16, Vec<String>)> {
+  let text = String::from_utf8_lossy(data).to_string();
+  let lines: Vec<&str> = text.lines().collect();
+  let code = lines.last()?.get(..3)?.parse::<u16>().ok()?;
+
+  let out = lines.iter().map(|i| {
+    match i.get(..3).map(|c| c.parse::<u16>().is_ok()) {
+      Some(true) => { i.get(4..).unwrap_or_default().to_string() },
+      _ =>          { i.trim().to_string() }
+    }
+  }).collect();
+
+  Some((code, out))
+}
+
+// Returns true once the last line of a numbered reply has arrived. It has a space after the code instead of a dash.
+pub fn reply_complete(data: &[u8]) -> bool {
+  if data.ends_with(b"\n") == false {
+    return false;
+  }
+
+  let text = String::from_utf8_lossy(data);
+  match text.trim_end().lines().last() {
+    Some(line) => { line.len() >= 3 && line.as_bytes()[..3].iter().all(|i| i.is_ascii_digit()) && line.as_bytes().get(3) != Some(&b'-') },
+    None =>       { false }
+  }
+}
+
+// Reads a null terminated string from the start of the data and returns it with the rest of the data.
+pub fn null_string(data: &[u8]) -> Option<(String, &[u8])> {
+  let end = data.iter().position(|i| *i == 0)?;
+  Some((String::from_utf8_lossy(&data[..end]).to_string(), &data[end + 1..]))
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn parse_reply_multiline() {
+    let (code, lines) = parse_reply(b"250-mail.example.com\r\n250-SIZE 1000\r\n250 STARTTLS\r\n").unwrap();
+    assert_eq!(code, 250);
+    assert_eq!(lines, vec!["mail.example.com", "SIZE 1000", "STARTTLS"]);
+  }
+
+  #[test]
+  fn parse_reply_malformed() {
+    assert_eq!(parse_reply(b""), None);
+    assert_eq!(parse_reply(b"2"), None);
+    assert_eq!(parse_reply(b"\xff\xfe\xfd\r\n"), None);
+    assert_eq!(parse_reply(b"22\xc3\xa9 x\r\n"), None);
+    assert_eq!(parse_reply(b"220\xc3\xa9\r\n").map(|i| i.0), Some(220));
+  }
+
+  #[test]
+  fn reply_complete_waits_for_last_line() {
+    assert!(!reply_complete(b""));
+    assert!(!reply_complete(b"\r\n"));
+    assert!(!reply_complete(b"250-first\r\n"));
+    assert!(!reply_complete(b"250 last"));
+    assert!(reply_complete(b"250-first\r\n250 last\r\n"));
+    assert!(!reply_complete(b"\xff\xfe\n"));
+  }
+
+  #[test]
+  fn null_string_needs_terminator() {
+    assert_eq!(null_string(b""), None);
+    assert_eq!(null_string(b"abc"), None);
+    assert_eq!(null_string(b"ab\0cd"), Some((String::from("ab"), &b"cd"[..])));
+  }
+}
@@ -0,0 +1,93 @@
+use std::net::SocketAddr;
+use std::time::Duration;
+use serde::Serialize;
+use super::{open_stream, text_command, SourceBinding};
+
+// The greeting of an ftp server, its features and whether it lets anyone log in.
+#[derive(Debug, Clone, Default, PartialEq, Serialize)]
+pub struct FtpInfo {
+  pub greeting: String,
+  pub features: Vec<String>,      // The answer to feat, one feature per entry.
+  pub auth_tls: bool,
+  pub anonymous: bool,            // The server accepted the anonymous user.
+
+  #[serde(skip_serializing_if = "Option::is_none")]
+  pub system: Option<String>,     // The answer to syst, only asked once we are logged in.
+}
+
+impl FtpInfo {
+  pub fn details(&self) -> Vec<(&'static str, String)> {
+    let mut out = vec![("Greeting", self.greeting.clone()), ("Anonymous", self.anonymous.to_string()), ("AUTH TLS", self.auth_tls.to_string())];
+
+    if let Some(system) = self.system.as_ref() {
+      out.push(("System", system.clone()));
+    }
+
+    out
+  }
+}
+
+/**Function reads the greeting and features of an ftp server and tries to log in as the anonymous user.
+ * Params:
+ *  address: &SocketAddr    {The address and port of the server}
+ *  timeout: Duration       {How long to wait for the connection and for each reply}
+ *  binding: &SourceBinding {The interface and address picked by the user}
+ * Returns Option<FtpInfo>
+ */
+pub fn inspect_ftp(address: &SocketAddr, timeout: Duration, binding: &SourceBinding) -> Option<FtpInfo> {
+  let mut stream = open_stream(address, timeout, binding).ok()?;
+
+  let (code, greeting) = text_command(&mut stream, "")?;
+  if code != 220 {
+    return None;
+  }
+
+  let mut out = FtpInfo { greeting: greeting.join(" "), ..Default::default() };
+
+  if let Some((211, lines)) = text_command(&mut stream, "FEAT") {
+    out.features = parse_features(&lines);
+    out.auth_tls = out.features.iter().any(|i| i.to_uppercase().starts_with("AUTH") && i.to_uppercase().contains("TLS"));
+  }
+
+  out.anonymous = match text_command(&mut stream, "USER anonymous") {
+    Some((230, _)) => { true },
+    Some((331, _)) => { matches!(text_command(&mut stream, "PASS lethal_potato@"), Some((230, _))) },
+    _ =>              { false }
+  };
+
+  if out.anonymous == true {
+    if let Some((215, lines)) = text_command(&mut stream, "SYST") {
+      out.system = Some(lines.join(" "));
+    }
+  }
+
+  let _ = text_command(&mut stream, "QUIT");
+  Some(out)
+}
+
+// Returns the features listed in the answer to feat. The first and last lines of the answer are not features.
+pub fn parse_features(lines: &[String]) -> Vec<String> {
+  lines.iter().skip(1).take(lines.len().saturating_sub(2)).map(|i| i.trim().to_string()).filter(|i| !i.is_empty()).collect()
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  fn lines(list: &[&str]) -> Vec<String> {
+    list.iter().map(|i| i.to_string()).collect()
+  }
+
+  #[test]
+  fn parse_features_skips_first_and_last() {
+    assert_eq!(parse_features(&lines(&["Features:", " AUTH TLS", " UTF8", "End"])), vec!["AUTH TLS", "UTF8"]);
+  }
+
+  #[test]
+  fn parse_features_malformed() {
+    assert_eq!(parse_features(&lines(&[])).len(), 0);
+    assert_eq!(parse_features(&lines(&["No features"])).len(), 0);
+    assert_eq!(parse_features(&lines(&["Features:", "End"])).len(), 0);
+    assert_eq!(parse_features(&lines(&["Features:", "   ", " \u{fffd}\u{fffd}", "End"])), vec!["\u{fffd}\u{fffd}"]);
+  }
+}
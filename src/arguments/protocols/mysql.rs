@@ -0,0 +1,148 @@
+use serde::Serialize;
+use super::null_string;
+
+// What a mysql or mariadb server tells every client in its first packet.
+#[derive(Debug, Clone, Default, PartialEq, Serialize)]
+pub struct MysqlInfo {
+  pub protocol_version: u8,
+  pub server_version: String,
+  pub connection_id: u32,
+
+  #[serde(skip_serializing_if = "Option::is_none")]
+  pub auth_plugin: Option<String>,
+
+  #[serde(skip_serializing_if = "Option::is_none")]
+  pub error: Option<String>,        // Servers that refuse our address send an error instead of the handshake.
+}
+
+impl MysqlInfo {
+  pub fn details(&self) -> Vec<(&'static str, String)> {
+    if let Some(error) = self.error.as_ref() {
+      return vec![("Error", error.clone())];
+    }
+
+    let mut out = vec![("Version", self.server_version.clone()), ("Connection", self.connection_id.to_string())];
+    if let Some(plugin) = self.auth_plugin.as_ref() {
+      out.push(("Auth plugin", plugin.clone()));
+    }
+
+    out
+  }
+}
+
+/**Function reads the handshake a mysql server sends once a client connects.
+ * Params:
+ *  data: &[u8] {The banner of the server}
+ * Returns Option<MysqlInfo> (None when the banner is not a mysql handshake)
+ */
+pub fn parse_mysql_handshake(data: &[u8]) -> Option<MysqlInfo> {
+  let length = u32::from_le_bytes([*data.first()?, *data.get(1)?, *data.get(2)?, 0]) as usize;
+
+  // The first packet always has sequence number 0 and fits in the banner when it was not cut short.
+  if length < 2 || *data.get(3)? != 0 || data.len() < 5 {
+    return None;
+  }
+
+  let payload = &data[4..data.len().min(4 + length)];
+
+  match payload[0] {
+    0x0a => { parse_greeting(payload) },
+    0xff => {
+      let code = u16::from_le_bytes([*payload.get(1)?, *payload.get(2)?]);
+
+      // Newer servers put a sql state such as #HY000 before the message.
+      let message = match payload.get(3) {
+        Some(b'#') => { String::from_utf8_lossy(payload.get(9..).unwrap_or_default()).to_string() },
+        _ =>          { String::from_utf8_lossy(payload.get(3..)?).to_string() }
+      };
+
+      if message.chars().any(|i| i.is_control()) == true {
+        return None;
+      }
+
+      Some(MysqlInfo { error: Some(format!("{} {}", code, message)), ..Default::default() })
+    },
+
+    _ => { None }
+  }
+}
+
+// Reads the fields of a protocol 10 handshake.
+fn parse_greeting(payload: &[u8]) -> Option<MysqlInfo> {
+  let (server_version, rest) = null_string(&payload[1..])?;
+  if server_version.is_empty() || server_version.chars().all(|i| i.is_ascii_graphic()) == false {
+    return None;
+  }
+
+  let mut out = MysqlInfo {
+    protocol_version: payload[0],
+    server_version,
+    connection_id: u32::from_le_bytes(rest.get(..4)?.try_into().ok()?),
+    ..Default::default()
+  };
+
+  // Salt, filler, capabilities, character set, status, more capabilities, salt length and reserved bytes come before the rest of the salt.
+  let salt_len = *rest.get(4 + 8 + 1 + 2 + 1 + 2 + 2).unwrap_or(&0) as usize;
+  let plugin_start = 4 + 8 + 1 + 2 + 1 + 2 + 2 + 1 + 10 + salt_len.saturating_sub(8).max(13);
+
+  if let Some((plugin, _)) = rest.get(plugin_start..).and_then(null_string) {
+    if !plugin.is_empty() {
+      out.auth_plugin = Some(plugin);
+    }
+  }
+
+  Some(out)
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  // Wraps a payload in a packet header with sequence number 0.
+  fn packet(payload: &[u8]) -> Vec<u8> {
+    let mut out = (payload.len() as u32).to_le_bytes()[..3].to_vec();
+    out.push(0);
+    out.extend_from_slice(payload);
+    out
+  }
+
+  #[test]
+  fn parse_greeting_reads_version() {
+    let mut payload = vec![0x0a];
+    payload.extend_from_slice(b"8.0.36\0");
+    payload.extend_from_slice(&42u32.to_le_bytes());
+    payload.extend_from_slice(&[0x61; 8]);
+    payload.extend_from_slice(&[0, 0xff, 0xff, 0x21, 0x02, 0, 0xff, 0xdf, 21]);
+    payload.extend_from_slice(&[0; 10]);
+    payload.extend_from_slice(&[0x62; 12]);
+    payload.push(0);
+    payload.extend_from_slice(b"caching_sha2_password\0");
+
+    let info = parse_mysql_handshake(&packet(&payload)).unwrap();
+    assert_eq!(info.protocol_version, 10);
+    assert_eq!(info.server_version, "8.0.36");
+    assert_eq!(info.connection_id, 42);
+    assert_eq!(info.auth_plugin, Some(String::from("caching_sha2_password")));
+  }
+
+  #[test]
+  fn parse_error_with_sql_state() {
+    let info = parse_mysql_handshake(&packet(b"\xff\x6a\x04#HY000Host is not allowed")).unwrap();
+    assert_eq!(info.error, Some(String::from("1130 Host is not allowed")));
+  }
+
+  #[test]
+  fn parse_mysql_handshake_malformed() {
+    assert_eq!(parse_mysql_handshake(b""), None);
+    assert_eq!(parse_mysql_handshake(b"\x05\x00"), None);
+    assert_eq!(parse_mysql_handshake(b"\x05\x00\x00\x00"), None);
+    assert_eq!(parse_mysql_handshake(b"\x05\x00\x00\x01\x0a8.0"), None);
+    assert_eq!(parse_mysql_handshake(&packet(b"\x0a8.0.36")), None);
+    assert_eq!(parse_mysql_handshake(&packet(b"\x0a8.0.36\0\x01\x02")), None);
+    assert_eq!(parse_mysql_handshake(&packet(b"\x0a\xc3\xa9\0\x01\x02\x03\x04")), None);
+    assert_eq!(parse_mysql_handshake(&packet(b"\xff\x01")), None);
+    assert!(parse_mysql_handshake(&packet(b"\xff\x01\x00#\xc3\xa9\xc3\xa9\xc3\xa9")).is_some());
+    assert!(parse_mysql_handshake(&packet(b"\xff\x01\x00#")).is_some());
+    assert!(parse_mysql_handshake(&packet(b"\xff\x01\x00\xfe\xff")).is_some());
+  }
+}
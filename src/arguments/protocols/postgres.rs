@@ -0,0 +1,211 @@
+use std::io::Write;
+use std::net::SocketAddr;
+use std::time::Duration;
+use serde::Serialize;
+use super::{null_string, open_stream, read_until, SourceBinding};
+
+const PROTOCOL_VERSION: u32 = 196608;     // Version 3.0 of the frontend and backend protocol.
+
+// What a postgresql server tells a client that asks to log in as the postgres user.
+#[derive(Debug, Clone, Default, PartialEq, Serialize)]
+pub struct PostgresInfo {
+  #[serde(skip_serializing_if = "Option::is_none")]
+  pub server_version: Option<String>,     // Only sent once the login succeeds.
+
+  #[serde(skip_serializing_if = "Option::is_none")]
+  pub authentication: Option<String>,     // The login method the server asked for.
+
+  #[serde(skip_serializing_if = "Option::is_none")]
+  pub error: Option<String>,
+}
+
+impl PostgresInfo {
+  pub fn details(&self) -> Vec<(&'static str, String)> {
+    let mut out: Vec<(&'static str, String)> = Default::default();
+
+    for (name, value) in [("Version", &self.server_version), ("Auth", &self.authentication), ("Error", &self.error)] {
+      if let Some(value) = value {
+        out.push((name, value.clone()));
+      }
+    }
+
+    out
+  }
+}
+
+/**Function starts a login as the postgres user and records how the server answers. Servers that trust us send
+ * their version, every other server tells us how it wants us to log in.
+ * Params:
+ *  address: &SocketAddr    {The address and port of the server}
+ *  timeout: Duration       {How long to wait for the connection and for each reply}
+ *  binding: &SourceBinding {The interface and address picked by the user}
+ * Returns Option<PostgresInfo> (None when the port does not speak the postgresql protocol)
+ */
+pub fn inspect_postgres(address: &SocketAddr, timeout: Duration, binding: &SourceBinding) -> Option<PostgresInfo> {
+  let mut stream = open_stream(address, timeout, binding).ok()?;
+  stream.write_all(&startup_message()).ok()?;
+
+  let mut buffer: Vec<u8> = Default::default();
+  read_until(&mut stream, &mut buffer, startup_finished);
+
+  let out = parse_startup_reply(&buffer)?;
+
+  // Terminate, so the server does not log a lost connection.
+  let _ = stream.write_all(&[b'X', 0, 0, 0, 4]);
+  Some(out)
+}
+
+// Builds the startup message for the postgres user and database.
+fn startup_message() -> Vec<u8> {
+  let mut body = PROTOCOL_VERSION.to_be_bytes().to_vec();
+
+  for i in ["user", "postgres", "database", "postgres", "application_name", "lethal_potato"] {
+    body.extend_from_slice(i.as_bytes());
+    body.push(0);
+  }
+
+  body.push(0);
+
+  let mut out = ((body.len() + 4) as u32).to_be_bytes().to_vec();
+  out.extend_from_slice(&body);
+  out
+}
+
+// Splits a reply into its messages. A message cut short ends the list.
+fn messages(data: &[u8]) -> Vec<(u8, &[u8])> {
+  let mut out: Vec<(u8, &[u8])> = Default::default();
+  let mut idx = 0;
+
+  while idx + 5 <= data.len() {
+    let len = u32::from_be_bytes([data[idx + 1], data[idx + 2], data[idx + 3], data[idx + 4]]) as usize;
+    if len < 4 || idx + 1 + len > data.len() {
+      break;
+    }
+
+    out.push((data[idx], &data[idx + 5..idx + 1 + len]));
+    idx += 1 + len;
+  }
+
+  out
+}
+
+// Returns true once the server asked for a password, failed the login or is ready for queries.
+fn startup_finished(data: &[u8]) -> bool {
+  messages(data).iter().any(|(kind, body)| {
+    match kind {
+      b'E' | b'Z' => { true },
+      b'R' =>        { body.get(..4) != Some(&[0, 0, 0, 0]) },
+      _ =>           { false }
+    }
+  })
+}
+
+/**Function reads the messages a server sends in answer to the startup message.
+ * Params:
+ *  data: &[u8] {The reply of the server}
+ * Returns Option<PostgresInfo> (None when the reply is not from a postgresql server)
+ */
+pub fn parse_startup_reply(data: &[u8]) -> Option<PostgresInfo> {
+  let list = messages(data);
+  if list.is_empty() || [b'R', b'E'].contains(&list[0].0) == false {
+    return None;
+  }
+
+  let mut out = PostgresInfo::default();
+
+  for (kind, body) in list {
+    match kind {
+      b'R' => {
+        let method = u32::from_be_bytes(body.get(..4)?.try_into().ok()?);
+
+        out.authentication = Some(match method {
+          0 =>  { String::from("trust") },
+          3 =>  { String::from("password") },
+          5 =>  { String::from("md5") },
+          7 =>  { String::from("gss") },
+          9 =>  { String::from("sspi") },
+          10 => {
+            let mechanisms: Vec<String> = body[4..].split(|i| *i == 0).filter(|i| !i.is_empty()).map(|i| String::from_utf8_lossy(i).to_string()).collect();
+            format!("sasl ({})", mechanisms.join(", "))
+          },
+
+          other => { format!("unknown ({})", other) }
+        });
+      },
+
+      b'S' => {
+        if let Some((name, rest)) = null_string(body) {
+          if name == "server_version" {
+            out.server_version = null_string(rest).map(|i| i.0);
+          }
+        }
+      },
+
+      // Errors are made of fields that start with a type byte, M holds the message.
+      b'E' => {
+        out.error = body.split(|i| *i == 0).find(|i| i.first() == Some(&b'M')).map(|i| String::from_utf8_lossy(&i[1..]).to_string());
+      },
+
+      _ => {}
+    }
+  }
+
+  Some(out)
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  // Builds a message of the given type.
+  fn message(kind: u8, body: &[u8]) -> Vec<u8> {
+    let mut out = vec![kind];
+    out.extend_from_slice(&((body.len() + 4) as u32).to_be_bytes());
+    out.extend_from_slice(body);
+    out
+  }
+
+  #[test]
+  fn parse_startup_reply_sasl() {
+    let data = message(b'R', b"\0\0\0\x0aSCRAM-SHA-256\0SCRAM-SHA-256-PLUS\0\0");
+    let info = parse_startup_reply(&data).unwrap();
+
+    assert!(startup_finished(&data));
+    assert_eq!(info.authentication, Some(String::from("sasl (SCRAM-SHA-256, SCRAM-SHA-256-PLUS)")));
+  }
+
+  #[test]
+  fn parse_startup_reply_trust() {
+    let mut data = message(b'R', &[0, 0, 0, 0]);
+    assert!(!startup_finished(&data));
+
+    data.extend_from_slice(&message(b'S', b"server_version\x0016.2\0"));
+    data.extend_from_slice(&message(b'Z', b"I"));
+
+    let info = parse_startup_reply(&data).unwrap();
+    assert!(startup_finished(&data));
+    assert_eq!(info.authentication, Some(String::from("trust")));
+    assert_eq!(info.server_version, Some(String::from("16.2")));
+  }
+
+  #[test]
+  fn parse_startup_reply_error() {
+    let data = message(b'E', b"SFATAL\0C28000\0Mno pg_hba.conf entry\0\0");
+    assert_eq!(parse_startup_reply(&data).unwrap().error, Some(String::from("no pg_hba.conf entry")));
+  }
+
+  #[test]
+  fn parse_startup_reply_malformed() {
+    let data = message(b'R', &[0, 0, 0, 5, 1, 2, 3, 4]);
+
+    assert_eq!(parse_startup_reply(b""), None);
+    assert_eq!(parse_startup_reply(&data[..3]), None);
+    assert_eq!(parse_startup_reply(&data[..data.len() - 1]), None);
+    assert_eq!(parse_startup_reply(b"R\0\0\0\x02"), None);
+    assert_eq!(parse_startup_reply(b"R\xff\xff\xff\xff"), None);
+    assert_eq!(parse_startup_reply(&message(b'S', b"server_version\0")), None);
+    assert_eq!(parse_startup_reply(&message(b'R', b"\0\0")), None);
+    assert!(parse_startup_reply(&message(b'E', b"M\xff\xfe\0")).is_some());
+    assert!(parse_startup_reply(&message(b'E', b"")).is_some());
+  }
+}
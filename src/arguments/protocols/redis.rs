@@ -0,0 +1,159 @@
+use std::io::Write;
+use std::net::SocketAddr;
+use std::time::Duration;
+use serde::Serialize;
+use super::{open_stream, read_until, SourceBinding};
+
+// What a redis server says about itself in its answer to info.
+#[derive(Debug, Clone, Default, PartialEq, Serialize)]
+pub struct RedisInfo {
+  pub auth_required: bool,            // The server wants a password before it answers info.
+
+  #[serde(skip_serializing_if = "Option::is_none")]
+  pub version: Option<String>,
+
+  #[serde(skip_serializing_if = "Option::is_none")]
+  pub mode: Option<String>,           // standalone, cluster or sentinel.
+
+  #[serde(skip_serializing_if = "Option::is_none")]
+  pub role: Option<String>,
+
+  #[serde(skip_serializing_if = "Option::is_none")]
+  pub os: Option<String>,
+
+  #[serde(skip_serializing_if = "Option::is_none")]
+  pub connected_clients: Option<u64>,
+}
+
+impl RedisInfo {
+  pub fn details(&self) -> Vec<(&'static str, String)> {
+    let mut out = vec![("Auth", self.auth_required.to_string())];
+
+    for (name, value) in [("Version", &self.version), ("Mode", &self.mode), ("Role", &self.role), ("OS", &self.os)] {
+      if let Some(value) = value {
+        out.push((name, value.clone()));
+      }
+    }
+
+    out
+  }
+}
+
+// Returns true when the banner is the answer of a redis server to ping.
+pub fn is_redis_reply(banner: &[u8]) -> bool {
+  [&b"+PONG"[..], b"-NOAUTH", b"-DENIED", b"-WRONGPASS"].iter().any(|i| banner.starts_with(i))
+}
+
+/**Function asks a redis server for its info without logging in.
+ * Params:
+ *  address: &SocketAddr    {The address and port of the server}
+ *  banner:  &[u8]          {The answer of the server to ping}
+ *  timeout: Duration       {How long to wait for the connection and for each reply}
+ *  binding: &SourceBinding {The interface and address picked by the user}
+ * Returns Option<RedisInfo>
+ */
+pub fn inspect_redis(address: &SocketAddr, banner: &[u8], timeout: Duration, binding: &SourceBinding) -> Option<RedisInfo> {
+  if banner.starts_with(b"+PONG") == false {
+    return Some(RedisInfo { auth_required: true, ..Default::default() });
+  }
+
+  let mut stream = open_stream(address, timeout, binding).ok()?;
+  stream.write_all(b"INFO\r\n").ok()?;
+
+  let mut buffer: Vec<u8> = Default::default();
+  read_until(&mut stream, &mut buffer, bulk_complete);
+
+  if buffer.starts_with(b"-NOAUTH") == true {
+    return Some(RedisInfo { auth_required: true, ..Default::default() });
+  }
+
+  Some(parse_info(&String::from_utf8_lossy(&buffer)))
+}
+
+// Returns true once the whole bulk string, or an error, has arrived.
+fn bulk_complete(data: &[u8]) -> bool {
+  if data.starts_with(b"-") == true {
+    return data.ends_with(b"\r\n");
+  }
+
+  let header_end = match data.windows(2).position(|i| i == b"\r\n") {
+    Some(s) => { s },
+    None =>    { return false; }
+  };
+
+  let header = match data.get(1..header_end) {
+    Some(s) => { s },
+    None =>    { return false; }
+  };
+
+  match String::from_utf8_lossy(header).parse::<usize>() {
+    Ok(len) => { data.len() >= header_end.saturating_add(2).saturating_add(len) },
+    Err(_) =>  { true }
+  }
+}
+
+/**Function reads the fields we keep from the answer to info.
+ * Params:
+ *  text: &str {The answer of the server}
+ * Returns RedisInfo
+ */
+pub fn parse_info(text: &str) -> RedisInfo {
+  let mut out = RedisInfo::default();
+
+  for (key, value) in text.lines().filter_map(|i| i.split_once(':')) {
+    let value = value.trim().to_string();
+
+    match key {
+      "redis_version" =>     { out.version = Some(value); },
+      "redis_mode" =>        { out.mode = Some(value); },
+      "role" =>              { out.role = Some(value); },
+      "os" =>                { out.os = Some(value); },
+      "connected_clients" => { out.connected_clients = value.parse::<u64>().ok(); },
+      _ => {}
+    }
+  }
+
+  out
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn bulk_complete_waits_for_length() {
+    assert!(!bulk_complete(b"$10\r\nredis"));
+    assert!(bulk_complete(b"$5\r\nredis\r\n"));
+    assert!(bulk_complete(b"-NOAUTH Authentication required.\r\n"));
+  }
+
+  #[test]
+  fn bulk_complete_malformed() {
+    assert!(!bulk_complete(b""));
+    assert!(!bulk_complete(b"\r\n"));
+    assert!(!bulk_complete(b"\r\nabc"));
+    assert!(!bulk_complete(b"$"));
+    assert!(bulk_complete(b"$\r\n"));
+    assert!(bulk_complete(b"$\xff\xfe\r\n"));
+    assert!(!bulk_complete(b"$18446744073709551615\r\n"));
+  }
+
+  #[test]
+  fn parse_info_reads_fields() {
+    let info = parse_info("$60\r\n# Server\r\nredis_version:7.2.4\r\nredis_mode:standalone\r\nos:Linux\r\nconnected_clients:3\r\n");
+
+    assert_eq!(info.version, Some(String::from("7.2.4")));
+    assert_eq!(info.mode, Some(String::from("standalone")));
+    assert_eq!(info.os, Some(String::from("Linux")));
+    assert_eq!(info.connected_clients, Some(3));
+  }
+
+  #[test]
+  fn parse_info_malformed() {
+    assert_eq!(parse_info(""), RedisInfo::default());
+    assert_eq!(parse_info("\r\n:\r\nredis_version"), RedisInfo::default());
+    assert_eq!(parse_info(&String::from_utf8_lossy(b"role:\xff\r\nconnected_clients:x")).role, Some(String::from("\u{fffd}")));
+    assert!(!is_redis_reply(b""));
+    assert!(!is_redis_reply(b"\xff+PONG"));
+  }
+}
@@ -0,0 +1,237 @@
+use std::io::Write;
+use std::net::SocketAddr;
+use std::time::Duration;
+use chrono::{TimeZone, Utc};
+use serde::Serialize;
+use crate::arguments::hex_banner;
+use super::{open_stream, read_until, SourceBinding};
+
+const SMB2_HEADER_LEN: usize = 64;
+const DIALECTS: [u16; 5] = [0x0202, 0x0210, 0x0300, 0x0302, 0x0311];   // Every smb2 and smb3 dialect, so the server picks its newest.
+const FILETIME_EPOCH: i64 = 11644473600;                                // Seconds between 1601 and 1970.
+
+// What an smb server answered to a negotiate request.
+#[derive(Debug, Clone, Default, PartialEq, Serialize)]
+pub struct SmbInfo {
+  pub dialect: String,
+  pub signing_enabled: bool,
+  pub signing_required: bool,
+  pub server_guid: String,
+  pub capabilities: Vec<String>,
+
+  #[serde(skip_serializing_if = "Option::is_none")]
+  pub system_time: Option<String>,    // The clock of the server as an rfc 3339 date.
+}
+
+impl SmbInfo {
+  pub fn details(&self) -> Vec<(&'static str, String)> {
+    let signing = match (self.signing_required, self.signing_enabled) {
+      (true, _) =>      { String::from("required") },
+      (false, true) =>  { String::from("enabled, not required") },
+      (false, false) => { String::from("disabled") }
+    };
+
+    let mut out = vec![("Dialect", self.dialect.clone()), ("Signing", signing), ("GUID", self.server_guid.clone())];
+    if !self.capabilities.is_empty() {
+      out.push(("Capabilities", self.capabilities.join(", ")));
+    }
+
+    out
+  }
+}
+
+/**Function negotiates an smb2 session and records the dialect and settings the server picked.
+ * Params:
+ *  address: &SocketAddr    {The address and port of the server}
+ *  timeout: Duration       {How long to wait for the connection and for each reply}
+ *  binding: &SourceBinding {The interface and address picked by the user}
+ * Returns Option<SmbInfo>
+ */
+pub fn inspect_smb(address: &SocketAddr, timeout: Duration, binding: &SourceBinding) -> Option<SmbInfo> {
+  let mut stream = open_stream(address, timeout, binding).ok()?;
+  stream.write_all(&negotiate_request()).ok()?;
+
+  let mut buffer: Vec<u8> = Default::default();
+  read_until(&mut stream, &mut buffer, |i| i.len() >= 4 && i.len() >= 4 + u32::from_be_bytes([0, i[1], i[2], i[3]]) as usize);
+
+  parse_negotiate_response(&buffer)
+}
+
+/**Function builds an smb2 negotiate request, wrapped in the four byte header used on port 445.
+ * Returns Vec<u8>
+ */
+pub fn negotiate_request() -> Vec<u8> {
+  let mut out: Vec<u8> = Default::default();
+
+  // The smb2 header. Everything but the protocol id, size, command and credits is zero.
+  out.extend_from_slice(b"\xfeSMB");
+  out.extend_from_slice(&(SMB2_HEADER_LEN as u16).to_le_bytes());
+  out.extend_from_slice(&[0; 6]);
+  out.extend_from_slice(&0u16.to_le_bytes());       // Negotiate.
+  out.extend_from_slice(&1u16.to_le_bytes());       // Credits requested.
+  out.extend_from_slice(&[0; 48]);
+
+  // The negotiate request, with the contexts smb 3.1.1 requires after the dialects.
+  let context_offset = (SMB2_HEADER_LEN + 36 + DIALECTS.len() * 2 + 7) & !7;
+
+  out.extend_from_slice(&36u16.to_le_bytes());
+  out.extend_from_slice(&(DIALECTS.len() as u16).to_le_bytes());
+  out.extend_from_slice(&1u16.to_le_bytes());       // Signing enabled.
+  out.extend_from_slice(&[0; 2]);
+  out.extend_from_slice(&0u32.to_le_bytes());
+  out.extend_from_slice(&rand::random::<[u8; 16]>());
+  out.extend_from_slice(&(context_offset as u32).to_le_bytes());
+  out.extend_from_slice(&2u16.to_le_bytes());
+  out.extend_from_slice(&[0; 2]);
+
+  for i in DIALECTS {
+    out.extend_from_slice(&i.to_le_bytes());
+  }
+
+  out.resize(context_offset, 0);
+
+  // Sha-512 for pre authentication integrity with a random salt.
+  let mut integrity = vec![0x01, 0x00, 0x20, 0x00, 0x01, 0x00];
+  integrity.extend_from_slice(&rand::random::<[u8; 32]>());
+  push_context(&mut out, 0x0001, &integrity);
+
+  out.resize((out.len() + 7) & !7, 0);
+
+  // Aes-128-gcm and aes-128-ccm for encryption.
+  push_context(&mut out, 0x0002, &[0x02, 0x00, 0x02, 0x00, 0x01, 0x00]);
+
+  let mut framed = (out.len() as u32).to_be_bytes().to_vec();
+  framed.extend_from_slice(&out);
+  framed
+}
+
+// Appends a negotiate context with its type and length.
+fn push_context(out: &mut Vec<u8>, kind: u16, data: &[u8]) -> () {
+  out.extend_from_slice(&kind.to_le_bytes());
+  out.extend_from_slice(&(data.len() as u16).to_le_bytes());
+  out.extend_from_slice(&[0; 4]);
+  out.extend_from_slice(data);
+}
+
+/**Function reads the answer to our negotiate request.
+ * Params:
+ *  data: &[u8] {The reply of the server, starting with the four byte header used on port 445}
+ * Returns Option<SmbInfo> (None when the reply is not a successful smb2 negotiate response)
+ */
+pub fn parse_negotiate_response(data: &[u8]) -> Option<SmbInfo> {
+  let packet = data.get(4..)?;
+  if packet.starts_with(b"\xfeSMB") == false || u32::from_le_bytes(packet.get(8..12)?.try_into().ok()?) != 0 {
+    return None;
+  }
+
+  let body = packet.get(SMB2_HEADER_LEN..SMB2_HEADER_LEN + 64)?;
+  let security_mode = u16::from_le_bytes([body[2], body[3]]);
+  let dialect = u16::from_le_bytes([body[4], body[5]]);
+  let capabilities = u32::from_le_bytes(body[24..28].try_into().ok()?);
+  let system_time = u64::from_le_bytes(body[40..48].try_into().ok()?);
+
+  let names = [
+    (0x01, "DFS"), (0x02, "LEASING"), (0x04, "LARGE_MTU"), (0x08, "MULTI_CHANNEL"),
+    (0x10, "PERSISTENT_HANDLES"), (0x20, "DIRECTORY_LEASING"), (0x40, "ENCRYPTION"),
+  ];
+
+  Some(SmbInfo {
+    dialect: dialect_name(dialect),
+    signing_enabled: security_mode & 0x01 != 0,
+    signing_required: security_mode & 0x02 != 0,
+    server_guid: format_guid(&body[8..24]),
+    capabilities: names.iter().filter(|i| capabilities & i.0 != 0).map(|i| i.1.to_string()).collect(),
+    system_time: filetime(system_time),
+  })
+}
+
+// Returns the name of a dialect such as 3.1.1.
+fn dialect_name(dialect: u16) -> String {
+  match dialect {
+    0x0202 => { String::from("2.0.2") }
+    0x0210 => { String::from("2.1") }
+    0x0300 => { String::from("3.0") }
+    0x0302 => { String::from("3.0.2") }
+    0x0311 => { String::from("3.1.1") }
+    other =>  { format!("0x{:04x}", other) }
+  }
+}
+
+// Returns a guid in its usual text form. The first three groups are stored little endian.
+fn format_guid(data: &[u8]) -> String {
+  format!(
+    "{:08x}-{:04x}-{:04x}-{}-{}",
+    u32::from_le_bytes([data[0], data[1], data[2], data[3]]),
+    u16::from_le_bytes([data[4], data[5]]),
+    u16::from_le_bytes([data[6], data[7]]),
+    hex_banner(&data[8..10]),
+    hex_banner(&data[10..16])
+  )
+}
+
+// Returns a windows file time, counted in 100 nanosecond steps since 1601, as an rfc 3339 date.
+fn filetime(value: u64) -> Option<String> {
+  if value == 0 {
+    return None;
+  }
+
+  let seconds = (value / 10_000_000) as i64 - FILETIME_EPOCH;
+  Utc.timestamp_opt(seconds, 0).single().map(|i| i.to_rfc3339())
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  // Builds a successful negotiate response for the dialect.
+  fn response(dialect: u16) -> Vec<u8> {
+    let mut packet = b"\xfeSMB".to_vec();
+    packet.resize(SMB2_HEADER_LEN, 0);
+
+    let mut body = vec![0; 64];
+    body[2] = 0x03;
+    body[4..6].copy_from_slice(&dialect.to_le_bytes());
+    body[8..24].copy_from_slice(&[0x33, 0x22, 0x11, 0x00, 0x55, 0x44, 0x77, 0x66, 0x88, 0x99, 0xaa, 0xbb, 0xcc, 0xdd, 0xee, 0xff]);
+    body[24] = 0x41;
+    body[40..48].copy_from_slice(&133_000_000_000_000_000u64.to_le_bytes());
+    packet.extend_from_slice(&body);
+
+    let mut out = (packet.len() as u32).to_be_bytes().to_vec();
+    out.extend_from_slice(&packet);
+    out
+  }
+
+  #[test]
+  fn parse_negotiate_response_reads_fields() {
+    let info = parse_negotiate_response(&response(0x0311)).unwrap();
+
+    assert_eq!(info.dialect, "3.1.1");
+    assert!(info.signing_enabled);
+    assert!(info.signing_required);
+    assert_eq!(info.server_guid, "00112233-4455-6677-8899-aabbccddeeff");
+    assert_eq!(info.capabilities, vec!["DFS", "ENCRYPTION"]);
+    assert!(info.system_time.is_some());
+  }
+
+  #[test]
+  fn parse_negotiate_response_malformed() {
+    let data = response(0x0210);
+    let mut failed = data.clone();
+    failed[12] = 0x22;
+
+    assert_eq!(parse_negotiate_response(b""), None);
+    assert_eq!(parse_negotiate_response(&data[..4]), None);
+    assert_eq!(parse_negotiate_response(&data[..20]), None);
+    assert_eq!(parse_negotiate_response(&data[..data.len() - 1]), None);
+    assert_eq!(parse_negotiate_response(&failed), None);
+    assert_eq!(parse_negotiate_response(b"\0\0\0\x04\xff\xfe\xfd\xfc"), None);
+    assert_eq!(parse_negotiate_response(&response(0xbeef)).unwrap().dialect, "0xbeef");
+  }
+
+  #[test]
+  fn negotiate_request_is_framed() {
+    let request = negotiate_request();
+    assert_eq!(u32::from_be_bytes(request[..4].try_into().unwrap()) as usize, request.len() - 4);
+    assert!(request[4..].starts_with(b"\xfeSMB"));
+  }
+}
@@ -0,0 +1,119 @@
+use std::net::SocketAddr;
+use std::time::Duration;
+use serde::Serialize;
+use super::{open_stream, text_command, SourceBinding};
+
+// The greeting of a mail server and the extensions it lists in its answer to ehlo.
+#[derive(Debug, Clone, Default, PartialEq, Serialize)]
+pub struct SmtpInfo {
+  pub greeting: String,
+
+  #[serde(skip_serializing_if = "Option::is_none")]
+  pub hostname: Option<String>,     // The name the server gives itself in its answer to ehlo.
+
+  pub extensions: Vec<String>,
+  pub starttls: bool,
+  pub auth: Vec<String>,            // The login mechanisms offered before starttls.
+
+  #[serde(skip_serializing_if = "Option::is_none")]
+  pub size: Option<u64>,            // The largest message the server accepts in bytes.
+}
+
+impl SmtpInfo {
+  pub fn details(&self) -> Vec<(&'static str, String)> {
+    let mut out = vec![("Greeting", self.greeting.clone())];
+
+    if !self.extensions.is_empty() {
+      out.push(("Extensions", self.extensions.join(", ")));
+    }
+
+    out.push(("STARTTLS", self.starttls.to_string()));
+
+    if !self.auth.is_empty() {
+      out.push(("Auth", self.auth.join(", ")));
+    }
+
+    out
+  }
+}
+
+/**Function reads the greeting of a mail server and asks it which extensions it supports.
+ * Params:
+ *  address: &SocketAddr    {The address and port of the server}
+ *  timeout: Duration       {How long to wait for the connection and for each reply}
+ *  binding: &SourceBinding {The interface and address picked by the user}
+ * Returns Option<SmtpInfo>
+ */
+pub fn inspect_smtp(address: &SocketAddr, timeout: Duration, binding: &SourceBinding) -> Option<SmtpInfo> {
+  let mut stream = open_stream(address, timeout, binding).ok()?;
+
+  let (code, greeting) = text_command(&mut stream, "")?;
+  if code != 220 {
+    return None;
+  }
+
+  let mut out = SmtpInfo { greeting: greeting.join(" "), ..Default::default() };
+
+  if let Some((250, lines)) = text_command(&mut stream, "EHLO lethal_potato") {
+    parse_ehlo(&mut out, &lines);
+  }
+
+  let _ = text_command(&mut stream, "QUIT");
+  Some(out)
+}
+
+/**Function fills in the fields that come from the answer to ehlo. The first line names the server, each line after it is an extension.
+ * Params:
+ *  info:  &mut SmtpInfo {What we know about the server so far}
+ *  lines: &[String]     {The lines of the answer without their reply codes}
+ * Returns nothing.
+ */
+pub fn parse_ehlo(info: &mut SmtpInfo, lines: &[String]) -> () {
+  info.hostname = lines.first().and_then(|i| i.split_whitespace().next()).map(|i| i.to_string());
+  info.extensions = lines.iter().skip(1).map(|i| i.trim().to_string()).filter(|i| !i.is_empty()).collect();
+
+  for i in info.extensions.iter() {
+    let mut words = i.split_whitespace();
+    let keyword = words.next().unwrap_or_default().to_uppercase();
+
+    match keyword.as_str() {
+      "STARTTLS" => { info.starttls = true; },
+      "AUTH" =>     { info.auth.extend(words.map(|w| w.to_uppercase())); },
+      "SIZE" =>     { info.size = words.next().and_then(|w| w.parse::<u64>().ok()); },
+      _ => {}
+    }
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  fn lines(list: &[&str]) -> Vec<String> {
+    list.iter().map(|i| i.to_string()).collect()
+  }
+
+  #[test]
+  fn parse_ehlo_reads_extensions() {
+    let mut info = SmtpInfo::default();
+    parse_ehlo(&mut info, &lines(&["mail.example.com Hello", "SIZE 35882577", "starttls", "AUTH plain LOGIN"]));
+
+    assert_eq!(info.hostname, Some(String::from("mail.example.com")));
+    assert_eq!(info.size, Some(35882577));
+    assert!(info.starttls);
+    assert_eq!(info.auth, vec!["PLAIN", "LOGIN"]);
+  }
+
+  #[test]
+  fn parse_ehlo_malformed() {
+    let mut info = SmtpInfo::default();
+    parse_ehlo(&mut info, &lines(&[]));
+    assert_eq!(info, SmtpInfo::default());
+
+    let mut info = SmtpInfo::default();
+    parse_ehlo(&mut info, &lines(&["", "", "SIZE", "SIZE \u{fffd}"]));
+    assert_eq!(info.hostname, None);
+    assert_eq!(info.size, None);
+    assert_eq!(info.extensions, vec!["SIZE", "SIZE \u{fffd}"]);
+  }
+}
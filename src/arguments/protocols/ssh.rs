@@ -0,0 +1,202 @@
+use std::io::Write;
+use std::net::SocketAddr;
+use std::time::Duration;
+use serde::Serialize;
+use super::{open_stream, read_until, SourceBinding};
+
+const SSH_MSG_KEXINIT: u8 = 20;
+const CLIENT_IDENT: &[u8] = b"SSH-2.0-lethal_potato\r\n";
+
+// The version string of an ssh server and the algorithms it offers in its key exchange.
+#[derive(Debug, Clone, Default, PartialEq, Serialize)]
+pub struct SshInfo {
+  pub protocol_version: String,
+  pub software: String,
+
+  #[serde(skip_serializing_if = "Option::is_none")]
+  pub comments: Option<String>,             // Whatever follows the software version, often the os package.
+
+  pub kex_algorithms: Vec<String>,
+  pub host_key_algorithms: Vec<String>,
+  pub encryption_algorithms: Vec<String>,   // The ciphers offered from the client to the server.
+  pub mac_algorithms: Vec<String>,
+  pub compression_algorithms: Vec<String>,
+}
+
+impl SshInfo {
+  pub fn details(&self) -> Vec<(&'static str, String)> {
+    let mut out = vec![("Version", format!("{} (protocol {})", self.software, self.protocol_version))];
+
+    if let Some(comments) = self.comments.as_ref() {
+      out.push(("Comments", comments.clone()));
+    }
+
+    for (name, list) in [("Kex", &self.kex_algorithms), ("Host keys", &self.host_key_algorithms), ("Ciphers", &self.encryption_algorithms), ("MACs", &self.mac_algorithms)] {
+      if !list.is_empty() {
+        out.push((name, list.join(", ")));
+      }
+    }
+
+    out
+  }
+}
+
+/**Function reads the version string of an ssh server and the key exchange it sends once we send ours.
+ * Params:
+ *  address: &SocketAddr    {The address and port of the server}
+ *  timeout: Duration       {How long to wait for the connection and for each reply}
+ *  binding: &SourceBinding {The interface and address picked by the user}
+ * Returns Option<SshInfo>
+ */
+pub fn inspect_ssh(address: &SocketAddr, timeout: Duration, binding: &SourceBinding) -> Option<SshInfo> {
+  let mut stream = open_stream(address, timeout, binding).ok()?;
+  let mut buffer: Vec<u8> = Default::default();
+
+  // Servers may send other lines before the version string.
+  read_until(&mut stream, &mut buffer, |i| ident_end(i).is_some());
+  let end = ident_end(&buffer)?;
+
+  let start = find(&buffer[..end], b"SSH-")?;
+  let mut out = parse_ident(String::from_utf8_lossy(&buffer[start..end]).trim_end())?;
+
+  // The key exchange is read when the server sends it, a server that stops after its version string still gives us that.
+  if stream.write_all(CLIENT_IDENT).is_ok() {
+    let mut packet = buffer[end..].to_vec();
+    read_until(&mut stream, &mut packet, |i| i.len() >= 4 && i.len() >= 4 + u32::from_be_bytes([i[0], i[1], i[2], i[3]]) as usize);
+
+    if let Some(lists) = parse_kexinit(&packet) {
+      out.kex_algorithms = lists[0].clone();
+      out.host_key_algorithms = lists[1].clone();
+      out.encryption_algorithms = lists[2].clone();
+      out.mac_algorithms = lists[4].clone();
+      out.compression_algorithms = lists[6].clone();
+    }
+  }
+
+  Some(out)
+}
+
+// Returns the offset just past the line holding the version string.
+fn ident_end(data: &[u8]) -> Option<usize> {
+  let start = find(data, b"SSH-")?;
+  Some(start + data[start..].iter().position(|i| *i == b'\n')? + 1)
+}
+
+// Returns the offset of the first match of the needle.
+fn find(data: &[u8], needle: &[u8]) -> Option<usize> {
+  data.windows(needle.len()).position(|i| i == needle)
+}
+
+/**Function splits a version string such as SSH-2.0-OpenSSH_9.6p1 Ubuntu-3 into its parts.
+ * Params:
+ *  line: &str {The version string without its line ending}
+ * Returns Option<SshInfo>
+ */
+pub fn parse_ident(line: &str) -> Option<SshInfo> {
+  let rest = line.strip_prefix("SSH-")?;
+  let (protocol_version, rest) = rest.split_once('-')?;
+
+  let (software, comments) = match rest.split_once(' ') {
+    Some((s, c)) => { (s.to_string(), Some(c.to_string())) },
+    None =>         { (rest.to_string(), None) }
+  };
+
+  Some(SshInfo {
+    protocol_version: protocol_version.to_string(),
+    software,
+    comments,
+    ..Default::default()
+  })
+}
+
+/**Function reads the ten name lists from a key exchange packet.
+ * Params:
+ *  packet: &[u8] {The binary packet, starting with its length}
+ * Returns Option<Vec<Vec<String>>>
+ */
+pub fn parse_kexinit(packet: &[u8]) -> Option<Vec<Vec<String>>> {
+  let length = u32::from_be_bytes(packet.get(..4)?.try_into().ok()?) as usize;
+  let padding = *packet.get(4)? as usize;
+  let payload = packet.get(5..4 + length.checked_sub(padding)?)?;
+
+  if payload.first() != Some(&SSH_MSG_KEXINIT) {
+    return None;
+  }
+
+  // The message type is followed by a 16 byte cookie.
+  let mut idx = 17;
+  let mut out: Vec<Vec<String>> = Default::default();
+
+  for _ in 0..10 {
+    let len = u32::from_be_bytes(payload.get(idx..idx + 4)?.try_into().ok()?) as usize;
+    let list = String::from_utf8_lossy(payload.get(idx + 4..idx + 4 + len)?).to_string();
+
+    out.push(list.split(',').filter(|i| !i.is_empty()).map(|i| i.to_string()).collect());
+    idx += 4 + len;
+  }
+
+  Some(out)
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  // Builds a key exchange packet holding the given name lists.
+  fn kexinit(lists: &[&str]) -> Vec<u8> {
+    let mut payload = vec![SSH_MSG_KEXINIT];
+    payload.extend_from_slice(&[0; 16]);
+
+    for i in lists {
+      payload.extend_from_slice(&(i.len() as u32).to_be_bytes());
+      payload.extend_from_slice(i.as_bytes());
+    }
+
+    payload.extend_from_slice(&[0; 5]);
+    let padding = 4;
+
+    let mut out = ((payload.len() + 1 + padding) as u32).to_be_bytes().to_vec();
+    out.push(padding as u8);
+    out.extend_from_slice(&payload);
+    out.extend_from_slice(&[0; 4]);
+    out
+  }
+
+  #[test]
+  fn parse_ident_splits_comments() {
+    let info = parse_ident("SSH-2.0-OpenSSH_9.6p1 Ubuntu-3").unwrap();
+    assert_eq!(info.protocol_version, "2.0");
+    assert_eq!(info.software, "OpenSSH_9.6p1");
+    assert_eq!(info.comments, Some(String::from("Ubuntu-3")));
+  }
+
+  #[test]
+  fn parse_ident_malformed() {
+    assert_eq!(parse_ident(""), None);
+    assert_eq!(parse_ident("SSH-"), None);
+    assert_eq!(parse_ident("HTTP/1.1 200 OK"), None);
+    assert_eq!(parse_ident(&String::from_utf8_lossy(b"SSH-2.0-\xff\xfe")).map(|i| i.protocol_version), Some(String::from("2.0")));
+  }
+
+  #[test]
+  fn parse_kexinit_reads_lists() {
+    let packet = kexinit(&["curve25519-sha256,ecdh-sha2-nistp256", "ssh-ed25519", "aes128-ctr", "aes128-ctr", "hmac-sha2-256", "hmac-sha2-256", "none", "none", "", ""]);
+    let lists = parse_kexinit(&packet).unwrap();
+
+    assert_eq!(lists.len(), 10);
+    assert_eq!(lists[0], vec!["curve25519-sha256", "ecdh-sha2-nistp256"]);
+    assert_eq!(lists[9].len(), 0);
+  }
+
+  #[test]
+  fn parse_kexinit_malformed() {
+    let packet = kexinit(&["a", "b", "c", "d", "e", "f", "g", "h", "i", "j"]);
+
+    assert_eq!(parse_kexinit(&[]), None);
+    assert_eq!(parse_kexinit(&packet[..3]), None);
+    assert_eq!(parse_kexinit(&packet[..packet.len() / 2]), None);
+    assert_eq!(parse_kexinit(&[0xff, 0xff, 0xff, 0xff, 0xff, 0xff]), None);
+    assert_eq!(parse_kexinit(&[0, 0, 0, 1, 8]), None);
+    assert!(parse_kexinit(&kexinit(&["\u{e9}", "", "", "", "", "", "", "", "", ""])).is_some());
+  }
+}
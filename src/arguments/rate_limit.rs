@@ -0,0 +1,199 @@
+use std::sync::Mutex;
+use std::thread;
+use std::time::{Duration, Instant};
+use crossbeam::channel::Receiver;
+use rand::Rng;
+
+// Tracks when the next probe may be sent.
+struct LimiterState {
+  next: Instant,      // The earliest time the next probe may start.
+  sent: u64,          // The number of probes started so far.
+}
+
+// Paces the probes of every worker so that the scan as a whole stays within the limits set by the user.
+pub struct RateLimiter {
+  interval: Duration,         // The smallest gap between two probes. Zero when the rate is unlimited.
+  jitter: Duration,           // The largest random delay added to each gap.
+  min_rate: Option<u32>,      // The fewest probes to start each second.
+  started: Instant,
+  state: Mutex<LimiterState>,
+}
+
+impl RateLimiter {
+
+  /**Function creates a limiter from the pacing options.
+   * Params:
+   *  max_rate:   Option<u32> {The most probes to start each second}
+   *  min_rate:   Option<u32> {The fewest probes to start each second}
+   *  scan_delay: u64         {The time in ms to wait between probes}
+   *  jitter:     u64         {The largest random delay in ms to add to each gap}
+   * Returns Result<RateLimiter, String>
+   */
+  pub fn new(max_rate: Option<u32>, min_rate: Option<u32>, scan_delay: u64, jitter: u64) -> Result<RateLimiter, String> {
+    let mut interval = Duration::from_millis(scan_delay);
+
+    if let Some(rate) = max_rate {
+      interval = interval.max(Duration::from_secs(1) / rate.max(1));
+    }
+
+    if let (Some(min), Some(max)) = (min_rate, max_rate) {
+      if min > max {
+        return Err(format!("--min-rate {} is above --max-rate {}", min, max));
+      }
+    }
+
+    // The gaps between probes must leave enough room to reach the minimum rate.
+    if let Some(rate) = min_rate {
+      if interval > Duration::from_secs(1) / rate.max(1) {
+        return Err(format!("--scan-delay {}ms is too slow for --min-rate {}", scan_delay, rate));
+      }
+    }
+
+    let now = Instant::now();
+    Ok(RateLimiter {
+      interval,
+      jitter: Duration::from_millis(jitter),
+      min_rate,
+      started: now,
+      state: Mutex::new(LimiterState { next: now, sent: 0 }),
+    })
+  }
+
+  /**Function reserves the next free slot for a probe.
+   * Params:
+   *  &self
+   * Returns Instant (The time the probe may start)
+   */
+  pub fn reserve(&self) -> Instant {
+    let now = Instant::now();
+
+    if self.interval.is_zero() && self.jitter.is_zero() {
+      if let Ok(mut state) = self.state.lock() {
+        state.sent += 1;
+      }
+
+      return now;
+    }
+
+    let mut gap = self.interval;
+    if self.jitter.is_zero() == false {
+      gap += Duration::from_micros(rand::thread_rng().gen_range(0..=self.jitter.as_micros() as u64));
+    }
+
+    match self.state.lock() {
+      Ok(mut state) => {
+        // Time that went unused is never made up with a burst of probes, unless the scan fell behind the minimum rate.
+        let slot = match self.behind(&state) {
+          true =>  { now },
+          false => { state.next.max(now) }
+        };
+
+        state.next = slot + gap;
+        state.sent += 1;
+
+        slot
+      },
+
+      Err(_) => { now }
+    }
+  }
+
+  // Reserves the next free slot and sleeps until it starts.
+  pub fn wait(&self) -> () {
+    let slot = self.reserve();
+    let now = Instant::now();
+
+    if slot > now {
+      thread::sleep(slot - now);
+    }
+  }
+
+  // Returns how many times its usual size a pool of workers may grow to while catching up with the minimum rate.
+  pub fn burst(&self) -> usize {
+    match self.min_rate {
+      Some(_) => { 2 },
+      None =>    { 1 }
+    }
+  }
+
+  // Returns true when fewer probes were started than the minimum rate asks for.
+  pub fn behind_min_rate(&self) -> bool {
+    match self.state.lock() {
+      Ok(state) => { self.behind(&state) },
+      Err(_) =>    { false }
+    }
+  }
+
+  // Compares the probes started so far with the number the minimum rate asks for.
+  fn behind(&self, state: &LimiterState) -> bool {
+    match self.min_rate {
+      Some(rate) => { (state.sent as f64) < self.started.elapsed().as_secs_f64() * rate as f64 },
+      None =>       { false }
+    }
+  }
+
+  /**Function takes the next job off a queue for a worker of a blocking pool. Extra workers only take jobs while
+   * the scan is behind the minimum rate, which lets the pool grow past its usual size without breaking --concurrency.
+   * Params:
+   *  &self
+   *  jobs:  &Receiver<T> {The queue of jobs, filled before the workers start}
+   *  extra: bool         {The worker is only there to catch up with the minimum rate}
+   * Returns Option<T> (None once the queue is empty)
+   */
+  pub fn next_job<T>(&self, jobs: &Receiver<T>, extra: bool) -> Option<T> {
+    while extra == true && self.behind_min_rate() == false {
+      if jobs.is_empty() == true {
+        return None;
+      }
+
+      thread::sleep(Duration::from_millis(10));
+    }
+
+    jobs.recv().ok()
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crossbeam::channel::unbounded;
+
+  #[test]
+  fn new_rejects_conflicting_rates() {
+    assert!(RateLimiter::new(Some(10), Some(20), 0, 0).is_err());
+    assert!(RateLimiter::new(None, Some(100), 50, 0).is_err());
+    assert!(RateLimiter::new(Some(20), Some(10), 0, 0).is_ok());
+  }
+
+  #[test]
+  fn reserve_catches_up_with_min_rate() {
+    let limiter = RateLimiter::new(Some(100), Some(100), 0, 0).unwrap();
+    thread::sleep(Duration::from_millis(100));
+    assert!(limiter.behind_min_rate());
+
+    // Every slot starts straight away until the probes sent match the minimum rate.
+    for _ in 0..5 {
+      assert!(limiter.reserve() <= Instant::now());
+    }
+  }
+
+  #[test]
+  fn next_job_holds_back_extra_workers() {
+    let (sender, jobs) = unbounded::<u32>();
+    sender.send(1).unwrap();
+    drop(sender);
+
+    let limiter = RateLimiter::new(None, None, 0, 0).unwrap();
+    assert_eq!(limiter.burst(), 1);
+    assert_eq!(limiter.next_job(&jobs, false), Some(1));
+    assert_eq!(limiter.next_job(&jobs, true), None);
+
+    let (sender, jobs) = unbounded::<u32>();
+    sender.send(2).unwrap();
+
+    let limiter = RateLimiter::new(None, Some(1000), 0, 0).unwrap();
+    thread::sleep(Duration::from_millis(10));
+    assert_eq!(limiter.burst(), 2);
+    assert_eq!(limiter.next_job(&jobs, true), Some(2));
+  }
+}
@@ -0,0 +1,192 @@
+use std::collections::HashMap;
+use std::net::IpAddr;
+use std::sync::Mutex;
+use std::sync::atomic::{AtomicU32, Ordering};
+use std::time::Duration;
+
+const CLOCK_GRANULARITY: Duration = Duration::from_millis(1);
+
+// The smoothed round trip time of a host and how much it varies, as described in RFC 6298.
+#[derive(Debug, Clone, Copy)]
+pub struct RttEstimate {
+  pub srtt: Duration,
+  pub rttvar: Duration,
+}
+
+impl RttEstimate {
+
+  // Creates the estimate from the first measurement.
+  pub fn new(sample: Duration) -> RttEstimate {
+    RttEstimate {
+      srtt: sample,
+      rttvar: sample / 2,
+    }
+  }
+
+  // Folds a new measurement into the estimate.
+  pub fn update(&mut self, sample: Duration) -> () {
+    let delta = self.srtt.abs_diff(sample);
+
+    self.rttvar = (self.rttvar * 3 + delta) / 4;
+    self.srtt = (self.srtt * 7 + sample) / 8;
+  }
+
+  // Returns the retransmission timeout for the estimate.
+  pub fn timeout(&self) -> Duration {
+    self.srtt + (self.rttvar * 4).max(CLOCK_GRANULARITY)
+  }
+}
+
+// Measures the round trip time to every host and works out how long to wait for each probe.
+pub struct RttTable {
+  min: Duration,                              // The shortest timeout, no matter how fast the host answers.
+  max: Duration,                              // The longest timeout and the timeout of hosts without measurements.
+  hosts: Mutex<HashMap<IpAddr, RttEstimate>>,
+  backoff: AtomicU32,                         // Every measured timeout is multiplied by this while probes are retried.
+}
+
+impl RttTable {
+
+  /**Function creates a table that keeps every timeout between the two limits.
+   * Params:
+   *  min_timeout: u64 {The shortest timeout in ms}
+   *  max_timeout: u64 {The longest timeout in ms}
+   * Returns RttTable.
+   */
+  pub fn new(min_timeout: u64, max_timeout: u64) -> RttTable {
+    let max = Duration::from_millis(max_timeout.max(1));
+
+    RttTable {
+      min: Duration::from_millis(min_timeout).min(max),
+      max,
+      hosts: Mutex::new(Default::default()),
+      backoff: AtomicU32::new(1),
+    }
+  }
+
+  /**Function records how long a host took to answer a probe.
+   * Only answers that prove the host is reachable, such as an accepted or refused connection, should be recorded.
+   * Params:
+   *  &self
+   *  ip:     IpAddr   {The host that answered}
+   *  sample: Duration {The time between sending the probe and the answer}
+   * Returns nothing.
+   */
+  pub fn sample(&self, ip: IpAddr, sample: Duration) -> () {
+    if let Ok(mut hosts) = self.hosts.lock() {
+      match hosts.get_mut(&ip) {
+        Some(estimate) => { estimate.update(sample); },
+        None =>           { hosts.insert(ip, RttEstimate::new(sample)); }
+      }
+    }
+  }
+
+  /**Function returns how long to wait for an answer from the host.
+   * Params:
+   *  &self
+   *  ip: &IpAddr {The host that is being probed}
+   * Returns Duration.
+   */
+  pub fn timeout(&self, ip: &IpAddr) -> Duration {
+    let estimate = match self.hosts.lock() {
+      Ok(hosts) => { hosts.get(ip).cloned() },
+      Err(_) =>    { None }
+    };
+
+    match estimate {
+      Some(s) => { (s.timeout() * self.backoff.load(Ordering::Relaxed)).max(self.min).min(self.max) },
+      None =>    { self.max }
+    }
+  }
+
+  // Multiplies every measured timeout, the way tcp backs off when it retransmits.
+  pub fn set_backoff(&self, factor: u32) -> () {
+    self.backoff.store(factor.max(1), Ordering::Relaxed);
+  }
+
+  // Returns the smoothed round trip time of the host if it was measured.
+  pub fn srtt(&self, ip: &IpAddr) -> Option<Duration> {
+    match self.hosts.lock() {
+      Ok(hosts) => { hosts.get(ip).map(|i| i.srtt) },
+      Err(_) =>    { None }
+    }
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use std::net::Ipv4Addr;
+
+  const HOST: IpAddr = IpAddr::V4(Ipv4Addr::new(192, 0, 2, 10));
+
+  fn ms(value: u64) -> Duration {
+    Duration::from_millis(value)
+  }
+
+  #[test]
+  fn first_sample_sets_srtt_and_half_rttvar() {
+    let estimate = RttEstimate::new(ms(100));
+    assert_eq!(estimate.srtt, ms(100));
+    assert_eq!(estimate.rttvar, ms(50));
+
+    let table = RttTable::new(1, 10000);
+    table.sample(HOST, ms(100));
+    assert_eq!(table.srtt(&HOST), Some(ms(100)));
+  }
+
+  #[test]
+  fn update_applies_rfc_6298_gains() {
+    let mut estimate = RttEstimate::new(ms(100));
+    estimate.update(ms(200));
+
+    // rttvar = 3/4 * 50 + 1/4 * |100 - 200|, srtt = 7/8 * 100 + 1/8 * 200
+    assert_eq!(estimate.rttvar, Duration::from_micros(62500));
+    assert_eq!(estimate.srtt, Duration::from_micros(112500));
+    assert_eq!(estimate.timeout(), Duration::from_micros(362500));
+  }
+
+  #[test]
+  fn timeout_keeps_clock_granularity() {
+    let mut estimate = RttEstimate::new(ms(10));
+    for _ in 0..64 {
+      estimate.update(ms(10));
+    }
+
+    assert!(estimate.timeout() >= ms(10) + CLOCK_GRANULARITY);
+  }
+
+  #[test]
+  fn timeout_clamps_to_limits() {
+    let table = RttTable::new(500, 1000);
+    assert_eq!(table.timeout(&HOST), ms(1000));
+
+    table.sample(HOST, ms(10));
+    assert_eq!(table.timeout(&HOST), ms(500));
+
+    let table = RttTable::new(500, 1000);
+    table.sample(HOST, ms(400));
+    assert_eq!(table.timeout(&HOST), ms(1000));
+
+    // A minimum above the maximum is lowered to it.
+    let table = RttTable::new(5000, 1000);
+    table.sample(HOST, ms(10));
+    assert_eq!(table.timeout(&HOST), ms(1000));
+  }
+
+  #[test]
+  fn backoff_multiplies_measured_timeout() {
+    let table = RttTable::new(1, 10000);
+    table.sample(HOST, ms(100));
+    assert_eq!(table.timeout(&HOST), ms(300));
+
+    table.set_backoff(4);
+    assert_eq!(table.timeout(&HOST), ms(1200));
+
+    table.set_backoff(64);
+    assert_eq!(table.timeout(&HOST), ms(10000));
+
+    table.set_backoff(0);
+    assert_eq!(table.timeout(&HOST), ms(300));
+  }
+}
@@ -0,0 +1,908 @@
+use std::collections::HashSet;
+use std::io::{Error, ErrorKind, Read, Write};
+use std::net::{SocketAddr, TcpStream};
+use std::sync::Arc;
+use std::time::Duration;
+use regex::bytes::{Captures, Regex, RegexBuilder};
+use rustls::{ClientConfig, ClientConnection, StreamOwned};
+use rustls::pki_types::ServerName;
+use serde::Serialize;
+use super::{is_tls_banner, read_banner, SourceBinding};
+
+const BUILTIN_PROBES: &str = include_str!("../../assets/service-probes");
+const DEFAULT_RARITY: u8 = 7;             // Probes rarer than this are only sent to the ports they list, like the default intensity of nmap.
+const MAX_RESPONSE_LEN: usize = 16384;    // The most bytes of an answer that are matched against the rules.
+const REST_WAIT: Duration = Duration::from_millis(150);   // How long to wait for the rest of an answer over tls once it starts arriving.
+
+// What version detection learned about a service.
+#[derive(Debug, Clone, Default, PartialEq, Serialize)]
+pub struct ServiceInfo {
+  pub service: String,
+
+  #[serde(skip_serializing_if = "Option::is_none")]
+  pub product: Option<String>,
+
+  #[serde(skip_serializing_if = "Option::is_none")]
+  pub version: Option<String>,
+
+  #[serde(skip_serializing_if = "Option::is_none")]
+  pub info: Option<String>,         // Anything else the service told us, such as the protocol version.
+
+  #[serde(skip_serializing_if = "Option::is_none")]
+  pub hostname: Option<String>,
+
+  #[serde(skip_serializing_if = "Option::is_none")]
+  pub os: Option<String>,
+
+  #[serde(skip_serializing_if = "Option::is_none")]
+  pub device: Option<String>,
+
+  #[serde(skip_serializing_if = "Vec::is_empty")]
+  pub cpe: Vec<String>,
+}
+
+impl ServiceInfo {
+
+  // Returns the version followed by the extra info, the way it is shown in the results table.
+  pub fn display_version(&self) -> String {
+    match (self.version.as_ref(), self.info.as_ref()) {
+      (Some(v), Some(i)) => { format!("{} ({})", v, i) }
+      (Some(v), None) =>    { v.clone() }
+      (None, Some(i)) =>    { format!("({})", i) }
+      (None, None) =>       { String::new() }
+    }
+  }
+}
+
+// A rule that recognises a service from the answer to a probe.
+#[derive(Debug, Clone)]
+pub struct ServiceMatch {
+  pub service: String,
+  pub pattern: Regex,
+  pub soft: bool,                   // A soft match only names the service, later probes may still find its version.
+  pub fields: Vec<(char, String)>,  // The templates of the version info keyed by their letter. Cpe templates use 'c'.
+}
+
+impl ServiceMatch {
+
+  /**Function checks an answer against the rule and fills in the version info from the captured groups.
+   * Params:
+   *  &self
+   *  response: &[u8] {The raw answer sent by the service}
+   * Returns Option<ServiceInfo>
+   */
+  pub fn apply(&self, response: &[u8]) -> Option<ServiceInfo> {
+    let captures = self.pattern.captures(response)?;
+    let mut out = ServiceInfo { service: self.service.clone(), ..Default::default() };
+
+    for (letter, template) in self.fields.iter() {
+      let value = fill_template(template, &captures);
+      if value.is_empty() {
+        continue;
+      }
+
+      match letter {
+        'p' => { out.product = Some(value); },
+        'v' => { out.version = Some(value); },
+        'i' => { out.info = Some(value); },
+        'h' => { out.hostname = Some(value); },
+        'o' => { out.os = Some(value); },
+        'd' => { out.device = Some(value); },
+        'c' => { out.cpe.push(format!("cpe:/{}", value)); },
+        _ =>   {}
+      }
+    }
+
+    Some(out)
+  }
+}
+
+// The bytes sent to a service and the rules that recognise its answer.
+#[derive(Debug, Clone, Default)]
+pub struct ServiceProbe {
+  pub name: String,
+  pub udp: bool,
+  pub payload: Vec<u8>,
+  pub ports: HashSet<u16>,            // The ports the probe is always sent to.
+  pub ssl_ports: HashSet<u16>,        // The ports the probe is sent to once tls has been set up.
+  pub rarity: u8,                     // How seldom the probe gets an answer, from 1 to 9.
+  pub total_wait: Option<Duration>,   // How long the service may take to answer.
+  pub fallback: Vec<String>,          // The probes whose rules are also tried against the answer.
+  pub matches: Vec<ServiceMatch>,
+}
+
+impl ServiceProbe {
+
+  // Returns true when the probe lists the port, the tls ports are used once tls has been set up.
+  pub fn lists(&self, port: u16, tls: bool) -> bool {
+    match tls {
+      false => { self.ports.contains(&port) },
+      true =>  { self.ssl_ports.contains(&port) }
+    }
+  }
+
+  // Returns true when the probe is worth sending to a port.
+  pub fn suits(&self, port: u16, tls: bool) -> bool {
+    self.lists(port, tls) || self.rarity <= DEFAULT_RARITY
+  }
+}
+
+// Every probe read from a probe file.
+#[derive(Debug, Clone, Default)]
+pub struct ProbeDatabase {
+  pub probes: Vec<ServiceProbe>,
+  pub exclude_tcp: HashSet<u16>,      // Ports that are never probed, such as printers that print whatever they are sent.
+  pub exclude_udp: HashSet<u16>,
+  pub skipped: usize,                 // Rules whose pattern uses regex features that are not supported.
+}
+
+impl ProbeDatabase {
+
+  // Returns the probes that ship with lethal_potato.
+  pub fn builtin() -> Result<ProbeDatabase, String> {
+    Self::parse(BUILTIN_PROBES)
+  }
+
+  /**Function reads a probe file in the nmap-service-probes format.
+   * Params:
+   *  path: &str {The path to the file}
+   * Returns Result<ProbeDatabase, String>
+   */
+  pub fn from_file(path: &str) -> Result<ProbeDatabase, String> {
+    match std::fs::read(path) {
+      Ok(s) =>  { Self::parse(&String::from_utf8_lossy(&s)) },
+      Err(e) => { Err(format!("unable to read the file - {}", e)) }
+    }
+  }
+
+  /**Function parses probes in the nmap-service-probes format.
+   * Params:
+   *  text: &str {The contents of the probe file}
+   * Returns Result<ProbeDatabase, String>
+   */
+  pub fn parse(text: &str) -> Result<ProbeDatabase, String> {
+    let mut out = ProbeDatabase::default();
+
+    for (idx, i) in text.lines().enumerate() {
+      let line = i.trim();
+      if line.is_empty() || line.starts_with('#') {
+        continue;
+      }
+
+      let (directive, rest) = match line.split_once(char::is_whitespace) {
+        Some((d, r)) => { (d, r.trim()) },
+        None =>         { (line, "") }
+      };
+
+      if let Err(e) = out.parse_line(directive, rest) {
+        return Err(format!("line {}: {}", idx + 1, e));
+      }
+    }
+
+    Ok(out)
+  }
+
+  /**Function applies one line of a probe file.
+   * Params:
+   *  &mut self
+   *  directive: &str {The first word of the line}
+   *  rest:      &str {Everything after the first word}
+   * Returns Result<(), String>
+   */
+  pub fn parse_line(&mut self, directive: &str, rest: &str) -> Result<(), String> {
+    match directive {
+      "Exclude" => { return self.parse_exclude(rest); },
+      "Probe" =>   { self.probes.push(parse_probe(rest)?); return Ok(()); },
+      _ => {}
+    }
+
+    // Every other directive belongs to the probe above it.
+    let probe = match self.probes.last_mut() {
+      Some(s) => { s },
+      None =>    { return Err(format!("{} comes before the first probe", directive)); }
+    };
+
+    match directive {
+      "match" | "softmatch" => {
+        match parse_match(rest, directive == "softmatch")? {
+          Some(s) => { probe.matches.push(s); },
+          None =>    { self.skipped += 1; }
+        }
+      },
+
+      "ports" =>        { probe.ports = parse_port_list(rest)?; },
+      "sslports" =>     { probe.ssl_ports = parse_port_list(rest)?; },
+      "rarity" =>       { probe.rarity = rest.parse::<u8>().map_err(|_| format!("{} is not a valid rarity", rest))?; },
+      "totalwaitms" =>  { probe.total_wait = Some(Duration::from_millis(rest.parse::<u64>().map_err(|_| format!("{} is not a valid wait", rest))?)); },
+      "tcpwrappedms" => {},
+      "fallback" =>     { probe.fallback = rest.split(',').map(|i| i.trim().to_string()).filter(|i| !i.is_empty()).collect(); },
+      _ =>              { return Err(format!("unknown directive {}", directive)); }
+    }
+
+    Ok(())
+  }
+
+  /**Function reads the ports that are never probed. A T: or U: prefix limits the ports that follow it to one protocol.
+   * Params:
+   *  &mut self
+   *  spec: &str {The ports, such as T:9100-9107,U:30000-40000}
+   * Returns Result<(), String>
+   */
+  pub fn parse_exclude(&mut self, spec: &str) -> Result<(), String> {
+    let (mut tcp, mut udp) = (true, true);
+
+    for i in spec.split(',') {
+      let mut item = i.trim();
+
+      if let Some(s) = item.strip_prefix("T:") {
+        (tcp, udp, item) = (true, false, s);
+      }
+
+      else if let Some(s) = item.strip_prefix("U:") {
+        (tcp, udp, item) = (false, true, s);
+      }
+
+      let (start, end) = parse_port_range(item)?;
+      for port in start..=end {
+        if tcp == true {
+          self.exclude_tcp.insert(port);
+        }
+
+        if udp == true {
+          self.exclude_udp.insert(port);
+        }
+      }
+    }
+
+    Ok(())
+  }
+
+  // Returns the probe with a name.
+  pub fn probe(&self, name: &str, udp: bool) -> Option<&ServiceProbe> {
+    self.probes.iter().find(|i| i.name == name && i.udp == udp)
+  }
+
+  /**Function returns the rules that are tried against the answer to a probe.
+   * The rules of the probe come first, then those of its fallbacks. Tcp probes also fall back to the null probe
+   * because many services send their greeting before they read what we sent.
+   * Params:
+   *  &self
+   *  probe:     &ServiceProbe {The probe that was sent}
+   *  with_null: bool          {Adds the rules of the null probe}
+   * Returns Vec<&ServiceMatch>
+   */
+  pub fn rules<'a>(&'a self, probe: &'a ServiceProbe, with_null: bool) -> Vec<&'a ServiceMatch> {
+    let mut probes: Vec<&ServiceProbe> = vec![probe];
+    probes.extend(probe.fallback.iter().filter_map(|i| self.probe(i, probe.udp)));
+
+    if with_null == true && probe.udp == false && probe.name != "NULL" {
+      probes.extend(self.probe("NULL", false));
+    }
+
+    probes.iter().flat_map(|i| i.matches.iter()).collect()
+  }
+
+  /**Function matches an answer against the rules of a probe.
+   * Params:
+   *  &self
+   *  probe:    &ServiceProbe {The probe that was sent}
+   *  response: &[u8]         {The raw answer sent by the service}
+   *  service:  Option<&str>  {The service named by an earlier soft match, only its rules are tried}
+   * Returns Option<(ServiceInfo, bool)> (The version info and true when it came from a soft match)
+   */
+  pub fn match_response(&self, probe: &ServiceProbe, response: &[u8], service: Option<&str>) -> Option<(ServiceInfo, bool)> {
+    let mut soft: Option<ServiceInfo> = None;
+
+    for rule in self.rules(probe, true) {
+      if let Some(name) = service {
+        if rule.service != name {
+          continue;
+        }
+      }
+
+      if let Some(info) = rule.apply(response) {
+        if rule.soft == false {
+          return Some((info, false));
+        }
+
+        if soft.is_none() {
+          soft = Some(info);
+        }
+      }
+    }
+
+    soft.map(|i| (i, true))
+  }
+
+  /**Function identifies the service on a port by sending it probes until one of the rules matches the answer.
+   * The null probe goes first, then the probes that list the port, then every other probe that is common enough.
+   * Once a soft match names the service only the probes that can tell us more about it are sent.
+   * Ports that answer with tls, or that a probe lists as a tls port, are probed again once tls has been set up.
+   * Params:
+   *  &self
+   *  address: &SocketAddr               {The address and port of the service}
+   *  udp:     bool                      {True when the port was found with a udp scan}
+   *  tls:     Option<&Arc<ClientConfig>> {The tls settings, no tls probes are sent without them}
+   *  timeout: Duration                  {How long to wait for the connection and for each answer}
+   *  binding: &SourceBinding            {The interface and address picked by the user}
+   * Returns Option<ServiceInfo>
+   */
+  pub fn detect(&self, address: &SocketAddr, udp: bool, tls: Option<&Arc<ClientConfig>>, timeout: Duration, binding: &SourceBinding) -> Option<ServiceInfo> {
+    let port = address.port();
+    let excluded = match udp {
+      false => { &self.exclude_tcp },
+      true =>  { &self.exclude_udp }
+    };
+
+    if excluded.contains(&port) == true {
+      return None;
+    }
+
+    let (found, tls_answer) = self.run_probes(address, udp, None, timeout, binding);
+
+    let config = match (tls, udp) {
+      (Some(s), false) => { s },
+      _ =>                { return found; }
+    };
+
+    // A service named over plain tcp is trusted unless it answered with tls. Tls ports that stayed quiet get a second look.
+    let named = found.as_ref().map(|i| i.service != "ssl").unwrap_or(false);
+    let listed = self.probes.iter().any(|i| i.udp == false && i.ssl_ports.contains(&port));
+
+    if tls_answer == false && (named == true || listed == false) {
+      return found;
+    }
+
+    match self.run_probes(address, false, Some(config), timeout, binding) {
+      (Some(mut info), _) => {
+        info.service = format!("ssl/{}", info.service);
+        Some(info)
+      },
+
+      (None, _) => { found }
+    }
+  }
+
+  /**Function sends the probes that suit a port until one of the rules matches the answer.
+   * Params:
+   *  &self
+   *  address: &SocketAddr               {The address and port of the service}
+   *  udp:     bool                      {True when the port was found with a udp scan}
+   *  tls:     Option<&Arc<ClientConfig>> {The tls settings when the probes are sent over tls}
+   *  timeout: Duration                  {How long to wait for the connection and for each answer}
+   *  binding: &SourceBinding            {The interface and address picked by the user}
+   * Returns (Option<ServiceInfo>, bool) (The service and true when an answer was a tls record)
+   */
+  fn run_probes(&self, address: &SocketAddr, udp: bool, tls: Option<&Arc<ClientConfig>>, timeout: Duration, binding: &SourceBinding) -> (Option<ServiceInfo>, bool) {
+    let port = address.port();
+    let mut probes: Vec<&ServiceProbe> = self.probes.iter().filter(|i| i.udp == udp && i.suits(port, tls.is_some()) == true).collect();
+    probes.sort_by_key(|i| (!i.payload.is_empty(), i.lists(port, tls.is_some()) == false));
+
+    let mut soft: Option<ServiceInfo> = None;
+    let mut tls_answer = false;
+
+    for probe in probes {
+      if let Some(s) = soft.as_ref() {
+        if self.rules(probe, false).iter().any(|i| i.service == s.service) == false {
+          continue;
+        }
+      }
+
+      let wait = probe.total_wait.unwrap_or(timeout).min(timeout);
+      let response = match send_probe(address, probe, tls, timeout, wait, binding) {
+        Ok(s) => { s },
+        Err(e) if e.kind() == ErrorKind::ConnectionRefused => { break; },
+        Err(_) => { continue; }
+      };
+
+      if response.is_empty() {
+        continue;
+      }
+
+      if tls.is_none() && is_tls_banner(&response) == true {
+        tls_answer = true;
+      }
+
+      match self.match_response(probe, &response, soft.as_ref().map(|i| i.service.as_str())) {
+        Some((info, false)) => { return (Some(info), tls_answer); },
+        Some((info, true)) if soft.is_none() => { soft = Some(info); },
+        _ => {}
+      }
+    }
+
+    (soft, tls_answer)
+  }
+}
+
+/**Function sends a probe to a service and reads the answer.
+ * Params:
+ *  address: &SocketAddr               {The address and port of the service}
+ *  probe:   &ServiceProbe             {The probe to send}
+ *  tls:     Option<&Arc<ClientConfig>> {The tls settings when the probe is sent over tls}
+ *  timeout: Duration                  {How long to wait for the connection}
+ *  wait:    Duration                  {How long to wait for the answer}
+ *  binding: &SourceBinding            {The interface and address picked by the user}
+ * Returns Result<Vec<u8>, Error>
+ */
+pub fn send_probe(address: &SocketAddr, probe: &ServiceProbe, tls: Option<&Arc<ClientConfig>>, timeout: Duration, wait: Duration, binding: &SourceBinding) -> Result<Vec<u8>, Error> {
+  if probe.udp == true {
+    let socket = binding.udp_socket(address)?;
+    socket.connect(address)?;
+    socket.send(&probe.payload)?;
+    socket.set_read_timeout(Some(wait.max(Duration::from_millis(1))))?;
+
+    let mut buffer = vec![0u8; MAX_RESPONSE_LEN];
+    let size = socket.recv(&mut buffer)?;
+    buffer.truncate(size);
+    return Ok(buffer);
+  }
+
+  let mut stream = binding.connect_timeout(address, timeout)?;
+  stream.set_write_timeout(Some(timeout.max(Duration::from_millis(1))))?;
+
+  let config = match tls {
+    Some(s) => { s },
+    None => {
+      if !probe.payload.is_empty() {
+        stream.write_all(&probe.payload)?;
+      }
+
+      return Ok(read_banner(&mut stream, wait, MAX_RESPONSE_LEN));
+    }
+  };
+
+  let connection = match ClientConnection::new(config.clone(), ServerName::IpAddress(address.ip().into())) {
+    Ok(s) => { s },
+    Err(e) => { return Err(Error::new(ErrorKind::InvalidInput, e)); }
+  };
+
+  // The handshake is finished before the probe is sent, so services that talk first get the chance to.
+  stream.set_read_timeout(Some(timeout.max(Duration::from_millis(1))))?;
+  let mut tls_stream = StreamOwned::new(connection, stream);
+  while tls_stream.conn.is_handshaking() == true {
+    tls_stream.conn.complete_io(&mut tls_stream.sock)?;
+  }
+
+  if !probe.payload.is_empty() {
+    tls_stream.write_all(&probe.payload)?;
+  }
+
+  Ok(read_tls_answer(&mut tls_stream, wait))
+}
+
+// Reads the answer to a probe sent over tls the same way a banner is read, the rest of it gets a short wait once it starts.
+fn read_tls_answer(stream: &mut StreamOwned<ClientConnection, TcpStream>, wait: Duration) -> Vec<u8> {
+  let mut out: Vec<u8> = Default::default();
+  let mut buffer = [0u8; 4096];
+
+  if stream.sock.set_read_timeout(Some(wait.max(Duration::from_millis(1)))).is_err() {
+    return out;
+  }
+
+  while out.len() < MAX_RESPONSE_LEN {
+    match stream.read(&mut buffer) {
+      Ok(0) => { break; },
+      Ok(size) => {
+        out.extend_from_slice(&buffer[..size]);
+
+        if stream.sock.set_read_timeout(Some(REST_WAIT)).is_err() {
+          break;
+        }
+      },
+
+      Err(_) => { break; }
+    }
+  }
+
+  out.truncate(MAX_RESPONSE_LEN);
+  out
+}
+
+/**Function parses the first line of a probe, such as TCP GetRequest q|GET / HTTP/1.0\r\n\r\n|.
+ * Params:
+ *  spec: &str {Everything after the Probe directive}
+ * Returns Result<ServiceProbe, String>
+ */
+pub fn parse_probe(spec: &str) -> Result<ServiceProbe, String> {
+  let mut parts = spec.splitn(3, char::is_whitespace);
+  let (protocol, name, payload) = match (parts.next(), parts.next(), parts.next()) {
+    (Some(p), Some(n), Some(q)) => { (p, n, q.trim()) },
+    _ =>                           { return Err(format!("{} is not a valid probe", spec)); }
+  };
+
+  let udp = match protocol {
+    "TCP" => { false },
+    "UDP" => { true },
+    _ =>     { return Err(format!("{} is not a valid protocol", protocol)); }
+  };
+
+  let payload = match payload.strip_prefix('q') {
+    Some(s) => { delimited(s)?.0 },
+    None =>    { return Err(format!("the payload of probe {} must start with q", name)); }
+  };
+
+  Ok(ServiceProbe { name: name.to_string(), udp, payload: unescape_payload(payload), rarity: 1, ..Default::default() })
+}
+
+/**Function parses a match rule, such as ssh m|^SSH-([\d.]+)-| p/OpenSSH/ v/$1/.
+ * Params:
+ *  spec: &str {Everything after the match or softmatch directive}
+ *  soft: bool {True for a softmatch}
+ * Returns Result<Option<ServiceMatch>, String> (None when the pattern uses regex features that are not supported)
+ */
+pub fn parse_match(spec: &str, soft: bool) -> Result<Option<ServiceMatch>, String> {
+  let (service, rest) = match spec.split_once(char::is_whitespace) {
+    Some((s, r)) => { (s, r.trim_start()) },
+    None =>         { return Err(format!("the {} rule has no pattern", spec)); }
+  };
+
+  let (pattern, mut rest) = match rest.strip_prefix('m') {
+    Some(s) => { delimited(s)? },
+    None =>    { return Err(format!("the pattern of the {} rule must start with m", service)); }
+  };
+
+  let flags: String = rest.chars().take_while(|c| c.is_ascii_alphabetic()).collect();
+  rest = &rest[flags.len()..];
+
+  let mut fields: Vec<(char, String)> = Default::default();
+  loop {
+    rest = rest.trim_start();
+
+    let (letter, field) = match (rest.strip_prefix("cpe:"), rest.chars().next()) {
+      (Some(s), _) =>    { ('c', s) },
+      (None, Some(c)) => { (c, &rest[c.len_utf8()..]) },
+      (None, None) =>    { break; }
+    };
+
+    let (value, after) = delimited(field)?;
+    fields.push((letter, value.to_string()));
+
+    // Flags after a field, such as the a of a cpe, are not needed.
+    rest = after.trim_start_matches(|c: char| c.is_ascii_alphabetic());
+  }
+
+  let regex = RegexBuilder::new(&translate_pattern(pattern))
+  .unicode(false)
+  .case_insensitive(flags.contains('i'))
+  .dot_matches_new_line(flags.contains('s'))
+  .build();
+
+  match regex {
+    Ok(s) =>  { Ok(Some(ServiceMatch { service: service.to_string(), pattern: s, soft, fields })) },
+    Err(_) => { Ok(None) }
+  }
+}
+
+/**Function splits off text that is wrapped in a delimiter, such as |GET / HTTP/1.0| or /OpenSSH/.
+ * Params:
+ *  text: &str {Text that starts with the delimiter}
+ * Returns Result<(&str, &str), String> (The wrapped text and everything after the closing delimiter)
+ */
+pub fn delimited(text: &str) -> Result<(&str, &str), String> {
+  let delimiter = match text.chars().next() {
+    Some(s) => { s },
+    None =>    { return Err(String::from("a delimiter is missing")); }
+  };
+
+  let body = &text[delimiter.len_utf8()..];
+  match body.find(delimiter) {
+    Some(end) => { Ok((&body[..end], &body[end + delimiter.len_utf8()..])) },
+    None =>      { Err(format!("the {} delimiter is never closed", delimiter)) }
+  }
+}
+
+/**Function rewrites the parts of a pcre pattern the regex crate spells differently.
+ * Params:
+ *  pattern: &str {The pattern from the probe file}
+ * Returns String
+ */
+pub fn translate_pattern(pattern: &str) -> String {
+  let mut out = String::new();
+  let mut chars = pattern.chars();
+
+  while let Some(c) = chars.next() {
+    if c != '\\' {
+      out.push(c);
+      continue;
+    }
+
+    match chars.next() {
+      Some('0') => { out.push_str("\\x00"); },
+      Some('Z') => { out.push_str("(?:\\n?\\z)"); },
+      Some(s) =>   { out.push('\\'); out.push(s); },
+      None =>      { out.push('\\'); }
+    }
+  }
+
+  out
+}
+
+/**Function reads a list of ports and port ranges, such as 80,443,8000-8010.
+ * Params:
+ *  spec: &str {The ports}
+ * Returns Result<HashSet<u16>, String>
+ */
+pub fn parse_port_list(spec: &str) -> Result<HashSet<u16>, String> {
+  let mut out: HashSet<u16> = Default::default();
+
+  for i in spec.split(',') {
+    let (start, end) = parse_port_range(i.trim())?;
+    out.extend(start..=end);
+  }
+
+  Ok(out)
+}
+
+// Returns the first and last port of a range such as 8000-8010, or the same port twice when there is no range.
+pub fn parse_port_range(item: &str) -> Result<(u16, u16), String> {
+  let (start, end) = item.split_once('-').unwrap_or((item, item));
+
+  match (start.trim().parse::<u16>(), end.trim().parse::<u16>()) {
+    (Ok(s), Ok(e)) if s <= e => { Ok((s, e)) },
+    _ =>                        { Err(format!("{} is not a valid port or port range", item)) }
+  }
+}
+
+/**Function turns the escaped payload of a probe into the bytes that are sent.
+ * Params:
+ *  text: &str {The payload from the probe file}
+ * Returns Vec<u8>
+ */
+pub fn unescape_payload(text: &str) -> Vec<u8> {
+  let bytes = text.as_bytes();
+  let mut out: Vec<u8> = Default::default();
+  let mut idx = 0;
+
+  while idx < bytes.len() {
+    if bytes[idx] != b'\\' || idx + 1 >= bytes.len() {
+      out.push(bytes[idx]);
+      idx += 1;
+      continue;
+    }
+
+    idx += 2;
+    match bytes[idx - 1] {
+      b'0' => { out.push(0); },
+      b'a' => { out.push(0x07); },
+      b'b' => { out.push(0x08); },
+      b'f' => { out.push(0x0c); },
+      b'n' => { out.push(b'\n'); },
+      b'r' => { out.push(b'\r'); },
+      b't' => { out.push(b'\t'); },
+      b'v' => { out.push(0x0b); },
+      b'x' => {
+        let hex = std::str::from_utf8(&bytes[idx..(idx + 2).min(bytes.len())]).unwrap_or("");
+        match u8::from_str_radix(hex, 16) {
+          Ok(s) =>  { out.push(s); idx += 2; },
+          Err(_) => { out.push(b'x'); }
+        }
+      },
+
+      other => { out.push(other); }
+    }
+  }
+
+  out
+}
+
+/**Function fills in the version info template of a rule from the groups the pattern captured.
+ * $1 inserts a group as it is. $P(1) keeps only its printable bytes, $SUBST(1,"_",".") replaces text in it and
+ * $I(1,">") reads it as a big endian or little endian number.
+ * Params:
+ *  template: &str       {The template, such as $1 or protocol $1.$2}
+ *  captures: &Captures  {The groups captured by the pattern}
+ * Returns String
+ */
+pub fn fill_template(template: &str, captures: &Captures) -> String {
+  let mut out = String::new();
+  let mut rest = template;
+
+  while let Some(start) = rest.find('$') {
+    out.push_str(&rest[..start]);
+    rest = &rest[start + 1..];
+
+    if let Some(digit) = rest.chars().next().and_then(|c| c.to_digit(10)) {
+      out.push_str(&String::from_utf8_lossy(capture_group(captures, digit as usize)));
+      rest = &rest[1..];
+      continue;
+    }
+
+    match template_helper(rest) {
+      Some((name, args, after)) => {
+        out.push_str(&apply_helper(name, &args, captures));
+        rest = after;
+      },
+
+      None => { out.push('$'); }
+    }
+  }
+
+  out.push_str(rest);
+  out.trim().to_string()
+}
+
+// Returns the bytes of a captured group, or nothing when the group did not take part in the match.
+fn capture_group<'a>(captures: &Captures<'a>, group: usize) -> &'a [u8] {
+  captures.get(group).map(|i| i.as_bytes()).unwrap_or(&[])
+}
+
+/**Function splits a helper such as SUBST(1,"_",".") into its name and arguments.
+ * Params:
+ *  text: &str {The template right after the $}
+ * Returns Option<(&str, Vec<String>, &str)> (The name, the arguments without quotes and the rest of the template)
+ */
+fn template_helper(text: &str) -> Option<(&str, Vec<String>, &str)> {
+  let open = text.find('(')?;
+  let name = &text[..open];
+  if name.is_empty() || name.chars().all(|c| c.is_ascii_alphabetic()) == false {
+    return None;
+  }
+
+  let mut args: Vec<String> = vec![String::new()];
+  let mut quoted = false;
+
+  for (idx, c) in text[open + 1..].char_indices() {
+    match (c, quoted) {
+      ('"', _) =>     { quoted = !quoted; },
+      (',', false) => { args.push(String::new()); },
+      (')', false) => { return Some((name, args, &text[open + 1 + idx + 1..])); },
+      _ => {
+        if let Some(last) = args.last_mut() {
+          last.push(c);
+        }
+      }
+    }
+  }
+
+  None
+}
+
+// Returns the text a template helper stands for.
+fn apply_helper(name: &str, args: &[String], captures: &Captures) -> String {
+  let group = match args.first().and_then(|i| i.trim().parse::<usize>().ok()) {
+    Some(s) => { capture_group(captures, s) },
+    None =>    { return String::new(); }
+  };
+
+  match (name, args.len()) {
+    ("P", _) => {
+      group.iter().filter(|i| (0x20..=0x7e).contains(*i)).map(|i| *i as char).collect()
+    },
+
+    ("SUBST", 3) => {
+      String::from_utf8_lossy(group).replace(args[1].as_str(), args[2].as_str())
+    },
+
+    ("I", 2) if group.len() <= 8 => {
+      let value = match args[1].as_str() {
+        "<" => { group.iter().rev().fold(0u64, |acc, i| (acc << 8) | *i as u64) },
+        _ =>   { group.iter().fold(0u64, |acc, i| (acc << 8) | *i as u64) }
+      };
+
+      value.to_string()
+    },
+
+    _ => { String::new() }
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  const PROBES: &str = "
+# A comment.
+Exclude T:9100-9102,U:53,7
+
+Probe TCP NULL q||
+totalwaitms 6000
+match ssh m|^SSH-([\\d.]+)-OpenSSH_([\\w.]+)| p/OpenSSH/ v/$2/ i/protocol $1/ cpe:/a:openbsd:openssh:$2/
+softmatch ftp m|^220 |
+
+Probe TCP GetRequest q|GET / HTTP/1.0\\r\\n\\r\\n|
+rarity 1
+ports 80,8000-8002
+sslports 443
+fallback NULL
+match http m|^HTTP/1\\.[01] \\d\\d\\d .*\\r\\nServer: nginx/([\\d.]+)|s p/nginx/ v/$1/
+match broken m|^(?=lookahead)|
+
+Probe UDP DNS q|\\0\\x06\\x01|
+rarity 9
+ports 53
+";
+
+  #[test]
+  fn parse_reads_probes() {
+    let database = ProbeDatabase::parse(PROBES).unwrap();
+
+    assert_eq!(database.probes.len(), 3);
+    assert_eq!(database.skipped, 1);
+    assert_eq!(database.exclude_tcp, HashSet::from([9100, 9101, 9102]));
+    assert_eq!(database.exclude_udp, HashSet::from([53, 7]));
+
+    let get = &database.probes[1];
+    assert_eq!(get.payload, b"GET / HTTP/1.0\r\n\r\n");
+    assert_eq!(get.ports, HashSet::from([80, 8000, 8001, 8002]));
+    assert_eq!(get.ssl_ports, HashSet::from([443]));
+    assert_eq!(get.fallback, vec!["NULL"]);
+    assert!(get.suits(8001, false));
+    assert!(get.lists(443, true));
+
+    let dns = &database.probes[2];
+    assert!(dns.udp);
+    assert!(dns.suits(53, false));
+    assert!(!dns.suits(54, false));
+    assert_eq!(database.probes[0].total_wait, Some(Duration::from_millis(6000)));
+  }
+
+  #[test]
+  fn parse_rejects_malformed_files() {
+    assert!(ProbeDatabase::parse("ports 80").is_err());
+    assert!(ProbeDatabase::parse("Probe TCP NULL").is_err());
+    assert!(ProbeDatabase::parse("Probe SCTP NULL q||").is_err());
+    assert!(ProbeDatabase::parse("Probe TCP NULL q|never closed").is_err());
+    assert!(ProbeDatabase::parse("Probe TCP NULL q||\nrarity ten").is_err());
+    assert!(ProbeDatabase::parse("Probe TCP NULL q||\nports 90-80").is_err());
+    assert!(ProbeDatabase::parse("Probe TCP NULL q||\nmatch ssh").is_err());
+    assert!(ProbeDatabase::parse("Probe TCP NULL q||\nunknown 1").is_err());
+    assert_eq!(ProbeDatabase::parse("").unwrap().probes.len(), 0);
+  }
+
+  #[test]
+  fn builtin_probes_parse() {
+    let database = ProbeDatabase::builtin().unwrap();
+    assert!(database.probe("NULL", false).is_some());
+    assert!(database.probe("SSLSessionReq", false).is_some());
+  }
+
+  #[test]
+  fn match_response_fills_version() {
+    let database = ProbeDatabase::parse(PROBES).unwrap();
+    let (info, soft) = database.match_response(&database.probes[0], b"SSH-2.0-OpenSSH_9.6p1 Ubuntu\r\n", None).unwrap();
+
+    assert!(!soft);
+    assert_eq!(info.service, "ssh");
+    assert_eq!(info.product, Some(String::from("OpenSSH")));
+    assert_eq!(info.version, Some(String::from("9.6p1")));
+    assert_eq!(info.info, Some(String::from("protocol 2.0")));
+    assert_eq!(info.cpe, vec!["cpe:/a:openbsd:openssh:9.6p1"]);
+
+    // The null probe is a fallback of every tcp probe.
+    let (info, soft) = database.match_response(&database.probes[1], b"220 ready\r\n", None).unwrap();
+    assert_eq!((info.service.as_str(), soft), ("ftp", true));
+    assert!(database.match_response(&database.probes[1], b"\xff\xfe", None).is_none());
+  }
+
+  #[test]
+  fn unescape_payload_escapes() {
+    assert_eq!(unescape_payload(""), b"");
+    assert_eq!(unescape_payload("a\\r\\n\\t\\0\\x41\\x4a"), b"a\r\n\t\0AJ");
+    assert_eq!(unescape_payload("\\a\\b\\f\\v\\\\\\|"), b"\x07\x08\x0c\x0b\\|");
+    assert_eq!(unescape_payload("\\xzz"), b"xzz");
+    assert_eq!(unescape_payload("\\x4"), b"\x04");
+    assert_eq!(unescape_payload("end\\"), b"end\\");
+    assert_eq!(unescape_payload("\\x\u{e9}"), "x\u{e9}".as_bytes());
+  }
+
+  #[test]
+  fn fill_template_helpers() {
+    let pattern = Regex::new(r"(?-u)^(\w+) (\w+)(?: (x))?(\x00\x01)(\xff\x00)").unwrap();
+    let captures = pattern.captures(b"Some_Name 4_2\x00\x01\xff\x00").unwrap();
+
+    assert_eq!(fill_template("$1 v$2", &captures), "Some_Name v4_2");
+    assert_eq!(fill_template("[$3]", &captures), "[]");
+    assert_eq!(fill_template("$9", &captures), "");
+    assert_eq!(fill_template("$SUBST(2,\"_\",\".\")", &captures), "4.2");
+    assert_eq!(fill_template("$P(1)", &captures), "Some_Name");
+    assert_eq!(fill_template("$I(4,\">\") $I(4,\"<\") $I(5,\"<\")", &captures), "1 256 255");
+    assert_eq!(fill_template("$P(x) $ cost $UNKNOWN(1)", &captures), "$ cost");
+    assert_eq!(fill_template("$SUBST(1", &captures), "$SUBST(1");
+  }
+
+  #[test]
+  fn translate_pattern_rewrites_pcre() {
+    assert_eq!(translate_pattern("\\0\\Z"), "\\x00(?:\\n?\\z)");
+    assert_eq!(translate_pattern("\\d+\\"), "\\d+\\");
+  }
+}
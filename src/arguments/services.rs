@@ -171,4 +171,23 @@ pub fn service_map(service: u16) -> Option<&'static str> {
    47624 => { Some("directplaysrvr") }
    _ =>     { None }
   }
-}
\ No newline at end of file
+}
+
+/**Function returns every port that the service name maps to.
+ * Params:
+ *  name: &str {The name of the service}
+ * Returns Vec<u16> (The ports that use the service name)
+ */
+pub fn service_ports(name: &str) -> Vec<u16> {
+  let mut out: Vec<u16> = Default::default();
+
+  for i in 1..=u16::MAX {
+    if let Some(service) = service_map(i) {
+      if service.eq_ignore_ascii_case(name) {
+        out.push(i);
+      }
+    }
+  }
+
+  out
+}
@@ -0,0 +1,375 @@
+use std::io::{Error, ErrorKind, Write};
+use std::net::{SocketAddr, TcpStream};
+use std::sync::Arc;
+use std::time::Duration;
+use rustls::ClientConfig;
+use rustls::pki_types::ServerName;
+use super::{handshake_stream, open_stream, read_until, text_command, SourceBinding, TlsReport};
+
+// The ldap extended request for starttls, message id 1 with the oid 1.3.6.1.4.1.1466.20037.
+const LDAP_STARTTLS: &[u8] = b"\x30\x1d\x02\x01\x01\x77\x18\x80\x161.3.6.1.4.1.1466.20037";
+
+// Protocols that start in plain text and switch to tls when the client asks.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum StartTls {
+  Smtp,
+  Imap,
+  Pop3,
+  Ldap,
+  Ftp,
+  Xmpp,
+}
+
+impl StartTls {
+  pub fn name(&self) -> String {
+    match self {
+      StartTls::Smtp => { String::from("smtp") }
+      StartTls::Imap => { String::from("imap") }
+      StartTls::Pop3 => { String::from("pop3") }
+      StartTls::Ldap => { String::from("ldap") }
+      StartTls::Ftp =>  { String::from("ftp") }
+      StartTls::Xmpp => { String::from("xmpp") }
+    }
+  }
+}
+
+/**Function picks the starttls protocol of a port from its greeting, or from its usual port when it did not greet us.
+ * Params:
+ *  address: &SocketAddr {The address and port of the service}
+ *  banner:  &[u8]       {What the banner grab got back}
+ * Returns Option<StartTls>
+ */
+pub fn starttls_protocol(address: &SocketAddr, banner: &[u8]) -> Option<StartTls> {
+  let text = String::from_utf8_lossy(banner).to_lowercase();
+
+  if text.starts_with("220") == true {
+    if text.contains("ftp") == true || address.port() == 21 {
+      return Some(StartTls::Ftp);
+    }
+
+    if text.contains("smtp") == true || text.contains("mail") == true || [25, 587, 2525].contains(&address.port()) {
+      return Some(StartTls::Smtp);
+    }
+  }
+
+  if text.starts_with("* ok") == true {
+    return Some(StartTls::Imap);
+  }
+
+  if text.starts_with("+ok") == true {
+    return Some(StartTls::Pop3);
+  }
+
+  match address.port() {
+    389 =>         { Some(StartTls::Ldap) },
+    5222 | 5269 => { Some(StartTls::Xmpp) },
+    _ =>           { None }
+  }
+}
+
+/**Function upgrades a plain text connection with starttls and records the tls handshake that follows.
+ * Params:
+ *  address:  &SocketAddr        {The address and port of the service}
+ *  name:     &str               {The target as it was typed by the user}
+ *  protocol: StartTls           {The protocol the service speaks}
+ *  config:   &Arc<ClientConfig> {The tls settings}
+ *  timeout:  Duration           {How long to wait for the connection and for each reply}
+ *  binding:  &SourceBinding     {The interface and address picked by the user}
+ * Returns Result<TlsReport, Error>
+ */
+pub fn starttls_handshake(address: &SocketAddr, name: &str, protocol: StartTls, config: &Arc<ClientConfig>, timeout: Duration, binding: &SourceBinding) -> Result<TlsReport, Error> {
+  let mut stream = open_stream(address, timeout, binding)?;
+
+  let upgraded = match protocol {
+    StartTls::Smtp => { upgrade_smtp(&mut stream) },
+    StartTls::Imap => { upgrade_imap(&mut stream) },
+    StartTls::Pop3 => { upgrade_pop3(&mut stream) },
+    StartTls::Ldap => { upgrade_ldap(&mut stream) },
+    StartTls::Ftp =>  { upgrade_ftp(&mut stream) },
+    StartTls::Xmpp => { upgrade_xmpp(&mut stream, &xmpp_domain(name, address), address.port() == 5269) }
+  };
+
+  if upgraded == false {
+    return Err(Error::new(ErrorKind::Unsupported, format!("{} server refused starttls", protocol.name())));
+  }
+
+  let mut out = handshake_stream(&mut stream, address, name, config)?;
+  out.starttls = Some(protocol.name());
+  Ok(out)
+}
+
+// Reads the greeting, says ehlo and asks for starttls.
+fn upgrade_smtp(stream: &mut TcpStream) -> bool {
+  matches!(text_command(stream, ""), Some((220, _)))
+  && matches!(text_command(stream, "EHLO lethal_potato"), Some((250, _)))
+  && matches!(text_command(stream, "STARTTLS"), Some((220, _)))
+}
+
+// Reads the greeting and asks for auth tls.
+fn upgrade_ftp(stream: &mut TcpStream) -> bool {
+  matches!(text_command(stream, ""), Some((220, _))) && matches!(text_command(stream, "AUTH TLS"), Some((234, _)))
+}
+
+// Reads the greeting and sends a tagged starttls command. The tagged reply tells us whether it worked.
+fn upgrade_imap(stream: &mut TcpStream) -> bool {
+  let mut buffer: Vec<u8> = Default::default();
+  if read_until(stream, &mut buffer, |i| i.ends_with(b"\n")) == false || buffer.starts_with(b"* OK") == false {
+    return false;
+  }
+
+  if stream.write_all(b"a001 STARTTLS\r\n").is_err() {
+    return false;
+  }
+
+  buffer.clear();
+  read_until(stream, &mut buffer, |i| i.ends_with(b"\n") && String::from_utf8_lossy(i).lines().any(|l| l.starts_with("a001 ")));
+  String::from_utf8_lossy(&buffer).lines().any(|i| i.to_uppercase().starts_with("A001 OK"))
+}
+
+// Reads the greeting and asks for stls.
+fn upgrade_pop3(stream: &mut TcpStream) -> bool {
+  let mut buffer: Vec<u8> = Default::default();
+  if read_until(stream, &mut buffer, |i| i.ends_with(b"\n")) == false || buffer.starts_with(b"+OK") == false {
+    return false;
+  }
+
+  if stream.write_all(b"STLS\r\n").is_err() {
+    return false;
+  }
+
+  buffer.clear();
+  read_until(stream, &mut buffer, |i| i.ends_with(b"\n")) && buffer.starts_with(b"+OK")
+}
+
+// Sends the extended request for starttls and checks the result code of the extended response.
+fn upgrade_ldap(stream: &mut TcpStream) -> bool {
+  if stream.write_all(LDAP_STARTTLS).is_err() {
+    return false;
+  }
+
+  let mut buffer: Vec<u8> = Default::default();
+  read_until(stream, &mut buffer, ber_complete);
+
+  ldap_result_code(&buffer) == Some(0)
+}
+
+/**Function reads the result code of an ldap extended response.
+ * The reply is walked element by element: the LDAPMessage sequence, its message id and then the [APPLICATION 24]
+ * extended response, whose first element is the enumerated result code.
+ * Params:
+ *  data: &[u8] {The reply of the server}
+ * Returns Option<u32> (None when the reply is not a whole extended response)
+ */
+fn ldap_result_code(data: &[u8]) -> Option<u32> {
+  let message = ber_element(data, 0x30)?;
+
+  // The message id is an integer that comes before the operation.
+  if message.first() != Some(&0x02) {
+    return None;
+  }
+
+  let (header, length) = ber_header(message)?;
+  let operation = ber_element(message.get(header.checked_add(length)?..)?, 0x78)?;
+
+  let code = ber_element(operation, 0x0a)?;
+  if code.is_empty() || code.len() > 4 {
+    return None;
+  }
+
+  Some(code.iter().fold(0u32, |a, b| (a << 8) | *b as u32))
+}
+
+/**Function returns the contents of the ber element at the start of the data when it has the expected tag.
+ * Params:
+ *  data: &[u8] {The bytes that start with the element}
+ *  tag:  u8    {The tag the element must have}
+ * Returns Option<&[u8]>
+ */
+fn ber_element(data: &[u8], tag: u8) -> Option<&[u8]> {
+  if data.first() != Some(&tag) {
+    return None;
+  }
+
+  let (header, length) = ber_header(data)?;
+  data.get(header..header.checked_add(length)?)
+}
+
+/**Function reads the header of the ber element at the start of the data.
+ * Params:
+ *  data: &[u8] {The bytes that start with the element}
+ * Returns Option<(usize, usize)> (The size of the tag and length bytes and the size of the contents)
+ */
+fn ber_header(data: &[u8]) -> Option<(usize, usize)> {
+  let first = *data.get(1)?;
+
+  if first < 0x80 {
+    return Some((2, first as usize));
+  }
+
+  let size = (first & 0x7f) as usize;
+  if (1..=4).contains(&size) == false {
+    return None;
+  }
+
+  let length = data.get(2..2 + size)?.iter().fold(0usize, |a, b| (a << 8) | *b as usize);
+  Some((2 + size, length))
+}
+
+// Returns true once a whole ber element has arrived. Lengths above 127 are stored in the bytes that follow.
+// A length that takes more than four bytes is not ldap, so there is no point waiting for the rest of it.
+fn ber_complete(data: &[u8]) -> bool {
+  if data.len() < 2 {
+    return false;
+  }
+
+  if data[1] < 0x80 {
+    return data.len() >= 2 + data[1] as usize;
+  }
+
+  let size = (data[1] & 0x7f) as usize;
+  if size > 4 {
+    return true;
+  }
+
+  let length = match data.get(2..2 + size) {
+    Some(bytes) => { bytes.iter().fold(0usize, |a, b| (a << 8) | *b as usize) },
+    None =>        { return false; }
+  };
+
+  match (2 + size).checked_add(length) {
+    Some(end) => { data.len() >= end },
+    None =>      { true }
+  }
+}
+
+// Opens an xmpp stream, waits for the features of the server and asks for starttls.
+fn upgrade_xmpp(stream: &mut TcpStream, domain: &str, server: bool) -> bool {
+  let namespace = if server == true { "jabber:server" } else { "jabber:client" };
+  let header = format!(
+    "<?xml version='1.0'?><stream:stream to='{}' xmlns='{}' xmlns:stream='http://etherx.jabber.org/streams' version='1.0'>",
+    domain, namespace
+  );
+
+  if stream.write_all(header.as_bytes()).is_err() {
+    return false;
+  }
+
+  let mut buffer: Vec<u8> = Default::default();
+  read_until(stream, &mut buffer, |i| contains(i, b"</stream:features>") || contains(i, b"</stream:stream>"));
+
+  if contains(&buffer, b"<starttls") == false {
+    return false;
+  }
+
+  if stream.write_all(b"<starttls xmlns='urn:ietf:params:xml:ns:xmpp-tls'/>").is_err() {
+    return false;
+  }
+
+  buffer.clear();
+  read_until(stream, &mut buffer, |i| contains(i, b"<proceed") || contains(i, b"<failure"));
+  contains(&buffer, b"<proceed")
+}
+
+// Returns the domain named in the xmpp stream header, the typed hostname or else the address.
+fn xmpp_domain(name: &str, address: &SocketAddr) -> String {
+  match ServerName::try_from(name) {
+    Ok(ServerName::DnsName(_)) => { name.to_string() },
+    _ =>                          { address.ip().to_string() }
+  }
+}
+
+// Returns true when the needle appears anywhere in the data.
+fn contains(data: &[u8], needle: &[u8]) -> bool {
+  data.windows(needle.len()).any(|i| i == needle)
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  // Builds an ldap extended response with the result code and diagnostic message, using long form lengths when asked.
+  fn extended_response(message_id: u8, code: u8, diagnostic: &str, long_form: bool) -> Vec<u8> {
+    let length = |size: usize| -> Vec<u8> {
+      match long_form {
+        true =>  { vec![0x84, 0, 0, (size >> 8) as u8, size as u8] },
+        false => { vec![size as u8] }
+      }
+    };
+
+    let mut result = vec![0x0a, 0x01, code, 0x04, 0x00, 0x04, diagnostic.len() as u8];
+    result.extend_from_slice(diagnostic.as_bytes());
+
+    let mut operation = vec![0x78];
+    operation.extend(length(result.len()));
+    operation.extend(result);
+
+    let mut message = vec![0x02, 0x01, message_id];
+    message.extend(operation);
+
+    let mut out = vec![0x30];
+    out.extend(length(message.len()));
+    out.extend(message);
+    out
+  }
+
+  #[test]
+  fn ldap_result_code_success() {
+    // The reply of openldap to a starttls request, with the oid of the request as the response name.
+    let mut reply = b"\x30\x24\x02\x01\x01\x78\x1f\x0a\x01\x00\x04\x00\x04\x00\x8a\x16".to_vec();
+    reply.extend_from_slice(b"1.3.6.1.4.1.1466.20037");
+
+    assert!(ber_complete(&reply));
+    assert_eq!(ldap_result_code(&reply), Some(0));
+    assert_eq!(ldap_result_code(&extended_response(1, 0, "", true)), Some(0));
+  }
+
+  #[test]
+  fn ldap_result_code_refused() {
+    let reply = extended_response(1, 2, "unsupported extended operation", false);
+    assert_eq!(ldap_result_code(&reply), Some(2));
+
+    let reply = extended_response(1, 52, "00000000: LdapErr: DSID-0C09, comment: Error initializing SSL/TLS", true);
+    assert_eq!(ldap_result_code(&reply), Some(52));
+  }
+
+  #[test]
+  fn ldap_result_code_checks_structure() {
+    // A message id of 0x78 followed by a search result done must not be read as an extended response.
+    let reply = b"\x30\x0c\x02\x01\x78\x65\x07\x0a\x01\x00\x04\x00\x04\x00";
+    assert_eq!(ldap_result_code(reply), None);
+
+    let reply = extended_response(0x78, 0, "", false);
+    assert_eq!(ldap_result_code(&reply), Some(0));
+
+    let reply = extended_response(1, 0, "", false);
+    assert_eq!(ldap_result_code(&reply[..reply.len() - 1]), None);
+    assert_eq!(ldap_result_code(b"\x30\x03\x02\x01"), None);
+    assert_eq!(ldap_result_code(b""), None);
+  }
+
+  #[test]
+  fn ber_complete_short_form() {
+    assert!(!ber_complete(b""));
+    assert!(!ber_complete(b"\x30"));
+    assert!(!ber_complete(b"\x30\x03\x02\x01"));
+    assert!(ber_complete(b"\x30\x03\x02\x01\x01"));
+  }
+
+  #[test]
+  fn ber_complete_long_form() {
+    let mut data = vec![0x30, 0x82, 0x01, 0x00];
+    assert!(!ber_complete(&data[..3]));
+    assert!(!ber_complete(&data));
+
+    data.resize(4 + 256, 0);
+    assert!(ber_complete(&data));
+  }
+
+  #[test]
+  fn ber_complete_oversized_length() {
+    assert!(!ber_complete(b"\x30\x84\xff\xff\xff\xff"));
+    assert!(ber_complete(b"\x30\x85\xff\xff\xff\xff\xff"));
+    assert!(ber_complete(b"\x30\xff\xff\xff\xff\xff\xff\xff\xff\xff\xff\xff"));
+    assert!(ber_complete(b"\x30\x89"));
+  }
+}
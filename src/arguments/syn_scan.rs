@@ -0,0 +1,438 @@
+use std::collections::{HashMap, HashSet};
+use std::io::{Error, ErrorKind};
+use std::net::{IpAddr, SocketAddr};
+use std::sync::{Arc, Mutex, atomic::{AtomicBool, Ordering}};
+use std::thread;
+use std::time::{Duration, Instant};
+use pnet_packet::Packet;
+use pnet_packet::ip::IpNextHeaderProtocols;
+use pnet_packet::ipv4::{self, MutableIpv4Packet};
+use pnet_packet::tcp::{self, MutableTcpPacket, TcpPacket, TcpFlags, TcpOption};
+use pnet_transport::{transport_channel, TransportChannelType, TransportProtocol, TransportSender, TransportReceiver};
+use rand::Rng;
+use super::{RateLimiter, RttTable, SourceBinding};
+
+const IPV4_HEADER_LEN: usize = 20;
+const TCP_HEADER_LEN: usize = 24;       // The tcp header with a single mss option.
+
+// Describes how a target answered one of our syn packets.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum SynReply {
+  SynAck,     // The port is open.
+  Reset,      // The port is closed.
+}
+
+// Stores the values that are shared between the thread sending syn packets and the threads receiving replies.
+#[derive(Clone)]
+pub struct SynSession {
+  pub source_port: u16,                                       // Every probe is sent from this port.
+  pub sequence: u32,                                          // Every probe uses this sequence number.
+  pub sources: Arc<HashMap<IpAddr, IpAddr>>,                  // The local address used to reach each target.
+  pub targets: Arc<HashSet<(IpAddr, u16)>>,                   // Every address and port being scanned.
+  pub replies: Arc<Mutex<HashMap<(IpAddr, u16), SynReply>>>,  // The replies received so far.
+  pub sent: Arc<Mutex<HashMap<(IpAddr, u16), Instant>>>,      // The time each probe was sent.
+  pub rtt: Arc<RttTable>,                                     // Measures the round trip time to each host.
+  pub running: Arc<AtomicBool>,
+}
+
+/**Function finds the local address used to reach the target.
+ * The address picked by the user is used when there is one, otherwise the operating system picks it.
+ * Params:
+ *  target:  &SocketAddr     {The address of the target}
+ *  binding: &SourceBinding  {The interface and address picked by the user}
+ * Returns Result<IpAddr, Error>
+ */
+pub fn source_address(target: &SocketAddr, binding: &SourceBinding) -> Result<IpAddr, Error> {
+  if let Some(ip) = binding.source_for(&target.ip()) {
+    return Ok(ip);
+  }
+
+  let socket = binding.udp_socket(target)?;
+
+  // Connecting a udp socket sends nothing but makes the os pick a route and a source address.
+  socket.connect(target)?;
+  Ok(socket.local_addr()?.ip())
+}
+
+/**Function opens the raw socket used to send and receive tcp packets for one ip version.
+ * Ipv4 packets are built with their own ip header so the source address is always the one we picked.
+ * Params:
+ *  is_v6:   bool           {Opens an ipv6 socket when true}
+ *  binding: &SourceBinding {The interface and address picked by the user}
+ * Returns Result<(TransportSender, TransportReceiver), Error>
+ */
+pub fn open_channel(is_v6: bool, binding: &SourceBinding) -> Result<(TransportSender, TransportReceiver), Error> {
+  let channel_type = match is_v6 {
+    false => { TransportChannelType::Layer3(IpNextHeaderProtocols::Tcp) }
+    true =>  { TransportChannelType::Layer4(TransportProtocol::Ipv6(IpNextHeaderProtocols::Tcp)) }
+  };
+
+  let (tx, rx) = transport_channel(65535, channel_type)?;
+  binding.bind_raw(&tx, is_v6)?;
+
+  Ok((tx, rx))
+}
+
+/**Function builds a tcp packet and sends it to the target.
+ * Params:
+ *  sender:      &Mutex<TransportSender> {The raw socket}
+ *  source:      IpAddr                  {The local address}
+ *  source_port: u16                     {The local port}
+ *  target:      (IpAddr, u16)           {The address and port of the target}
+ *  sequence:    u32                     {The sequence number}
+ *  ack:         u32                     {The acknowledgement number}
+ *  flags:       u16                     {The tcp flags}
+ * Returns Result<usize, Error>
+ */
+pub fn send_tcp_packet(sender: &Mutex<TransportSender>, source: IpAddr, source_port: u16, target: (IpAddr, u16), sequence: u32, ack: u32, flags: u16) -> Result<usize, Error> {
+  let mut tcp_buffer = [0u8; TCP_HEADER_LEN];
+  let mut tcp_packet = match MutableTcpPacket::new(&mut tcp_buffer) {
+    Some(s) => { s },
+    None => { return Err(Error::other("unable to build tcp packet")); }
+  };
+
+  tcp_packet.set_source(source_port);
+  tcp_packet.set_destination(target.1);
+  tcp_packet.set_sequence(sequence);
+  tcp_packet.set_acknowledgement(ack);
+  tcp_packet.set_data_offset((TCP_HEADER_LEN / 4) as u8);
+  tcp_packet.set_flags(flags);
+  tcp_packet.set_window(1024);
+  tcp_packet.set_options(&[TcpOption::mss(1460)]);
+
+  let mut tx = match sender.lock() {
+    Ok(s) => { s },
+    Err(_) => { return Err(Error::other("raw socket is poisoned")); }
+  };
+
+  match (source, target.0) {
+    (IpAddr::V4(src), IpAddr::V4(dst)) => {
+      let checksum = tcp::ipv4_checksum(&tcp_packet.to_immutable(), &src, &dst);
+      tcp_packet.set_checksum(checksum);
+
+      let mut ip_buffer = [0u8; IPV4_HEADER_LEN + TCP_HEADER_LEN];
+      let mut ip_packet = match MutableIpv4Packet::new(&mut ip_buffer) {
+        Some(s) => { s },
+        None => { return Err(Error::other("unable to build ip packet")); }
+      };
+
+      ip_packet.set_version(4);
+      ip_packet.set_header_length((IPV4_HEADER_LEN / 4) as u8);
+      ip_packet.set_total_length((IPV4_HEADER_LEN + TCP_HEADER_LEN) as u16);
+      ip_packet.set_identification(rand::thread_rng().gen());
+      ip_packet.set_ttl(64);
+      ip_packet.set_next_level_protocol(IpNextHeaderProtocols::Tcp);
+      ip_packet.set_source(src);
+      ip_packet.set_destination(dst);
+      ip_packet.set_payload(tcp_packet.packet());
+
+      let checksum = ipv4::checksum(&ip_packet.to_immutable());
+      ip_packet.set_checksum(checksum);
+
+      tx.send_to(ip_packet, target.0)
+    }
+
+    (IpAddr::V6(src), IpAddr::V6(dst)) => {
+      let checksum = tcp::ipv6_checksum(&tcp_packet.to_immutable(), &src, &dst);
+      tcp_packet.set_checksum(checksum);
+
+      tx.send_to(tcp_packet, target.0)
+    }
+
+    _ => {
+      Err(Error::new(ErrorKind::InvalidInput, "source and target use different ip versions"))
+    }
+  }
+}
+
+/**Function works out if a tcp packet is a reply to one of our probes.
+ * Both a syn/ack and a reset have to acknowledge our sequence number, anything else is a stale or spoofed packet.
+ * Params:
+ *  session: &SynSession {The values shared by the scan}
+ *  from:    IpAddr      {The address that sent the packet}
+ *  packet:  &TcpPacket  {The tcp packet}
+ * Returns Option<SynReply>
+ */
+pub fn classify_reply(session: &SynSession, from: IpAddr, packet: &TcpPacket) -> Option<SynReply> {
+  let flags = packet.get_flags();
+
+  if packet.get_destination() != session.source_port || session.targets.contains(&(from, packet.get_source())) == false {
+    return None;
+  }
+
+  if packet.get_acknowledgement() != session.sequence.wrapping_add(1) {
+    return None;
+  }
+
+  if flags & TcpFlags::SYN != 0 && flags & TcpFlags::ACK != 0 {
+    return Some(SynReply::SynAck);
+  }
+
+  if flags & TcpFlags::RST != 0 {
+    return Some(SynReply::Reset);
+  }
+
+  None
+}
+
+/**Function works out if a tcp packet is a reply to one of our probes and records it.
+ * Open ports are torn down with a reset straight away.
+ * Params:
+ *  session: &SynSession            {The values shared by the scan}
+ *  sender:  &Mutex<TransportSender> {The raw socket used to send resets}
+ *  from:    IpAddr                  {The address that sent the packet}
+ *  packet:  &TcpPacket              {The tcp packet}
+ * Returns nothing.
+ */
+pub fn handle_reply(session: &SynSession, sender: &Mutex<TransportSender>, from: IpAddr, packet: &TcpPacket) -> () {
+  let target = (from, packet.get_source());
+  let reply = classify_reply(session, from, packet);
+
+  // We never finish the handshake, the connection is reset instead.
+  if reply == Some(SynReply::SynAck) {
+    if let Some(source) = session.sources.get(&from) {
+      let _ = send_tcp_packet(sender, *source, session.source_port, target, session.sequence.wrapping_add(1), 0, TcpFlags::RST);
+    }
+  }
+
+  if let Some(r) = reply {
+    let mut is_first = false;
+    if let Ok(mut replies) = session.replies.lock() {
+      is_first = replies.contains_key(&target) == false;
+      replies.entry(target).or_insert(r);
+    }
+
+    // Only the first reply to a probe is a fair measurement of the round trip time.
+    if is_first == true {
+      if let Some(sent) = session.sent.lock().ok().and_then(|i| i.get(&target).cloned()) {
+        session.rtt.sample(from, sent.elapsed());
+      }
+    }
+  }
+}
+
+/**Function receives packets from the raw socket until the scan stops running.
+ * Params:
+ *  session:  SynSession                   {The values shared by the scan}
+ *  sender:   Arc<Mutex<TransportSender>>  {The raw socket used to send resets}
+ *  receiver: TransportReceiver            {The raw socket used to receive replies}
+ *  is_v6:    bool                         {True when the socket receives ipv6 packets}
+ * Returns nothing.
+ */
+#[cfg(unix)]
+pub fn receive_replies(session: SynSession, sender: Arc<Mutex<TransportSender>>, mut receiver: TransportReceiver, is_v6: bool) -> () {
+  let poll_interval = Duration::from_millis(50);
+
+  if is_v6 == true {
+    let mut iter = pnet_transport::tcp_packet_iter(&mut receiver);
+
+    while session.running.load(Ordering::Relaxed) == true {
+      if let Ok(Some((packet, from))) = iter.next_with_timeout(poll_interval) {
+        handle_reply(&session, &sender, from, &packet);
+      }
+    }
+  }
+
+  else {
+    let mut iter = pnet_transport::ipv4_packet_iter(&mut receiver);
+
+    while session.running.load(Ordering::Relaxed) == true {
+      if let Ok(Some((packet, from))) = iter.next_with_timeout(poll_interval) {
+        if packet.get_next_level_protocol() != IpNextHeaderProtocols::Tcp {
+          continue;
+        }
+
+        if let Some(tcp_packet) = TcpPacket::new(packet.payload()) {
+          handle_reply(&session, &sender, from, &tcp_packet);
+        }
+      }
+    }
+  }
+}
+
+#[cfg(not(unix))]
+pub fn receive_replies(_session: SynSession, _sender: Arc<Mutex<TransportSender>>, _receiver: TransportReceiver, _is_v6: bool) -> () {}
+
+/**Function scans every target with tcp syn packets and returns the reply for each target that answered.
+ * Targets that never answered should be treated as filtered.
+ * Params:
+ *  targets: &Vec<SocketAddr> {Every address and port to scan}
+ *  rtt:     Arc<RttTable>    {Measures each host and decides how long to wait for replies after the last probe was sent}
+ *  debug:   bool             {Shows debug messages}
+ *  limiter: &RateLimiter     {Paces the syn packets}
+ *  binding: &SourceBinding   {The interface and address picked by the user}
+ * Returns Result<HashMap<SocketAddr, SynReply>, Error>
+ */
+pub fn syn_scan(targets: &Vec<SocketAddr>, rtt: Arc<RttTable>, debug: bool, limiter: &RateLimiter, binding: &SourceBinding) -> Result<HashMap<SocketAddr, SynReply>, Error> {
+  if cfg!(unix) == false {
+    return Err(Error::new(ErrorKind::Unsupported, "syn scans are only supported on unix systems"));
+  }
+
+  let mut rng = rand::thread_rng();
+  let mut sources: HashMap<IpAddr, IpAddr> = Default::default();
+
+  // The local address for each target is worked out before any packets are sent.
+  for i in targets {
+    if sources.contains_key(&i.ip()) == false {
+      sources.insert(i.ip(), source_address(i, binding)?);
+    }
+  }
+
+  let session = SynSession {
+    source_port: rng.gen_range(40000..60000),
+    sequence: rng.gen(),
+    sources: Arc::new(sources),
+    targets: Arc::new(targets.iter().map(|i| (i.ip(), i.port())).collect()),
+    replies: Arc::new(Mutex::new(Default::default())),
+    sent: Arc::new(Mutex::new(Default::default())),
+    rtt,
+    running: Arc::new(AtomicBool::new(true)),
+  };
+
+  if debug == true {
+    super::fmt::f_debug("Sending syn packets from port", format!("{}", session.source_port).as_str());
+  }
+
+  // A raw socket and receiving thread is created for each ip version we need.
+  let mut senders: HashMap<bool, Arc<Mutex<TransportSender>>> = Default::default();
+  let mut handles: Vec<thread::JoinHandle<()>> = Default::default();
+
+  for is_v6 in [false, true] {
+    if targets.iter().any(|i| i.is_ipv6() == is_v6) == false {
+      continue;
+    }
+
+    let (tx, rx) = open_channel(is_v6, binding)?;
+    let tx = Arc::new(Mutex::new(tx));
+    let th_session = session.clone();
+    let th_sender = tx.clone();
+
+    handles.push(thread::spawn(move || {
+      receive_replies(th_session, th_sender, rx, is_v6);
+    }));
+
+    senders.insert(is_v6, tx);
+  }
+
+  for i in targets {
+    let source = session.sources[&i.ip()];
+
+    if let Some(tx) = senders.get(&i.is_ipv6()) {
+      limiter.wait();
+
+      if let Ok(mut sent) = session.sent.lock() {
+        sent.insert((i.ip(), i.port()), Instant::now());
+      }
+
+      if let Err(e) = send_tcp_packet(tx, source, session.source_port, (i.ip(), i.port()), session.sequence, 0, TcpFlags::SYN) {
+        if debug == true {
+          super::fmt::f_error("unable to send syn packet", i.to_string().as_str(), format!("{}", e).as_str());
+        }
+      }
+    }
+  }
+
+  // Replies are collected until every target answered or the slowest host runs out of time.
+  let wait_start = Instant::now();
+  let addresses: HashSet<IpAddr> = targets.iter().map(|i| i.ip()).collect();
+
+  while wait_start.elapsed() < addresses.iter().map(|i| session.rtt.timeout(i)).max().unwrap_or_default() {
+    if let Ok(replies) = session.replies.lock() {
+      if replies.len() >= targets.len() {
+        break;
+      }
+    }
+
+    thread::sleep(Duration::from_millis(10));
+  }
+
+  session.running.store(false, Ordering::Relaxed);
+  for i in handles {
+    let _ = i.join();
+  }
+
+  let mut out: HashMap<SocketAddr, SynReply> = Default::default();
+  if let Ok(replies) = session.replies.lock() {
+    for i in targets {
+      if let Some(reply) = replies.get(&(i.ip(), i.port())) {
+        out.insert(*i, *reply);
+      }
+    }
+  }
+
+  Ok(out)
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use std::net::Ipv4Addr;
+
+  const TARGET: IpAddr = IpAddr::V4(Ipv4Addr::new(192, 0, 2, 10));
+
+  fn session() -> SynSession {
+    SynSession {
+      source_port: 45000,
+      sequence: 1000,
+      sources: Arc::new(Default::default()),
+      targets: Arc::new([(TARGET, 22)].into_iter().collect()),
+      replies: Arc::new(Mutex::new(Default::default())),
+      sent: Arc::new(Mutex::new(Default::default())),
+      rtt: Arc::new(RttTable::new(100, 1000)),
+      running: Arc::new(AtomicBool::new(true)),
+    }
+  }
+
+  fn reply(buffer: &mut [u8; TCP_HEADER_LEN], source: u16, destination: u16, ack: u32, flags: u16) -> TcpPacket<'_> {
+    let mut packet = MutableTcpPacket::new(buffer).unwrap();
+    packet.set_source(source);
+    packet.set_destination(destination);
+    packet.set_acknowledgement(ack);
+    packet.set_data_offset((TCP_HEADER_LEN / 4) as u8);
+    packet.set_flags(flags);
+
+    TcpPacket::new(buffer).unwrap()
+  }
+
+  #[test]
+  fn classify_reply_syn_ack_is_open() {
+    let mut buffer = [0u8; TCP_HEADER_LEN];
+    let packet = reply(&mut buffer, 22, 45000, 1001, TcpFlags::SYN | TcpFlags::ACK);
+
+    assert_eq!(classify_reply(&session(), TARGET, &packet), Some(SynReply::SynAck));
+  }
+
+  #[test]
+  fn classify_reply_matching_reset_is_closed() {
+    let mut buffer = [0u8; TCP_HEADER_LEN];
+    let packet = reply(&mut buffer, 22, 45000, 1001, TcpFlags::RST | TcpFlags::ACK);
+
+    assert_eq!(classify_reply(&session(), TARGET, &packet), Some(SynReply::Reset));
+  }
+
+  #[test]
+  fn classify_reply_ignores_mismatched_ack() {
+    let mut buffer = [0u8; TCP_HEADER_LEN];
+    let packet = reply(&mut buffer, 22, 45000, 5000, TcpFlags::RST | TcpFlags::ACK);
+    assert_eq!(classify_reply(&session(), TARGET, &packet), None);
+
+    let mut buffer = [0u8; TCP_HEADER_LEN];
+    let packet = reply(&mut buffer, 22, 45000, 1000, TcpFlags::SYN | TcpFlags::ACK);
+    assert_eq!(classify_reply(&session(), TARGET, &packet), None);
+  }
+
+  #[test]
+  fn classify_reply_ignores_other_ports_and_hosts() {
+    let mut buffer = [0u8; TCP_HEADER_LEN];
+    let packet = reply(&mut buffer, 22, 45001, 1001, TcpFlags::RST | TcpFlags::ACK);
+    assert_eq!(classify_reply(&session(), TARGET, &packet), None);
+
+    let mut buffer = [0u8; TCP_HEADER_LEN];
+    let packet = reply(&mut buffer, 80, 45000, 1001, TcpFlags::RST | TcpFlags::ACK);
+    assert_eq!(classify_reply(&session(), TARGET, &packet), None);
+
+    let mut buffer = [0u8; TCP_HEADER_LEN];
+    let packet = reply(&mut buffer, 22, 45000, 1001, TcpFlags::RST | TcpFlags::ACK);
+    assert_eq!(classify_reply(&session(), IpAddr::V4(Ipv4Addr::new(192, 0, 2, 11)), &packet), None);
+  }
+}
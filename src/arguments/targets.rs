@@ -0,0 +1,336 @@
+use std::fs::read_to_string;
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr};
+
+// The largest number of addresses a single target may expand into.
+pub const MAX_TARGET_HOSTS: u128 = 1 << 20;
+
+// Describes a single target item provided by the user.
+#[derive(Debug, Clone, PartialEq)]
+pub enum TargetSpec {
+  Address(IpAddr, u32),     // A single address and its ipv6 zone index.
+  Cidr(IpAddr, u8),         // A network address and its prefix length. Example: 10.0.0.0/24
+  Octets([(u8, u8); 4]),    // An ipv4 address where each octet may be a range. Example: 10.0.0.1-50
+  Hostname(String),         // A name that must be resolved before it can be scanned.
+}
+
+impl TargetSpec {
+
+  /**Function works out what kind of target was provided and parses it.
+   * Params:
+   *  text: &str {The target as a slice}
+   * Returns Result<TargetSpec, String>
+   */
+  pub fn parse(text: &str) -> Result<TargetSpec, String> {
+    let text = text.trim();
+
+    if text.is_empty() {
+      return Err(String::from("empty target"));
+    }
+
+    if let Some((ip, scope_id)) = parse_ip(text) {
+      return Ok(TargetSpec::Address(ip, scope_id));
+    }
+
+    // Network blocks in CIDR notation.
+    if let Some((network, prefix)) = text.split_once('/') {
+      let ip = match parse_ip(network) {
+        Some((s, 0)) => { s },
+        _ => { return Err(format!("invalid network address {}", network)); }
+      };
+
+      let max_prefix = if ip.is_ipv4() { 32 } else { 128 };
+      let prefix_len = match prefix.parse::<u8>() {
+        Ok(s) if s <= max_prefix => { s },
+        _ => { return Err(format!("invalid prefix length /{}", prefix)); }
+      };
+
+      return Ok(TargetSpec::Cidr(ip, prefix_len));
+    }
+
+    // Ipv4 addresses with ranges inside the octets.
+    let octets: Vec<&str> = text.split('.').collect();
+    if octets.len() == 4 && text.contains('-') && text.chars().all(|c| c.is_ascii_digit() || c == '.' || c == '-') {
+      let mut ranges: [(u8, u8); 4] = [(0, 0); 4];
+
+      for (idx, i) in octets.iter().enumerate() {
+        let (start, end) = match i.split_once('-') {
+          Some((a, b)) => { (a.parse::<u8>(), b.parse::<u8>()) }
+          None =>         { (i.parse::<u8>(), i.parse::<u8>()) }
+        };
+
+        match (start, end) {
+          (Ok(a), Ok(b)) if a <= b => { ranges[idx] = (a, b); },
+          _ => { return Err(format!("invalid octet range {}", i)); }
+        }
+      }
+
+      return Ok(TargetSpec::Octets(ranges));
+    }
+
+    // Anything else must look like a hostname.
+    if text.chars().all(|c| c.is_ascii_alphanumeric() || c == '.' || c == '-' || c == '_') {
+      return Ok(TargetSpec::Hostname(text.to_string()));
+    }
+
+    Err(String::from("invalid ip address"))
+  }
+
+  /**Function checks if an address is covered by the target.
+   * Params:
+   *  &self
+   *  ip: &IpAddr {The address to check}
+   * Returns bool.
+   */
+  pub fn contains(&self, ip: &IpAddr) -> bool {
+    match (self, ip) {
+      (TargetSpec::Address(a, _), _) => { a == ip }
+
+      (TargetSpec::Cidr(IpAddr::V4(net), prefix), IpAddr::V4(v4)) => {
+        let mask = if *prefix == 0 { 0 } else { u32::MAX << (32 - *prefix as u32) };
+        u32::from(*net) & mask == u32::from(*v4) & mask
+      }
+
+      (TargetSpec::Cidr(IpAddr::V6(net), prefix), IpAddr::V6(v6)) => {
+        let mask = if *prefix == 0 { 0 } else { u128::MAX << (128 - *prefix as u32) };
+        u128::from(*net) & mask == u128::from(*v6) & mask
+      }
+
+      (TargetSpec::Octets(ranges), IpAddr::V4(v4)) => {
+        v4.octets().iter().zip(ranges.iter()).all(|(o, (a, b))| o >= a && o <= b)
+      }
+
+      _ => { false }
+    }
+  }
+
+  /**Function returns the number of addresses the target expands into.
+   * Params:
+   *  &self
+   * Returns u128.
+   */
+  pub fn size(&self) -> u128 {
+    match self {
+      TargetSpec::Address(_, _) | TargetSpec::Hostname(_) => { 1 }
+      TargetSpec::Cidr(ip, prefix) => {
+        let bits = if ip.is_ipv4() { 32 } else { 128 };
+        let host_bits = (bits - *prefix as u32).min(127);
+        1u128 << host_bits
+      }
+      TargetSpec::Octets(ranges) => {
+        ranges.iter().map(|(a, b)| (*b as u128 - *a as u128) + 1).product()
+      }
+    }
+  }
+
+  /**Function expands the target into every address it covers.
+   * The network and broadcast addresses of ipv4 blocks larger than a /31 are skipped.
+   * Hostnames cannot be expanded and return an empty vec.
+   * Params:
+   *  &self
+   * Returns Result<Vec<(IpAddr, u32)>, String>
+   */
+  pub fn expand(&self) -> Result<Vec<(IpAddr, u32)>, String> {
+    let mut out: Vec<(IpAddr, u32)> = Default::default();
+
+    if self.size() > MAX_TARGET_HOSTS {
+      return Err(format!("target expands into more than {} addresses", MAX_TARGET_HOSTS));
+    }
+
+    match self {
+      TargetSpec::Address(ip, scope_id) => {
+        out.push((*ip, *scope_id));
+      }
+
+      TargetSpec::Cidr(IpAddr::V4(ip), prefix) => {
+        let mask = if *prefix == 0 { 0 } else { u32::MAX << (32 - *prefix as u32) };
+        let start = u32::from(*ip) & mask;
+        let end = start | !mask;
+
+        for i in start..=end {
+          if *prefix < 31 && (i == start || i == end) {
+            continue;
+          }
+
+          out.push((IpAddr::V4(Ipv4Addr::from(i)), 0));
+        }
+      }
+
+      TargetSpec::Cidr(IpAddr::V6(ip), prefix) => {
+        let mask = if *prefix == 0 { 0 } else { u128::MAX << (128 - *prefix as u32) };
+        let start = u128::from(*ip) & mask;
+        let end = start | !mask;
+
+        for i in start..=end {
+          out.push((IpAddr::V6(Ipv6Addr::from(i)), 0));
+        }
+      }
+
+      TargetSpec::Octets(r) => {
+        for a in r[0].0..=r[0].1 {
+          for b in r[1].0..=r[1].1 {
+            for c in r[2].0..=r[2].1 {
+              for d in r[3].0..=r[3].1 {
+                out.push((IpAddr::V4(Ipv4Addr::new(a, b, c, d)), 0));
+              }
+            }
+          }
+        }
+      }
+
+      TargetSpec::Hostname(_) => {}
+    }
+
+    Ok(out)
+  }
+}
+
+/**Function parses an ipv4 or ipv6 address. Ipv6 addresses may be wrapped in brackets and may carry
+ * a zone index or interface name, for example [fe80::1%eth0].
+ * Params:
+ *  value: &str {The address to parse as a slice}
+ * Returns Option<(IpAddr, u32)> (The address and the ipv6 zone index)
+ */
+pub fn parse_ip(value: &str) -> Option<(IpAddr, u32)> {
+  let mut text = value.trim();
+
+  if text.starts_with('[') && text.ends_with(']') {
+    text = &text[1..text.len()-1];
+  }
+
+  let (addr_str, zone) = match text.split_once('%') {
+    Some((a, z)) => { (a, Some(z)) }
+    None =>         { (text, None) }
+  };
+
+  let address = match addr_str.parse::<IpAddr>() {
+    Ok(s) => { s },
+    Err(_) => { return None; }
+  };
+
+  let mut scope_id: u32 = 0;
+  if let Some(z) = zone {
+
+    // Zone indexes only make sense for ipv6 addresses.
+    if address.is_ipv4() || z.is_empty() {
+      return None;
+    }
+
+    // The zone can either be the numeric index or the name of the interface.
+    match z.parse::<u32>() {
+      Ok(s) => { scope_id = s; },
+      Err(_) => {
+        match pnet_datalink::interfaces().into_iter().find(|i| i.name == z) {
+          Some(iface) => { scope_id = iface.index; },
+          None =>        { return None; }
+        }
+      }
+    }
+  }
+
+  Some((address, scope_id))
+}
+
+/**Function splits a list of targets separated by commas or whitespace.
+ * Params:
+ *  text: &str {The list of targets}
+ * Returns Vec<String>
+ */
+pub fn split_targets(text: &str) -> Vec<String> {
+  text.split(|c: char| c == ',' || c.is_whitespace())
+    .filter(|i| !i.is_empty())
+    .map(|i| i.to_string())
+    .collect()
+}
+
+/**Function reads a list of targets from a file. Blank lines and anything after a '#' are ignored.
+ * Params:
+ *  path: &str {The path to the file}
+ * Returns Result<Vec<String>, String>
+ */
+pub fn read_target_file(path: &str) -> Result<Vec<String>, String> {
+  let mut out: Vec<String> = Default::default();
+
+  let content = match read_to_string(path) {
+    Ok(s) => { s },
+    Err(e) => { return Err(format!("{}", e)); }
+  };
+
+  for line in content.lines() {
+    let line = match line.split_once('#') {
+      Some((s, _)) => { s }
+      None =>         { line }
+    };
+
+    out.append(&mut split_targets(line));
+  }
+
+  Ok(out)
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  fn ip(text: &str) -> IpAddr {
+    text.parse::<IpAddr>().unwrap()
+  }
+
+  #[test]
+  fn parse_kinds() {
+    assert_eq!(TargetSpec::parse(" 10.0.0.1 "), Ok(TargetSpec::Address(ip("10.0.0.1"), 0)));
+    assert_eq!(TargetSpec::parse("[fe80::1%3]"), Ok(TargetSpec::Address(ip("fe80::1"), 3)));
+    assert_eq!(TargetSpec::parse("10.0.0.0/24"), Ok(TargetSpec::Cidr(ip("10.0.0.0"), 24)));
+    assert_eq!(TargetSpec::parse("2001:db8::/126"), Ok(TargetSpec::Cidr(ip("2001:db8::"), 126)));
+    assert_eq!(TargetSpec::parse("10.0.1-2.1-50"), Ok(TargetSpec::Octets([(10, 10), (0, 0), (1, 2), (1, 50)])));
+    assert_eq!(TargetSpec::parse("scanme.example.com"), Ok(TargetSpec::Hostname(String::from("scanme.example.com"))));
+  }
+
+  #[test]
+  fn parse_rejects_malformed() {
+    for i in ["", "  ", "10.0.0.0/33", "::/129", "10.0.0.0/x", "10.0.0.1%1/24", "300.0.0.0/8", "10.0.0.5-1", "10.0.0.1-256", "a b", "10.0.0.1%eth0", "\u{e9}"] {
+      assert!(TargetSpec::parse(i).is_err(), "{} should not parse", i);
+    }
+  }
+
+  #[test]
+  fn expand_skips_network_and_broadcast() {
+    let hosts = TargetSpec::parse("192.168.1.0/30").unwrap().expand().unwrap();
+    assert_eq!(hosts, vec![(ip("192.168.1.1"), 0), (ip("192.168.1.2"), 0)]);
+
+    assert_eq!(TargetSpec::parse("192.168.1.0/31").unwrap().expand().unwrap().len(), 2);
+    assert_eq!(TargetSpec::parse("192.168.1.7/32").unwrap().expand().unwrap(), vec![(ip("192.168.1.7"), 0)]);
+    assert_eq!(TargetSpec::parse("2001:db8::/126").unwrap().expand().unwrap().len(), 4);
+    assert_eq!(TargetSpec::parse("10.0.1-2.1-3").unwrap().expand().unwrap().len(), 6);
+    assert_eq!(TargetSpec::parse("example.com").unwrap().expand().unwrap().len(), 0);
+  }
+
+  #[test]
+  fn expand_rejects_huge_targets() {
+    assert!(TargetSpec::parse("10.0.0.0/8").unwrap().expand().is_err());
+    assert!(TargetSpec::parse("::/0").unwrap().expand().is_err());
+    assert_eq!(TargetSpec::parse("::/0").unwrap().size(), 1 << 127);
+  }
+
+  #[test]
+  fn contains_matches_exclusions() {
+    let block = TargetSpec::parse("10.0.0.0/24").unwrap();
+    assert!(block.contains(&ip("10.0.0.255")));
+    assert!(block.contains(&ip("10.0.1.0")) == false);
+    assert!(block.contains(&ip("::ffff:10.0.0.1")) == false);
+
+    let octets = TargetSpec::parse("10.0.0-1.5-6").unwrap();
+    assert!(octets.contains(&ip("10.0.1.6")));
+    assert!(octets.contains(&ip("10.0.2.6")) == false);
+
+    assert!(TargetSpec::parse("0.0.0.0/0").unwrap().contains(&ip("8.8.8.8")));
+    assert!(TargetSpec::parse("2001:db8::/32").unwrap().contains(&ip("2001:db8:ffff::1")));
+    assert!(TargetSpec::parse("10.0.0.1").unwrap().contains(&ip("10.0.0.2")) == false);
+    assert!(TargetSpec::Hostname(String::from("example.com")).contains(&ip("10.0.0.1")) == false);
+  }
+
+  #[test]
+  fn split_targets_lists() {
+    assert_eq!(split_targets("10.0.0.1, 10.0.0.2\t10.0.0.0/30\n"), vec!["10.0.0.1", "10.0.0.2", "10.0.0.0/30"]);
+    assert_eq!(split_targets(" ,, ").len(), 0);
+  }
+}
@@ -0,0 +1,405 @@
+use std::io::{Error, ErrorKind};
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr, TcpStream};
+use std::sync::Arc;
+use std::time::Duration;
+use chrono::{TimeZone, Utc};
+use rustls::{AlertDescription, ClientConfig, ClientConnection, DigitallySignedStruct, SignatureScheme};
+use rustls::client::danger::{HandshakeSignatureValid, ServerCertVerified, ServerCertVerifier};
+use rustls::crypto::{CryptoProvider, ring, verify_tls12_signature, verify_tls13_signature};
+use rustls::pki_types::{CertificateDer, ServerName, UnixTime};
+use serde::Serialize;
+use sha2::{Digest, Sha256};
+use x509_parser::prelude::{FromDer, GeneralName, SubjectPublicKeyInfo, X509Certificate};
+use x509_parser::public_key::PublicKey;
+use super::{hex_banner, SourceBinding};
+
+const ALPN_PROTOCOLS: [&[u8]; 2] = [b"h2", b"http/1.1"];     // Offered so web servers tell us which protocol they prefer.
+
+// One certificate of the chain a server sent.
+#[derive(Debug, Clone, Default, PartialEq, Serialize)]
+pub struct CertificateInfo {
+  pub subject: String,
+  pub sans: Vec<String>,          // The dns names and addresses the certificate is valid for.
+  pub issuer: String,
+  pub not_before: String,
+  pub not_after: String,
+  pub key_type: String,
+  pub key_bits: usize,
+  pub sha256: String,             // The fingerprint of the whole certificate.
+}
+
+// What the tls handshake with a port told us.
+#[derive(Debug, Clone, Default, PartialEq, Serialize)]
+pub struct TlsReport {
+  pub port: u16,
+  pub version: String,
+  pub cipher: String,
+
+  #[serde(skip_serializing_if = "Option::is_none")]
+  pub alpn: Option<String>,
+
+  #[serde(skip_serializing_if = "Option::is_none")]
+  pub starttls: Option<String>,             // The protocol that was upgraded to tls, for ports that do not start with it.
+
+  pub certificates: Vec<CertificateInfo>,   // The chain in the order the server sent it, starting with its own certificate.
+}
+
+// Accepts every certificate, because we want to record the chain of a server and not decide whether to trust it.
+#[derive(Debug)]
+pub struct AcceptAnyCertificate(Arc<CryptoProvider>);
+
+impl ServerCertVerifier for AcceptAnyCertificate {
+  fn verify_server_cert(&self, _end_entity: &CertificateDer<'_>, _intermediates: &[CertificateDer<'_>], _server_name: &ServerName<'_>, _ocsp_response: &[u8], _now: UnixTime) -> Result<ServerCertVerified, rustls::Error> {
+    Ok(ServerCertVerified::assertion())
+  }
+
+  fn verify_tls12_signature(&self, message: &[u8], cert: &CertificateDer<'_>, dss: &DigitallySignedStruct) -> Result<HandshakeSignatureValid, rustls::Error> {
+    verify_tls12_signature(message, cert, dss, &self.0.signature_verification_algorithms)
+  }
+
+  fn verify_tls13_signature(&self, message: &[u8], cert: &CertificateDer<'_>, dss: &DigitallySignedStruct) -> Result<HandshakeSignatureValid, rustls::Error> {
+    verify_tls13_signature(message, cert, dss, &self.0.signature_verification_algorithms)
+  }
+
+  fn supported_verify_schemes(&self) -> Vec<SignatureScheme> {
+    self.0.signature_verification_algorithms.supported_schemes()
+  }
+}
+
+/**Function builds the tls settings used to inspect every port.
+ * Params:
+ *  alpn: bool {Offers h2 and http/1.1 during the handshake}
+ * Returns Result<Arc<ClientConfig>, rustls::Error>
+ */
+pub fn inspection_config(alpn: bool) -> Result<Arc<ClientConfig>, rustls::Error> {
+  let provider = Arc::new(ring::default_provider());
+
+  let mut config = ClientConfig::builder_with_provider(provider.clone())
+  .with_protocol_versions(rustls::ALL_VERSIONS)?
+  .dangerous()
+  .with_custom_certificate_verifier(Arc::new(AcceptAnyCertificate(provider)))
+  .with_no_client_auth();
+
+  if alpn == true {
+    config.alpn_protocols = ALPN_PROTOCOLS.iter().map(|i| i.to_vec()).collect();
+  }
+
+  Ok(Arc::new(config))
+}
+
+/**Function picks the name sent in the handshake. Hostnames typed by the user are sent so virtual hosts answer
+ * with the right certificate, every other target is named by its address.
+ * Params:
+ *  name:    &str        {The target as it was typed by the user}
+ *  address: &SocketAddr {The address that was scanned}
+ * Returns ServerName<'static>
+ */
+pub fn server_name(name: &str, address: &SocketAddr) -> ServerName<'static> {
+  match ServerName::try_from(name.to_string()) {
+    Ok(s @ ServerName::DnsName(_)) => { s },
+    _ =>                              { ServerName::IpAddress(address.ip().into()) }
+  }
+}
+
+/**Function runs a tls handshake with a port and records what was negotiated and the certificate chain.
+ * Params:
+ *  address: &SocketAddr        {The address and port of the service}
+ *  name:    &str               {The target as it was typed by the user}
+ *  config:  &Arc<ClientConfig> {The tls settings}
+ *  timeout: Duration           {How long to wait for the connection and for each message}
+ *  binding: &SourceBinding     {The interface and address picked by the user}
+ * Returns Result<TlsReport, Error>
+ */
+pub fn tls_handshake(address: &SocketAddr, name: &str, config: &Arc<ClientConfig>, timeout: Duration, binding: &SourceBinding) -> Result<TlsReport, Error> {
+  let mut stream = binding.connect_timeout(address, timeout)?;
+  stream.set_read_timeout(Some(timeout.max(Duration::from_millis(1))))?;
+  stream.set_write_timeout(Some(timeout.max(Duration::from_millis(1))))?;
+
+  handshake_stream(&mut stream, address, name, config)
+}
+
+/**Function runs a tls handshake over a connection that is already open, such as one that was just upgraded with starttls.
+ * Params:
+ *  stream:  &mut TcpStream     {The connection to the service}
+ *  address: &SocketAddr        {The address and port of the service}
+ *  name:    &str               {The target as it was typed by the user}
+ *  config:  &Arc<ClientConfig> {The tls settings}
+ * Returns Result<TlsReport, Error>
+ */
+pub fn handshake_stream(stream: &mut TcpStream, address: &SocketAddr, name: &str, config: &Arc<ClientConfig>) -> Result<TlsReport, Error> {
+  let mut connection = match ClientConnection::new(config.clone(), server_name(name, address)) {
+    Ok(s) => { s },
+    Err(e) => { return Err(Error::new(ErrorKind::InvalidInput, e)); }
+  };
+
+  while connection.is_handshaking() == true {
+    connection.complete_io(stream)?;
+  }
+
+  let mut out = TlsReport { port: address.port(), ..Default::default() };
+
+  if let Some(version) = connection.protocol_version() {
+    out.version = format!("{:?}", version).replace('_', ".");
+  }
+
+  if let Some(suite) = connection.negotiated_cipher_suite() {
+    out.cipher = format!("{:?}", suite.suite());
+  }
+
+  out.alpn = connection.alpn_protocol().map(|i| String::from_utf8_lossy(i).to_string());
+  out.certificates = connection.peer_certificates().unwrap_or_default().iter().map(|i| certificate_info(i.as_ref())).collect();
+
+  Ok(out)
+}
+
+/**Function inspects the tls setup of a port. Servers that refuse every protocol we offer with alpn are asked again without it.
+ * Params:
+ *  address: &SocketAddr                              {The address and port of the service}
+ *  name:    &str                                     {The target as it was typed by the user}
+ *  configs: &(Arc<ClientConfig>, Arc<ClientConfig>)  {The tls settings with and without alpn}
+ *  timeout: Duration                                 {How long to wait for the connection and for each message}
+ *  binding: &SourceBinding                           {The interface and address picked by the user}
+ * Returns Result<TlsReport, Error>
+ */
+pub fn inspect_tls(address: &SocketAddr, name: &str, configs: &(Arc<ClientConfig>, Arc<ClientConfig>), timeout: Duration, binding: &SourceBinding) -> Result<TlsReport, Error> {
+  match tls_handshake(address, name, &configs.0, timeout, binding) {
+    Err(e) if is_alert(&e, AlertDescription::NoApplicationProtocol) == true => {
+      tls_handshake(address, name, &configs.1, timeout, binding)
+    },
+
+    other => { other }
+  }
+}
+
+// Returns true when the handshake failed because the server sent an alert.
+fn is_alert(e: &Error, alert: AlertDescription) -> bool {
+  match e.get_ref().and_then(|i| i.downcast_ref::<rustls::Error>()) {
+    Some(rustls::Error::AlertReceived(s)) => { *s == alert },
+    _ =>                                     { false }
+  }
+}
+
+/**Function reads the fields of a certificate that matter to us.
+ * Params:
+ *  der: &[u8] {The certificate as it was sent}
+ * Returns CertificateInfo
+ */
+pub fn certificate_info(der: &[u8]) -> CertificateInfo {
+  let mut out = CertificateInfo { sha256: hex_banner(&Sha256::digest(der)), ..Default::default() };
+
+  let cert = match X509Certificate::from_der(der) {
+    Ok((_, s)) => { s },
+    Err(_) =>     { return out; }
+  };
+
+  out.subject = cert.subject().to_string();
+  out.issuer = cert.issuer().to_string();
+  out.not_before = format_time(cert.validity().not_before.timestamp());
+  out.not_after = format_time(cert.validity().not_after.timestamp());
+  (out.key_type, out.key_bits) = key_details(cert.public_key());
+
+  if let Ok(Some(extension)) = cert.subject_alternative_name() {
+    for i in extension.value.general_names.iter() {
+      match i {
+        GeneralName::DNSName(s) =>   { out.sans.push(s.to_string()); },
+        GeneralName::IPAddress(s) => {
+          if let Some(ip) = address_from_bytes(s) {
+            out.sans.push(ip.to_string());
+          }
+        },
+
+        _ => {}
+      }
+    }
+  }
+
+  out
+}
+
+/**Function names the type of a public key and works out its size.
+ * Params:
+ *  key: &SubjectPublicKeyInfo {The public key of the certificate}
+ * Returns (String, usize)
+ */
+pub fn key_details(key: &SubjectPublicKeyInfo) -> (String, usize) {
+  let algorithm = key.algorithm.algorithm.to_id_string();
+
+  match (key.parsed(), algorithm.as_str()) {
+    (Ok(PublicKey::RSA(s)), _) => { (String::from("RSA"), s.key_size()) },
+    (Ok(PublicKey::EC(s)), _) => {
+      let curve = key.algorithm.parameters.as_ref().and_then(|i| i.as_oid().ok()).map(|i| i.to_id_string());
+
+      let name = match curve.as_deref() {
+        Some("1.2.840.10045.3.1.7") => { String::from("EC P-256") },
+        Some("1.3.132.0.34") =>        { String::from("EC P-384") },
+        Some("1.3.132.0.35") =>        { String::from("EC P-521") },
+        _ =>                           { String::from("EC") }
+      };
+
+      (name, s.key_size())
+    },
+
+    (Ok(PublicKey::DSA(s)), _) => { (String::from("DSA"), s.len() * 8) },
+    (_, "1.3.101.112") =>         { (String::from("Ed25519"), 256) },
+    (_, "1.3.101.113") =>         { (String::from("Ed448"), 456) },
+    (_, other) =>                 { (other.to_string(), key.subject_public_key.data.len() * 8) }
+  }
+}
+
+// Returns a unix timestamp as an rfc 3339 date.
+fn format_time(timestamp: i64) -> String {
+  match Utc.timestamp_opt(timestamp, 0).single() {
+    Some(s) => { s.to_rfc3339() },
+    None =>    { timestamp.to_string() }
+  }
+}
+
+// Returns the address stored in a subject alternative name.
+fn address_from_bytes(data: &[u8]) -> Option<IpAddr> {
+  match data.len() {
+    4 => {
+      let octets: [u8; 4] = data.try_into().ok()?;
+      Some(IpAddr::V4(Ipv4Addr::from(octets)))
+    },
+
+    16 => {
+      let octets: [u8; 16] = data.try_into().ok()?;
+      Some(IpAddr::V6(Ipv6Addr::from(octets)))
+    },
+
+    _ => { None }
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  // A self-signed P-256 certificate for www.example.com, valid from 2024-01-01 to 2034-01-01, with dns and ip sans.
+  const EC_CERT: &[u8] = &[
+    0x30, 0x82, 0x01, 0xf9, 0x30, 0x82, 0x01, 0xa0, 0xa0, 0x03, 0x02, 0x01, 0x02, 0x02, 0x14, 0x5c,
+    0xa5, 0xc3, 0x0f, 0x75, 0xae, 0x2d, 0x03, 0xf0, 0xef, 0x99, 0x24, 0xc5, 0x5c, 0x5e, 0x21, 0xad,
+    0x6b, 0x7b, 0x9e, 0x30, 0x0a, 0x06, 0x08, 0x2a, 0x86, 0x48, 0xce, 0x3d, 0x04, 0x03, 0x02, 0x30,
+    0x30, 0x31, 0x18, 0x30, 0x16, 0x06, 0x03, 0x55, 0x04, 0x03, 0x0c, 0x0f, 0x77, 0x77, 0x77, 0x2e,
+    0x65, 0x78, 0x61, 0x6d, 0x70, 0x6c, 0x65, 0x2e, 0x63, 0x6f, 0x6d, 0x31, 0x14, 0x30, 0x12, 0x06,
+    0x03, 0x55, 0x04, 0x0a, 0x0c, 0x0b, 0x50, 0x6f, 0x74, 0x61, 0x74, 0x6f, 0x20, 0x54, 0x65, 0x73,
+    0x74, 0x30, 0x1e, 0x17, 0x0d, 0x32, 0x34, 0x30, 0x31, 0x30, 0x31, 0x30, 0x30, 0x30, 0x30, 0x30,
+    0x30, 0x5a, 0x17, 0x0d, 0x33, 0x34, 0x30, 0x31, 0x30, 0x31, 0x30, 0x30, 0x30, 0x30, 0x30, 0x30,
+    0x5a, 0x30, 0x30, 0x31, 0x18, 0x30, 0x16, 0x06, 0x03, 0x55, 0x04, 0x03, 0x0c, 0x0f, 0x77, 0x77,
+    0x77, 0x2e, 0x65, 0x78, 0x61, 0x6d, 0x70, 0x6c, 0x65, 0x2e, 0x63, 0x6f, 0x6d, 0x31, 0x14, 0x30,
+    0x12, 0x06, 0x03, 0x55, 0x04, 0x0a, 0x0c, 0x0b, 0x50, 0x6f, 0x74, 0x61, 0x74, 0x6f, 0x20, 0x54,
+    0x65, 0x73, 0x74, 0x30, 0x59, 0x30, 0x13, 0x06, 0x07, 0x2a, 0x86, 0x48, 0xce, 0x3d, 0x02, 0x01,
+    0x06, 0x08, 0x2a, 0x86, 0x48, 0xce, 0x3d, 0x03, 0x01, 0x07, 0x03, 0x42, 0x00, 0x04, 0xd7, 0x1c,
+    0x0f, 0x65, 0x18, 0x29, 0x46, 0x2d, 0xff, 0xdc, 0xc6, 0x37, 0x3b, 0x0a, 0xec, 0xa4, 0x68, 0xeb,
+    0x85, 0x03, 0x59, 0x85, 0x92, 0x82, 0xab, 0x37, 0xcf, 0x48, 0xab, 0x00, 0x5a, 0x99, 0xb0, 0x88,
+    0x21, 0x33, 0x24, 0x79, 0x45, 0x04, 0x8b, 0x7c, 0x9c, 0x5f, 0xb1, 0x0c, 0x78, 0x28, 0x25, 0x11,
+    0x46, 0xcc, 0x2d, 0x92, 0x65, 0x7a, 0x38, 0xa2, 0x8f, 0xa3, 0x71, 0x8d, 0xa4, 0xa3, 0xa3, 0x81,
+    0x97, 0x30, 0x81, 0x94, 0x30, 0x1d, 0x06, 0x03, 0x55, 0x1d, 0x0e, 0x04, 0x16, 0x04, 0x14, 0x8b,
+    0x02, 0x18, 0x31, 0x61, 0x25, 0x49, 0x7a, 0x1c, 0x80, 0xd2, 0x13, 0x9c, 0x73, 0x76, 0x8c, 0x60,
+    0xd6, 0x9f, 0xfe, 0x30, 0x1f, 0x06, 0x03, 0x55, 0x1d, 0x23, 0x04, 0x18, 0x30, 0x16, 0x80, 0x14,
+    0x8b, 0x02, 0x18, 0x31, 0x61, 0x25, 0x49, 0x7a, 0x1c, 0x80, 0xd2, 0x13, 0x9c, 0x73, 0x76, 0x8c,
+    0x60, 0xd6, 0x9f, 0xfe, 0x30, 0x0f, 0x06, 0x03, 0x55, 0x1d, 0x13, 0x01, 0x01, 0xff, 0x04, 0x05,
+    0x30, 0x03, 0x01, 0x01, 0xff, 0x30, 0x41, 0x06, 0x03, 0x55, 0x1d, 0x11, 0x04, 0x3a, 0x30, 0x38,
+    0x82, 0x0f, 0x77, 0x77, 0x77, 0x2e, 0x65, 0x78, 0x61, 0x6d, 0x70, 0x6c, 0x65, 0x2e, 0x63, 0x6f,
+    0x6d, 0x82, 0x0d, 0x2a, 0x2e, 0x65, 0x78, 0x61, 0x6d, 0x70, 0x6c, 0x65, 0x2e, 0x6f, 0x72, 0x67,
+    0x87, 0x04, 0xc0, 0x00, 0x02, 0x0a, 0x87, 0x10, 0x20, 0x01, 0x0d, 0xb8, 0x00, 0x00, 0x00, 0x00,
+    0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x01, 0x30, 0x0a, 0x06, 0x08, 0x2a, 0x86, 0x48, 0xce,
+    0x3d, 0x04, 0x03, 0x02, 0x03, 0x47, 0x00, 0x30, 0x44, 0x02, 0x20, 0x72, 0x8b, 0xfb, 0x07, 0x3c,
+    0x53, 0x08, 0x17, 0xff, 0xa8, 0xe8, 0x39, 0x52, 0x51, 0x25, 0x8a, 0x66, 0xee, 0x52, 0xd5, 0x0f,
+    0x1c, 0xc6, 0xdc, 0x42, 0x5a, 0x5f, 0x6e, 0xff, 0x2b, 0xac, 0x55, 0x02, 0x20, 0x37, 0x88, 0x9a,
+    0x51, 0xe3, 0x98, 0x38, 0x50, 0x31, 0x32, 0x3e, 0xbb, 0xb0, 0xc7, 0xdc, 0x35, 0x9a, 0xda, 0x88,
+    0x5c, 0x75, 0xcf, 0x02, 0xfe, 0xf4, 0xdc, 0xf3, 0x8f, 0x91, 0x58, 0x79, 0xea,
+  ];
+
+  // A self-signed 1024 bit rsa certificate for rsa.example.com without sans.
+  const RSA_CERT: &[u8] = &[
+    0x30, 0x82, 0x02, 0x10, 0x30, 0x82, 0x01, 0x79, 0xa0, 0x03, 0x02, 0x01, 0x02, 0x02, 0x14, 0x76,
+    0xe3, 0x4f, 0x51, 0x48, 0xe4, 0xa5, 0x50, 0x9b, 0x82, 0x2b, 0xf9, 0xc5, 0x27, 0x0e, 0x75, 0x45,
+    0xfb, 0x0a, 0x08, 0x30, 0x0d, 0x06, 0x09, 0x2a, 0x86, 0x48, 0x86, 0xf7, 0x0d, 0x01, 0x01, 0x0b,
+    0x05, 0x00, 0x30, 0x1a, 0x31, 0x18, 0x30, 0x16, 0x06, 0x03, 0x55, 0x04, 0x03, 0x0c, 0x0f, 0x72,
+    0x73, 0x61, 0x2e, 0x65, 0x78, 0x61, 0x6d, 0x70, 0x6c, 0x65, 0x2e, 0x63, 0x6f, 0x6d, 0x30, 0x1e,
+    0x17, 0x0d, 0x32, 0x30, 0x30, 0x31, 0x30, 0x32, 0x30, 0x33, 0x30, 0x34, 0x30, 0x35, 0x5a, 0x17,
+    0x0d, 0x33, 0x30, 0x30, 0x31, 0x30, 0x32, 0x30, 0x33, 0x30, 0x34, 0x30, 0x35, 0x5a, 0x30, 0x1a,
+    0x31, 0x18, 0x30, 0x16, 0x06, 0x03, 0x55, 0x04, 0x03, 0x0c, 0x0f, 0x72, 0x73, 0x61, 0x2e, 0x65,
+    0x78, 0x61, 0x6d, 0x70, 0x6c, 0x65, 0x2e, 0x63, 0x6f, 0x6d, 0x30, 0x81, 0x9f, 0x30, 0x0d, 0x06,
+    0x09, 0x2a, 0x86, 0x48, 0x86, 0xf7, 0x0d, 0x01, 0x01, 0x01, 0x05, 0x00, 0x03, 0x81, 0x8d, 0x00,
+    0x30, 0x81, 0x89, 0x02, 0x81, 0x81, 0x00, 0xab, 0x1b, 0xea, 0xff, 0xc8, 0xca, 0x2e, 0x57, 0xdf,
+    0xf1, 0xfa, 0xed, 0x71, 0xb6, 0xc8, 0x4b, 0x50, 0x46, 0xf9, 0x33, 0x9a, 0x32, 0x1a, 0x36, 0x74,
+    0xf4, 0xae, 0xa1, 0x35, 0x4b, 0x67, 0x4a, 0x49, 0x7c, 0xae, 0x50, 0x4f, 0x44, 0xe7, 0x2d, 0x87,
+    0xec, 0xff, 0xe9, 0xda, 0x70, 0x1b, 0xb1, 0x6d, 0xff, 0x23, 0x28, 0x22, 0x10, 0x02, 0x84, 0xa4,
+    0x2c, 0x23, 0xeb, 0x94, 0xa0, 0xca, 0xa9, 0xf7, 0x81, 0xd1, 0x87, 0xb7, 0xd8, 0x2d, 0x92, 0xc5,
+    0x03, 0x22, 0x28, 0xc8, 0x10, 0x65, 0xa4, 0x79, 0x8b, 0xed, 0x52, 0xd6, 0x85, 0xb1, 0xbc, 0x58,
+    0x25, 0xc1, 0x58, 0x7d, 0x11, 0xc5, 0x64, 0x73, 0x71, 0x43, 0xa9, 0xbd, 0x3c, 0x6e, 0x31, 0x9c,
+    0xd1, 0xfe, 0x64, 0xb5, 0x96, 0x1a, 0xa7, 0xe8, 0x23, 0x2f, 0x1c, 0xb9, 0x09, 0x2d, 0xfc, 0xbb,
+    0xa2, 0x40, 0x54, 0x98, 0xe5, 0x49, 0x81, 0x02, 0x03, 0x01, 0x00, 0x01, 0xa3, 0x53, 0x30, 0x51,
+    0x30, 0x1d, 0x06, 0x03, 0x55, 0x1d, 0x0e, 0x04, 0x16, 0x04, 0x14, 0x86, 0xd7, 0x3b, 0x0e, 0x57,
+    0x99, 0xd5, 0xe6, 0xc8, 0x2d, 0x12, 0xa7, 0x44, 0x72, 0x65, 0x67, 0xcd, 0x27, 0xe3, 0x3a, 0x30,
+    0x1f, 0x06, 0x03, 0x55, 0x1d, 0x23, 0x04, 0x18, 0x30, 0x16, 0x80, 0x14, 0x86, 0xd7, 0x3b, 0x0e,
+    0x57, 0x99, 0xd5, 0xe6, 0xc8, 0x2d, 0x12, 0xa7, 0x44, 0x72, 0x65, 0x67, 0xcd, 0x27, 0xe3, 0x3a,
+    0x30, 0x0f, 0x06, 0x03, 0x55, 0x1d, 0x13, 0x01, 0x01, 0xff, 0x04, 0x05, 0x30, 0x03, 0x01, 0x01,
+    0xff, 0x30, 0x0d, 0x06, 0x09, 0x2a, 0x86, 0x48, 0x86, 0xf7, 0x0d, 0x01, 0x01, 0x0b, 0x05, 0x00,
+    0x03, 0x81, 0x81, 0x00, 0x9a, 0x76, 0xaa, 0xd1, 0x66, 0x83, 0x4b, 0xe2, 0xf2, 0xb6, 0x65, 0x7d,
+    0x9c, 0x01, 0x0e, 0x5c, 0x42, 0x91, 0xdc, 0x29, 0xdd, 0x86, 0x4d, 0xd9, 0xa4, 0x21, 0x66, 0xf7,
+    0x45, 0x0a, 0x78, 0xe4, 0xb3, 0x7c, 0x97, 0x38, 0x83, 0x2d, 0x45, 0x3f, 0xa1, 0xb7, 0x90, 0x81,
+    0xe5, 0xd2, 0x1a, 0x28, 0x52, 0xe7, 0xe7, 0x63, 0xba, 0x64, 0x1c, 0x0e, 0xdb, 0x55, 0x2d, 0x47,
+    0x23, 0xfe, 0x08, 0xa4, 0x3f, 0x76, 0xbb, 0xd6, 0x77, 0x72, 0x2a, 0x7c, 0xc5, 0x71, 0xb9, 0xc7,
+    0x53, 0xee, 0xee, 0x5c, 0x8d, 0x15, 0xe9, 0x76, 0x0d, 0x86, 0xf9, 0x6f, 0x5b, 0xf7, 0x75, 0x95,
+    0x16, 0x41, 0xf6, 0x5f, 0xa5, 0xe6, 0x6a, 0x6c, 0x72, 0x9f, 0xf8, 0x83, 0xf9, 0xfd, 0xd0, 0x8f,
+    0x78, 0xbb, 0x1d, 0xb3, 0xe2, 0xfa, 0x2d, 0x28, 0xf4, 0xf9, 0xa3, 0x27, 0x27, 0x1b, 0x72, 0x00,
+    0xa4, 0x04, 0x6d, 0x5a,
+  ];
+
+  #[test]
+  fn certificate_info_ec() {
+    let info = certificate_info(EC_CERT);
+
+    assert_eq!(info.subject, "CN=www.example.com, O=Potato Test");
+    assert_eq!(info.issuer, info.subject);
+    assert_eq!(info.sans, vec!["www.example.com", "*.example.org", "192.0.2.10", "2001:db8::1"]);
+    assert_eq!(info.not_before, "2024-01-01T00:00:00+00:00");
+    assert_eq!(info.not_after, "2034-01-01T00:00:00+00:00");
+    assert_eq!((info.key_type.as_str(), info.key_bits), ("EC P-256", 256));
+    assert_eq!(info.sha256, "7c0989f18bb4edb036b6d31523141545ffae7b48889388afd7a873a0baa294ae");
+  }
+
+  #[test]
+  fn certificate_info_rsa() {
+    let info = certificate_info(RSA_CERT);
+
+    assert_eq!(info.subject, "CN=rsa.example.com");
+    assert!(info.sans.is_empty());
+    assert_eq!(info.not_before, "2020-01-02T03:04:05+00:00");
+    assert_eq!(info.not_after, "2030-01-02T03:04:05+00:00");
+    assert_eq!((info.key_type.as_str(), info.key_bits), ("RSA", 1024));
+    assert_eq!(info.sha256, "0c6afdeb300dafc7e80fdea921b1a8a0cd07ca33d6071f43b00664d6956b8233");
+  }
+
+  #[test]
+  fn certificate_info_bad_der() {
+    let info = certificate_info(b"not a certificate");
+
+    assert_eq!(info.sha256, hex_banner(&Sha256::digest(b"not a certificate")));
+    assert_eq!(info, CertificateInfo { sha256: info.sha256.clone(), ..Default::default() });
+
+    let info = certificate_info(&EC_CERT[..100]);
+    assert_eq!(info.sha256.len(), 64);
+    assert!(info.subject.is_empty() && info.sans.is_empty() && info.key_type.is_empty());
+  }
+
+  #[test]
+  fn address_from_bytes_lengths() {
+    assert_eq!(address_from_bytes(&[192, 0, 2, 10]), Some(IpAddr::V4(Ipv4Addr::new(192, 0, 2, 10))));
+    assert_eq!(address_from_bytes(&Ipv6Addr::LOCALHOST.octets()), Some(IpAddr::V6(Ipv6Addr::LOCALHOST)));
+    assert_eq!(address_from_bytes(&[192, 0, 2]), None);
+    assert_eq!(address_from_bytes(&[]), None);
+  }
+
+  #[test]
+  fn server_name_prefers_hostnames() {
+    let address: SocketAddr = "192.0.2.10:443".parse().unwrap();
+
+    assert_eq!(server_name("www.example.com", &address), ServerName::try_from("www.example.com").unwrap());
+    assert_eq!(server_name("192.0.2.10", &address), ServerName::IpAddress(address.ip().into()));
+    assert_eq!(server_name("10.0.0.0/24", &address), ServerName::IpAddress(address.ip().into()));
+  }
+}
@@ -0,0 +1,892 @@
+use std::collections::HashSet;
+use std::io::{Error, ErrorKind, Read, Write};
+use std::net::{SocketAddr, TcpStream};
+use std::time::Duration;
+use chrono::{DateTime, Utc};
+use rustls::pki_types::ServerName;
+use serde::{Serialize, Serializer};
+use super::{certificate_info, CertificateInfo, SourceBinding, TlsReport};
+
+const MAX_RECORD_LEN: usize = 18432;      // The largest tls record a server may send, with room for compression and padding.
+
+// Every protocol version we check, newest first so servers that do not speak tls are found with the first handshake.
+const PROTOCOLS: [(u16, &str); 5] = [
+  (0x0304, "TLSv1.3"),
+  (0x0303, "TLSv1.2"),
+  (0x0302, "TLSv1.1"),
+  (0x0301, "TLSv1.0"),
+  (0x0300, "SSLv3"),
+];
+
+/**Function returns the name of a cipher suite.
+ * Params:
+ *  id: u16 {The id of the cipher suite}
+ * Returns Option<&'static str>
+ */
+pub fn cipher_name(id: u16) -> Option<&'static str> {
+  match id {
+    0x0001 => { Some("TLS_RSA_WITH_NULL_MD5") }
+    0x0002 => { Some("TLS_RSA_WITH_NULL_SHA") }
+    0x0003 => { Some("TLS_RSA_EXPORT_WITH_RC4_40_MD5") }
+    0x0004 => { Some("TLS_RSA_WITH_RC4_128_MD5") }
+    0x0005 => { Some("TLS_RSA_WITH_RC4_128_SHA") }
+    0x0006 => { Some("TLS_RSA_EXPORT_WITH_RC2_CBC_40_MD5") }
+    0x0007 => { Some("TLS_RSA_WITH_IDEA_CBC_SHA") }
+    0x0008 => { Some("TLS_RSA_EXPORT_WITH_DES40_CBC_SHA") }
+    0x0009 => { Some("TLS_RSA_WITH_DES_CBC_SHA") }
+    0x000a => { Some("TLS_RSA_WITH_3DES_EDE_CBC_SHA") }
+    0x0011 => { Some("TLS_DHE_DSS_EXPORT_WITH_DES40_CBC_SHA") }
+    0x0012 => { Some("TLS_DHE_DSS_WITH_DES_CBC_SHA") }
+    0x0013 => { Some("TLS_DHE_DSS_WITH_3DES_EDE_CBC_SHA") }
+    0x0014 => { Some("TLS_DHE_RSA_EXPORT_WITH_DES40_CBC_SHA") }
+    0x0015 => { Some("TLS_DHE_RSA_WITH_DES_CBC_SHA") }
+    0x0016 => { Some("TLS_DHE_RSA_WITH_3DES_EDE_CBC_SHA") }
+    0x0017 => { Some("TLS_DH_anon_EXPORT_WITH_RC4_40_MD5") }
+    0x0018 => { Some("TLS_DH_anon_WITH_RC4_128_MD5") }
+    0x0019 => { Some("TLS_DH_anon_EXPORT_WITH_DES40_CBC_SHA") }
+    0x001a => { Some("TLS_DH_anon_WITH_DES_CBC_SHA") }
+    0x001b => { Some("TLS_DH_anon_WITH_3DES_EDE_CBC_SHA") }
+    0x002f => { Some("TLS_RSA_WITH_AES_128_CBC_SHA") }
+    0x0032 => { Some("TLS_DHE_DSS_WITH_AES_128_CBC_SHA") }
+    0x0033 => { Some("TLS_DHE_RSA_WITH_AES_128_CBC_SHA") }
+    0x0034 => { Some("TLS_DH_anon_WITH_AES_128_CBC_SHA") }
+    0x0035 => { Some("TLS_RSA_WITH_AES_256_CBC_SHA") }
+    0x0038 => { Some("TLS_DHE_DSS_WITH_AES_256_CBC_SHA") }
+    0x0039 => { Some("TLS_DHE_RSA_WITH_AES_256_CBC_SHA") }
+    0x003a => { Some("TLS_DH_anon_WITH_AES_256_CBC_SHA") }
+    0x003b => { Some("TLS_RSA_WITH_NULL_SHA256") }
+    0x003c => { Some("TLS_RSA_WITH_AES_128_CBC_SHA256") }
+    0x003d => { Some("TLS_RSA_WITH_AES_256_CBC_SHA256") }
+    0x0040 => { Some("TLS_DHE_DSS_WITH_AES_128_CBC_SHA256") }
+    0x0041 => { Some("TLS_RSA_WITH_CAMELLIA_128_CBC_SHA") }
+    0x0045 => { Some("TLS_DHE_RSA_WITH_CAMELLIA_128_CBC_SHA") }
+    0x0067 => { Some("TLS_DHE_RSA_WITH_AES_128_CBC_SHA256") }
+    0x006a => { Some("TLS_DHE_DSS_WITH_AES_256_CBC_SHA256") }
+    0x006b => { Some("TLS_DHE_RSA_WITH_AES_256_CBC_SHA256") }
+    0x006c => { Some("TLS_DH_anon_WITH_AES_128_CBC_SHA256") }
+    0x006d => { Some("TLS_DH_anon_WITH_AES_256_CBC_SHA256") }
+    0x0084 => { Some("TLS_RSA_WITH_CAMELLIA_256_CBC_SHA") }
+    0x0088 => { Some("TLS_DHE_RSA_WITH_CAMELLIA_256_CBC_SHA") }
+    0x0096 => { Some("TLS_RSA_WITH_SEED_CBC_SHA") }
+    0x009c => { Some("TLS_RSA_WITH_AES_128_GCM_SHA256") }
+    0x009d => { Some("TLS_RSA_WITH_AES_256_GCM_SHA384") }
+    0x009e => { Some("TLS_DHE_RSA_WITH_AES_128_GCM_SHA256") }
+    0x009f => { Some("TLS_DHE_RSA_WITH_AES_256_GCM_SHA384") }
+    0x00a2 => { Some("TLS_DHE_DSS_WITH_AES_128_GCM_SHA256") }
+    0x00a3 => { Some("TLS_DHE_DSS_WITH_AES_256_GCM_SHA384") }
+    0x00a6 => { Some("TLS_DH_anon_WITH_AES_128_GCM_SHA256") }
+    0x00a7 => { Some("TLS_DH_anon_WITH_AES_256_GCM_SHA384") }
+    0x1301 => { Some("TLS_AES_128_GCM_SHA256") }
+    0x1302 => { Some("TLS_AES_256_GCM_SHA384") }
+    0x1303 => { Some("TLS_CHACHA20_POLY1305_SHA256") }
+    0x1304 => { Some("TLS_AES_128_CCM_SHA256") }
+    0x1305 => { Some("TLS_AES_128_CCM_8_SHA256") }
+    0xc001 => { Some("TLS_ECDH_ECDSA_WITH_NULL_SHA") }
+    0xc002 => { Some("TLS_ECDH_ECDSA_WITH_RC4_128_SHA") }
+    0xc003 => { Some("TLS_ECDH_ECDSA_WITH_3DES_EDE_CBC_SHA") }
+    0xc004 => { Some("TLS_ECDH_ECDSA_WITH_AES_128_CBC_SHA") }
+    0xc005 => { Some("TLS_ECDH_ECDSA_WITH_AES_256_CBC_SHA") }
+    0xc006 => { Some("TLS_ECDHE_ECDSA_WITH_NULL_SHA") }
+    0xc007 => { Some("TLS_ECDHE_ECDSA_WITH_RC4_128_SHA") }
+    0xc008 => { Some("TLS_ECDHE_ECDSA_WITH_3DES_EDE_CBC_SHA") }
+    0xc009 => { Some("TLS_ECDHE_ECDSA_WITH_AES_128_CBC_SHA") }
+    0xc00a => { Some("TLS_ECDHE_ECDSA_WITH_AES_256_CBC_SHA") }
+    0xc00b => { Some("TLS_ECDH_RSA_WITH_NULL_SHA") }
+    0xc00c => { Some("TLS_ECDH_RSA_WITH_RC4_128_SHA") }
+    0xc00d => { Some("TLS_ECDH_RSA_WITH_3DES_EDE_CBC_SHA") }
+    0xc00e => { Some("TLS_ECDH_RSA_WITH_AES_128_CBC_SHA") }
+    0xc00f => { Some("TLS_ECDH_RSA_WITH_AES_256_CBC_SHA") }
+    0xc010 => { Some("TLS_ECDHE_RSA_WITH_NULL_SHA") }
+    0xc011 => { Some("TLS_ECDHE_RSA_WITH_RC4_128_SHA") }
+    0xc012 => { Some("TLS_ECDHE_RSA_WITH_3DES_EDE_CBC_SHA") }
+    0xc013 => { Some("TLS_ECDHE_RSA_WITH_AES_128_CBC_SHA") }
+    0xc014 => { Some("TLS_ECDHE_RSA_WITH_AES_256_CBC_SHA") }
+    0xc015 => { Some("TLS_ECDH_anon_WITH_NULL_SHA") }
+    0xc016 => { Some("TLS_ECDH_anon_WITH_RC4_128_SHA") }
+    0xc017 => { Some("TLS_ECDH_anon_WITH_3DES_EDE_CBC_SHA") }
+    0xc018 => { Some("TLS_ECDH_anon_WITH_AES_128_CBC_SHA") }
+    0xc019 => { Some("TLS_ECDH_anon_WITH_AES_256_CBC_SHA") }
+    0xc023 => { Some("TLS_ECDHE_ECDSA_WITH_AES_128_CBC_SHA256") }
+    0xc024 => { Some("TLS_ECDHE_ECDSA_WITH_AES_256_CBC_SHA384") }
+    0xc027 => { Some("TLS_ECDHE_RSA_WITH_AES_128_CBC_SHA256") }
+    0xc028 => { Some("TLS_ECDHE_RSA_WITH_AES_256_CBC_SHA384") }
+    0xc02b => { Some("TLS_ECDHE_ECDSA_WITH_AES_128_GCM_SHA256") }
+    0xc02c => { Some("TLS_ECDHE_ECDSA_WITH_AES_256_GCM_SHA384") }
+    0xc02f => { Some("TLS_ECDHE_RSA_WITH_AES_128_GCM_SHA256") }
+    0xc030 => { Some("TLS_ECDHE_RSA_WITH_AES_256_GCM_SHA384") }
+    0xc09c => { Some("TLS_RSA_WITH_AES_128_CCM") }
+    0xc09d => { Some("TLS_RSA_WITH_AES_256_CCM") }
+    0xc0ac => { Some("TLS_ECDHE_ECDSA_WITH_AES_128_CCM") }
+    0xc0ad => { Some("TLS_ECDHE_ECDSA_WITH_AES_256_CCM") }
+    0xcca8 => { Some("TLS_ECDHE_RSA_WITH_CHACHA20_POLY1305_SHA256") }
+    0xcca9 => { Some("TLS_ECDHE_ECDSA_WITH_CHACHA20_POLY1305_SHA256") }
+    0xccaa => { Some("TLS_DHE_RSA_WITH_CHACHA20_POLY1305_SHA256") }
+    _ =>      { None }
+  }
+}
+
+// Returns the cipher suites offered for a protocol version. Tls 1.3 has its own suites that no older version uses.
+pub fn protocol_ciphers(version: u16) -> Vec<u16> {
+  let tls13 = (0x1301..=0x1305).collect::<Vec<u16>>();
+
+  match version {
+    0x0304 => { tls13 },
+    _ =>      { (0x0001..=0xccaa).filter(|i| tls13.contains(i) == false && cipher_name(*i).is_some()).collect() }
+  }
+}
+
+// How much a weakness matters.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Severity {
+  High,
+  Medium,
+  Low,
+}
+
+impl Severity {
+  pub fn name(&self) -> String {
+    match self {
+      Severity::High =>   { String::from("high") }
+      Severity::Medium => { String::from("medium") }
+      Severity::Low =>    { String::from("low") }
+    }
+  }
+}
+
+impl Serialize for Severity {
+  fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+    serializer.serialize_str(self.name().as_str())
+  }
+}
+
+// A weakness found in the tls setup of a port.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct TlsFinding {
+  pub severity: Severity,
+  pub issue: String,
+}
+
+impl TlsFinding {
+  pub fn new(severity: Severity, issue: String) -> TlsFinding {
+    TlsFinding {
+      severity,
+      issue,
+    }
+  }
+}
+
+// A protocol version the port accepts and the cipher suites it accepts with it.
+#[derive(Debug, Clone, Default, PartialEq, Serialize)]
+pub struct ProtocolSupport {
+  pub version: String,
+  pub ciphers: Vec<String>,
+}
+
+// Every protocol version and cipher suite a port accepts and the weaknesses found in them.
+#[derive(Debug, Clone, Default, PartialEq, Serialize)]
+pub struct TlsAudit {
+  pub protocols: Vec<ProtocolSupport>,
+  pub findings: Vec<TlsFinding>,
+}
+
+// How a server answered one of our client hellos.
+#[derive(Debug, Clone, PartialEq)]
+pub enum HelloAnswer {
+  Accepted(u16, u16, Vec<Vec<u8>>),   // The protocol version, the cipher suite and the certificate chain when it was sent in the clear.
+  Rejected,                           // The server sent an alert or closed the connection.
+  NotTls,                             // The server did not answer with tls.
+}
+
+/**Function returns the reason a cipher suite is weak.
+ * Params:
+ *  name: &str {The name of the cipher suite}
+ * Returns Option<(Severity, &'static str)>
+ */
+pub fn cipher_weakness(name: &str) -> Option<(Severity, &'static str)> {
+  if name.contains("_NULL_") {
+    return Some((Severity::High, "no encryption"));
+  }
+
+  if name.contains("EXPORT") {
+    return Some((Severity::High, "export grade encryption"));
+  }
+
+  if name.contains("_anon_") {
+    return Some((Severity::High, "no server authentication"));
+  }
+
+  if name.contains("RC4") {
+    return Some((Severity::High, "RC4 cipher"));
+  }
+
+  if name.contains("_DES_") || name.contains("RC2") {
+    return Some((Severity::High, "56 bit or weaker encryption"));
+  }
+
+  if name.contains("3DES") || name.contains("IDEA") {
+    return Some((Severity::Medium, "64 bit block cipher"));
+  }
+
+  if name.contains("_CBC_") && name.ends_with("_SHA") {
+    return Some((Severity::Low, "CBC cipher with a SHA-1 MAC"));
+  }
+
+  None
+}
+
+/**Function builds a client hello that offers one protocol version and a list of cipher suites.
+ * Params:
+ *  version: u16          {The protocol version to offer}
+ *  ciphers: &[u16]       {The cipher suites to offer}
+ *  sni:     Option<&str> {The hostname to send, if the target has one}
+ * Returns Vec<u8> (The hello wrapped in a tls record)
+ */
+pub fn client_hello(version: u16, ciphers: &[u16], sni: Option<&str>) -> Vec<u8> {
+  let mut body: Vec<u8> = Default::default();
+
+  // Tls 1.3 is offered as tls 1.2 with the real version in an extension.
+  body.extend_from_slice(&version.min(0x0303).to_be_bytes());
+  body.extend_from_slice(&rand::random::<[u8; 32]>());
+
+  if version >= 0x0304 {
+    body.push(32);
+    body.extend_from_slice(&rand::random::<[u8; 32]>());
+  }
+
+  else {
+    body.push(0);
+  }
+
+  body.extend_from_slice(&((ciphers.len() * 2) as u16).to_be_bytes());
+  for i in ciphers {
+    body.extend_from_slice(&i.to_be_bytes());
+  }
+
+  // Only the null compression method is offered.
+  body.extend_from_slice(&[1, 0]);
+
+  if version >= 0x0301 {
+    let mut extensions: Vec<u8> = Default::default();
+
+    if let Some(name) = sni {
+      let name = name.as_bytes();
+      let mut list: Vec<u8> = vec![0];
+      list.extend_from_slice(&(name.len() as u16).to_be_bytes());
+      list.extend_from_slice(name);
+
+      let mut data = (list.len() as u16).to_be_bytes().to_vec();
+      data.extend_from_slice(&list);
+      push_extension(&mut extensions, 0x0000, &data);
+    }
+
+    // x25519, secp256r1, secp384r1 and secp521r1.
+    push_extension(&mut extensions, 0x000a, &[0x00, 0x08, 0x00, 0x1d, 0x00, 0x17, 0x00, 0x18, 0x00, 0x19]);
+    push_extension(&mut extensions, 0x000b, &[0x01, 0x00]);
+
+    // Ecdsa, rsa pss and rsa pkcs1 with sha-256, sha-384 and sha-512, then the sha-1 schemes older servers need.
+    push_extension(&mut extensions, 0x000d, &[
+      0x00, 0x16, 0x04, 0x03, 0x05, 0x03, 0x06, 0x03, 0x08, 0x04, 0x08, 0x05, 0x08, 0x06, 0x04, 0x01, 0x05, 0x01, 0x06, 0x01, 0x02, 0x03, 0x02, 0x01
+    ]);
+
+    push_extension(&mut extensions, 0xff01, &[0x00]);
+
+    if version >= 0x0304 {
+      push_extension(&mut extensions, 0x002b, &[0x02, 0x03, 0x04]);
+
+      // Any 32 bytes are a valid x25519 share. The handshake is never finished, so the private half is not needed.
+      let mut share: Vec<u8> = vec![0x00, 0x24, 0x00, 0x1d, 0x00, 0x20];
+      share.extend_from_slice(&rand::random::<[u8; 32]>());
+      push_extension(&mut extensions, 0x0033, &share);
+    }
+
+    body.extend_from_slice(&(extensions.len() as u16).to_be_bytes());
+    body.extend_from_slice(&extensions);
+  }
+
+  let mut handshake: Vec<u8> = vec![0x01];
+  handshake.extend_from_slice(&(body.len() as u32).to_be_bytes()[1..]);
+  handshake.extend_from_slice(&body);
+
+  // Servers that only speak sslv3 may reject a tls 1.0 record, every other version is sent in one.
+  let mut out: Vec<u8> = vec![0x16, 0x03, if version == 0x0300 { 0x00 } else { 0x01 }];
+  out.extend_from_slice(&(handshake.len() as u16).to_be_bytes());
+  out.extend_from_slice(&handshake);
+
+  out
+}
+
+// Appends an extension with its type and length to the extensions of a hello.
+fn push_extension(extensions: &mut Vec<u8>, kind: u16, data: &[u8]) -> () {
+  extensions.extend_from_slice(&kind.to_be_bytes());
+  extensions.extend_from_slice(&(data.len() as u16).to_be_bytes());
+  extensions.extend_from_slice(data);
+}
+
+/**Function reads the protocol version and cipher suite picked by the server from its server hello.
+ * Params:
+ *  body: &[u8] {The server hello without its handshake header}
+ * Returns Option<(u16, u16)>
+ */
+pub fn parse_server_hello(body: &[u8]) -> Option<(u16, u16)> {
+  let mut version = u16::from_be_bytes([*body.first()?, *body.get(1)?]);
+  let session_len = *body.get(34)? as usize;
+
+  let mut idx = 35 + session_len;
+  let cipher = u16::from_be_bytes([*body.get(idx)?, *body.get(idx + 1)?]);
+  idx += 3;
+
+  // Tls 1.3 servers name the real version in the supported versions extension.
+  if let (Some(a), Some(b)) = (body.get(idx), body.get(idx + 1)) {
+    let end = (idx + 2 + u16::from_be_bytes([*a, *b]) as usize).min(body.len());
+    idx += 2;
+
+    while idx + 4 <= end {
+      let kind = u16::from_be_bytes([body[idx], body[idx + 1]]);
+      let len = u16::from_be_bytes([body[idx + 2], body[idx + 3]]) as usize;
+
+      if kind == 0x002b && len == 2 && idx + 6 <= end {
+        version = u16::from_be_bytes([body[idx + 4], body[idx + 5]]);
+      }
+
+      idx += 4 + len;
+    }
+  }
+
+  Some((version, cipher))
+}
+
+/**Function reads the certificate chain from a certificate message.
+ * Params:
+ *  body: &[u8] {The certificate message without its handshake header}
+ * Returns Vec<Vec<u8>>
+ */
+pub fn parse_certificates(body: &[u8]) -> Vec<Vec<u8>> {
+  let mut out: Vec<Vec<u8>> = Default::default();
+  if body.len() < 3 {
+    return out;
+  }
+
+  let end = (3 + u24(&body[..3])).min(body.len());
+  let mut idx = 3;
+
+  while idx + 3 <= end {
+    let len = u24(&body[idx..idx + 3]);
+    if idx + 3 + len > end {
+      break;
+    }
+
+    out.push(body[idx + 3..idx + 3 + len].to_vec());
+    idx += 3 + len;
+  }
+
+  out
+}
+
+// Returns a 24 bit big endian length.
+fn u24(data: &[u8]) -> usize {
+  ((data[0] as usize) << 16) | ((data[1] as usize) << 8) | data[2] as usize
+}
+
+/**Function reads the answer to a client hello until the server hello arrives, or until the certificate when it is wanted.
+ * Params:
+ *  stream:    &mut TcpStream {The connection to the server}
+ *  want_cert: bool           {Keeps reading until the certificate message of servers older than tls 1.3}
+ * Returns HelloAnswer
+ */
+pub fn read_server_flight(stream: &mut TcpStream, want_cert: bool) -> HelloAnswer {
+  let mut handshake: Vec<u8> = Default::default();
+  let mut hello: Option<(u16, u16)> = None;
+
+  // Whatever arrived before the server stopped is enough once the server hello is here.
+  let finish = |hello: Option<(u16, u16)>, otherwise: HelloAnswer| {
+    match hello {
+      Some((version, cipher)) => { HelloAnswer::Accepted(version, cipher, Default::default()) },
+      None =>                    { otherwise }
+    }
+  };
+
+  loop {
+    let mut header = [0u8; 5];
+    match stream.read_exact(&mut header) {
+      Ok(_) => {},
+      Err(e) if e.kind() == ErrorKind::UnexpectedEof => { return finish(hello, HelloAnswer::Rejected); },
+      Err(_) => { return finish(hello, HelloAnswer::NotTls); }
+    }
+
+    let len = u16::from_be_bytes([header[3], header[4]]) as usize;
+    if (20..=23).contains(&header[0]) == false || header[1] != 0x03 || len > MAX_RECORD_LEN {
+      return finish(hello, HelloAnswer::NotTls);
+    }
+
+    let mut record = vec![0u8; len];
+    if stream.read_exact(&mut record).is_err() {
+      return finish(hello, HelloAnswer::Rejected);
+    }
+
+    match header[0] {
+      0x15 => { return finish(hello, HelloAnswer::Rejected); },
+      0x16 => { handshake.extend_from_slice(&record); },
+      _ =>    { return finish(hello, HelloAnswer::Rejected); }
+    }
+
+    // Handshake messages may be split across records or share one.
+    while handshake.len() >= 4 && handshake.len() >= 4 + u24(&handshake[1..4]) {
+      let message: Vec<u8> = handshake.drain(..4 + u24(&handshake[1..4])).collect();
+
+      match message[0] {
+        0x02 => {
+          hello = parse_server_hello(&message[4..]);
+
+          match hello {
+            Some((version, _)) if want_cert == false || version >= 0x0304 => { return finish(hello, HelloAnswer::Rejected); },
+            Some(_) => {},
+            None =>    { return HelloAnswer::NotTls; }
+          }
+        },
+
+        0x0b => {
+          if let Some((version, cipher)) = hello {
+            return HelloAnswer::Accepted(version, cipher, parse_certificates(&message[4..]));
+          }
+        },
+
+        0x0e => { return finish(hello, HelloAnswer::Rejected); },
+        _ =>    {}
+      }
+    }
+  }
+}
+
+/**Function sends one client hello and reads the answer.
+ * Params:
+ *  address:   &SocketAddr    {The address and port of the server}
+ *  hello:     &[u8]          {The client hello}
+ *  want_cert: bool           {Keeps reading until the certificate message of servers older than tls 1.3}
+ *  timeout:   Duration       {How long to wait for the connection and for each record}
+ *  binding:   &SourceBinding {The interface and address picked by the user}
+ * Returns Result<HelloAnswer, Error>
+ */
+pub fn send_hello(address: &SocketAddr, hello: &[u8], want_cert: bool, timeout: Duration, binding: &SourceBinding) -> Result<HelloAnswer, Error> {
+  let mut stream = binding.connect_timeout(address, timeout)?;
+  stream.set_read_timeout(Some(timeout.max(Duration::from_millis(1))))?;
+  stream.set_write_timeout(Some(timeout.max(Duration::from_millis(1))))?;
+  stream.write_all(hello)?;
+
+  Ok(read_server_flight(&mut stream, want_cert))
+}
+
+/**Function finds every protocol version and cipher suite a port accepts and flags the weak ones.
+ * Each cipher suite the server picks is left out of the next hello until the server refuses the rest.
+ * The certificate is taken from the tls report when there is one, otherwise from an older handshake that sent it in the clear.
+ * Params:
+ *  address: &SocketAddr        {The address and port of the server}
+ *  name:    &str               {The target as it was typed by the user}
+ *  report:  Option<&TlsReport> {What the tls handshake with the port told us, if it completed}
+ *  timeout: Duration           {How long to wait for the connection and for each record}
+ *  binding: &SourceBinding     {The interface and address picked by the user}
+ * Returns Option<TlsAudit> (None when the port does not speak tls)
+ */
+pub fn audit_tls(address: &SocketAddr, name: &str, report: Option<&TlsReport>, timeout: Duration, binding: &SourceBinding) -> Option<TlsAudit> {
+  let sni = match ServerName::try_from(name) {
+    Ok(ServerName::DnsName(_)) => { Some(name) },
+    _ =>                          { None }
+  };
+
+  let mut out = TlsAudit::default();
+  let mut certificates: Vec<Vec<u8>> = Default::default();
+  let mut speaks_tls = report.is_some();
+
+  for (version, version_name) in PROTOCOLS {
+    let mut remaining = protocol_ciphers(version);
+    let mut accepted: Vec<String> = Default::default();
+
+    while !remaining.is_empty() {
+      let want_cert = report.is_none() && certificates.is_empty();
+
+      let answer = match send_hello(address, &client_hello(version, &remaining, sni), want_cert, timeout, binding) {
+        Ok(s) => { s },
+        Err(_) => { break; }
+      };
+
+      match answer {
+        HelloAnswer::Accepted(v, cipher, chain) if v == version && remaining.contains(&cipher) == true => {
+          speaks_tls = true;
+          accepted.push(cipher_name(cipher).unwrap_or_default().to_string());
+          remaining.retain(|i| *i != cipher);
+
+          if !chain.is_empty() {
+            certificates = chain;
+          }
+        },
+
+        // The server answered with another version, so it does not accept this one.
+        HelloAnswer::Accepted(..) => { speaks_tls = true; break; },
+        HelloAnswer::Rejected =>     { speaks_tls = true; break; },
+        HelloAnswer::NotTls =>       { break; }
+      }
+    }
+
+    // Servers that do not answer the first hello with tls are not worth any more handshakes.
+    if speaks_tls == false {
+      return None;
+    }
+
+    if !accepted.is_empty() {
+      out.protocols.push(ProtocolSupport { version: version_name.to_string(), ciphers: accepted });
+    }
+  }
+
+  out.findings = protocol_findings(&out.protocols);
+
+  let leaf = match report.and_then(|i| i.certificates.first()) {
+    Some(s) => { Some(s.clone()) },
+    None =>    { certificates.first().map(|i| certificate_info(i)) }
+  };
+
+  // Certificates are rarely issued for an address, so the name is only checked for hostname targets.
+  if let Some(certificate) = leaf {
+    out.findings.extend(certificate_findings(&certificate, sni));
+  }
+
+  Some(out)
+}
+
+/**Function flags deprecated protocol versions and weak cipher suites.
+ * Params:
+ *  protocols: &Vec<ProtocolSupport> {Every protocol version the port accepts}
+ * Returns Vec<TlsFinding>
+ */
+pub fn protocol_findings(protocols: &Vec<ProtocolSupport>) -> Vec<TlsFinding> {
+  let mut out: Vec<TlsFinding> = Default::default();
+
+  for i in protocols {
+    match i.version.as_str() {
+      "SSLv3" =>             { out.push(TlsFinding::new(Severity::High, format!("{} is deprecated and accepted", i.version))); },
+      "TLSv1.0" | "TLSv1.1" => { out.push(TlsFinding::new(Severity::Medium, format!("{} is deprecated and accepted", i.version))); },
+      _ => {}
+    }
+  }
+
+  // Each weak cipher suite is reported once with every version that accepts it.
+  let mut seen: HashSet<&str> = Default::default();
+  for i in protocols.iter().flat_map(|p| p.ciphers.iter()) {
+    if seen.insert(i.as_str()) == false {
+      continue;
+    }
+
+    if let Some((severity, reason)) = cipher_weakness(i) {
+      let versions: Vec<&str> = protocols.iter().filter(|p| p.ciphers.contains(i)).map(|p| p.version.as_str()).collect();
+      out.push(TlsFinding::new(severity, format!("{} is accepted ({}) on {}", i, reason, versions.join(", "))));
+    }
+  }
+
+  out
+}
+
+/**Function flags certificates that are expired, not yet valid, self-signed or issued for another name.
+ * Params:
+ *  certificate: &CertificateInfo {The certificate of the server}
+ *  host:        Option<&str>     {The hostname the certificate should be valid for, nothing is checked when it is None}
+ * Returns Vec<TlsFinding>
+ */
+pub fn certificate_findings(certificate: &CertificateInfo, host: Option<&str>) -> Vec<TlsFinding> {
+  let mut out: Vec<TlsFinding> = Default::default();
+  let now = Utc::now();
+
+  if let Ok(not_after) = DateTime::parse_from_rfc3339(&certificate.not_after) {
+    if not_after < now {
+      out.push(TlsFinding::new(Severity::High, format!("The certificate expired on {}", certificate.not_after)));
+    }
+  }
+
+  if let Ok(not_before) = DateTime::parse_from_rfc3339(&certificate.not_before) {
+    if not_before > now {
+      out.push(TlsFinding::new(Severity::Medium, format!("The certificate is not valid until {}", certificate.not_before)));
+    }
+  }
+
+  if !certificate.subject.is_empty() && certificate.subject == certificate.issuer {
+    out.push(TlsFinding::new(Severity::Medium, String::from("The certificate is self-signed")));
+  }
+
+  if let Some(host) = host {
+    // The common name is only used when the certificate has no subject alternative names.
+    let mut names: Vec<String> = certificate.sans.clone();
+    if names.is_empty() {
+      names.extend(certificate.subject.split(", ").filter_map(|i| i.strip_prefix("CN=")).map(|i| i.to_string()));
+    }
+
+    if names.iter().any(|i| name_matches(i, host)) == false {
+      out.push(TlsFinding::new(Severity::Medium, format!("The certificate is not valid for {}", host)));
+    }
+  }
+
+  out
+}
+
+// Returns true when a name from a certificate covers the host. A wildcard only covers one label.
+pub fn name_matches(pattern: &str, host: &str) -> bool {
+  let (pattern, host) = (pattern.to_lowercase(), host.to_lowercase());
+  if pattern == host {
+    return true;
+  }
+
+  match (pattern.strip_prefix("*."), host.split_once('.')) {
+    (Some(parent), Some((_, host_parent))) => { parent == host_parent },
+    _ =>                                       { false }
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use std::net::TcpListener;
+  use std::thread;
+
+  // Builds a server hello body with a 32 byte session id and the given extensions.
+  fn server_hello(version: u16, cipher: u16, extensions: Option<&[u8]>) -> Vec<u8> {
+    let mut out = version.to_be_bytes().to_vec();
+    out.extend_from_slice(&[0xaa; 32]);
+    out.push(32);
+    out.extend_from_slice(&[0xbb; 32]);
+    out.extend_from_slice(&cipher.to_be_bytes());
+    out.push(0);
+
+    if let Some(data) = extensions {
+      out.extend_from_slice(&(data.len() as u16).to_be_bytes());
+      out.extend_from_slice(data);
+    }
+
+    out
+  }
+
+  // Wraps a body in a handshake header.
+  fn handshake(kind: u8, body: &[u8]) -> Vec<u8> {
+    let mut out = vec![kind];
+    out.extend_from_slice(&(body.len() as u32).to_be_bytes()[1..]);
+    out.extend_from_slice(body);
+    out
+  }
+
+  // Wraps data in a tls 1.2 record.
+  fn record(kind: u8, data: &[u8]) -> Vec<u8> {
+    let mut out = vec![kind, 0x03, 0x03];
+    out.extend_from_slice(&(data.len() as u16).to_be_bytes());
+    out.extend_from_slice(data);
+    out
+  }
+
+  // Builds a certificate message body holding each certificate.
+  fn certificate_message(certs: &[&[u8]]) -> Vec<u8> {
+    let mut list: Vec<u8> = Default::default();
+    for i in certs {
+      list.extend_from_slice(&(i.len() as u32).to_be_bytes()[1..]);
+      list.extend_from_slice(i);
+    }
+
+    let mut out = (list.len() as u32).to_be_bytes()[1..].to_vec();
+    out.extend(list);
+    out
+  }
+
+  // Sends the bytes from a local server and reads them back with read_server_flight.
+  fn read_flight(data: Vec<u8>, want_cert: bool) -> HelloAnswer {
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let address = listener.local_addr().unwrap();
+
+    let server = thread::spawn(move || {
+      let (mut stream, _) = listener.accept().unwrap();
+      stream.write_all(&data).unwrap();
+    });
+
+    let mut stream = TcpStream::connect(address).unwrap();
+    stream.set_read_timeout(Some(Duration::from_secs(5))).unwrap();
+    let answer = read_server_flight(&mut stream, want_cert);
+
+    server.join().unwrap();
+    answer
+  }
+
+  // The type and data of each extension in a hello.
+  type Extensions = Vec<(u16, Vec<u8>)>;
+
+  // Returns the session id and the extensions of a client hello record.
+  fn hello_parts(hello: &[u8]) -> (Vec<u8>, Option<Extensions>) {
+    let body = &hello[9..];
+    let session_len = body[34] as usize;
+    let session = body[35..35 + session_len].to_vec();
+
+    let mut idx = 35 + session_len;
+    idx += 2 + u16::from_be_bytes([body[idx], body[idx + 1]]) as usize;
+    idx += 1 + body[idx] as usize;
+
+    if idx == body.len() {
+      return (session, None);
+    }
+
+    let end = idx + 2 + u16::from_be_bytes([body[idx], body[idx + 1]]) as usize;
+    assert_eq!(end, body.len());
+    idx += 2;
+
+    let mut extensions: Extensions = Default::default();
+    while idx < end {
+      let kind = u16::from_be_bytes([body[idx], body[idx + 1]]);
+      let len = u16::from_be_bytes([body[idx + 2], body[idx + 3]]) as usize;
+      extensions.push((kind, body[idx + 4..idx + 4 + len].to_vec()));
+      idx += 4 + len;
+    }
+
+    (session, Some(extensions))
+  }
+
+  #[test]
+  fn parse_server_hello_tls12() {
+    let body = server_hello(0x0303, 0xc02f, Some(&[0xff, 0x01, 0x00, 0x01, 0x00]));
+    assert_eq!(parse_server_hello(&body), Some((0x0303, 0xc02f)));
+
+    let body = server_hello(0x0301, 0x002f, None);
+    assert_eq!(parse_server_hello(&body), Some((0x0301, 0x002f)));
+  }
+
+  #[test]
+  fn parse_server_hello_tls13_supported_versions() {
+    let mut extensions = vec![0x00, 0x33, 0x00, 0x24, 0x00, 0x1d, 0x00, 0x20];
+    extensions.extend_from_slice(&[0x11; 32]);
+    extensions.extend_from_slice(&[0x00, 0x2b, 0x00, 0x02, 0x03, 0x04]);
+
+    let body = server_hello(0x0303, 0x1301, Some(&extensions));
+    assert_eq!(parse_server_hello(&body), Some((0x0304, 0x1301)));
+  }
+
+  #[test]
+  fn parse_server_hello_truncated() {
+    let body = server_hello(0x0303, 0xc02f, None);
+
+    assert_eq!(parse_server_hello(&body[..67]), None);
+    assert_eq!(parse_server_hello(&body[..68]), None);
+    assert_eq!(parse_server_hello(&body[..30]), None);
+    assert_eq!(parse_server_hello(&[]), None);
+  }
+
+  #[test]
+  fn parse_certificates_outer_length_too_large() {
+    let mut body = certificate_message(&[b"first", b"second"]);
+    body[..3].copy_from_slice(&[0x00, 0xff, 0xff]);
+    assert_eq!(parse_certificates(&body), vec![b"first".to_vec(), b"second".to_vec()]);
+
+    // The second certificate claims more bytes than arrived.
+    body.truncate(body.len() - 2);
+    assert_eq!(parse_certificates(&body), vec![b"first".to_vec()]);
+
+    assert!(parse_certificates(&[0x00, 0x00]).is_empty());
+  }
+
+  #[test]
+  fn read_server_flight_joins_split_records() {
+    let hello = handshake(0x02, &server_hello(0x0303, 0xc02f, None));
+    let certificate = handshake(0x0b, &certificate_message(&[b"leaf", b"issuer"]));
+
+    // The server hello is split across two records and the second one also holds the certificate.
+    let mut second = hello[20..].to_vec();
+    second.extend_from_slice(&certificate);
+
+    let mut data = record(0x16, &hello[..20]);
+    data.extend(record(0x16, &second));
+
+    assert_eq!(read_flight(data, true), HelloAnswer::Accepted(0x0303, 0xc02f, vec![b"leaf".to_vec(), b"issuer".to_vec()]));
+  }
+
+  #[test]
+  fn read_server_flight_two_messages_in_one_record() {
+    let mut messages = handshake(0x02, &server_hello(0x0303, 0x009c, None));
+    messages.extend(handshake(0x0b, &certificate_message(&[b"leaf"])));
+
+    assert_eq!(read_flight(record(0x16, &messages), true), HelloAnswer::Accepted(0x0303, 0x009c, vec![b"leaf".to_vec()]));
+    assert_eq!(read_flight(record(0x16, &messages), false), HelloAnswer::Accepted(0x0303, 0x009c, vec![]));
+  }
+
+  #[test]
+  fn read_server_flight_alert_and_garbage() {
+    assert_eq!(read_flight(record(0x15, &[0x02, 0x28]), true), HelloAnswer::Rejected);
+    assert_eq!(read_flight(b"SSH-2.0-OpenSSH_9.6\r\n".to_vec(), true), HelloAnswer::NotTls);
+  }
+
+  #[test]
+  fn client_hello_sslv3_layout() {
+    let hello = client_hello(0x0300, &[0x000a, 0x002f], Some("example.com"));
+
+    assert_eq!(&hello[..3], &[0x16, 0x03, 0x00]);
+    assert_eq!(u16::from_be_bytes([hello[3], hello[4]]) as usize, hello.len() - 5);
+    assert_eq!(hello[5], 0x01);
+    assert_eq!(u24(&hello[6..9]), hello.len() - 9);
+    assert_eq!(&hello[9..11], &[0x03, 0x00]);
+
+    let (session, extensions) = hello_parts(&hello);
+    assert!(session.is_empty());
+    assert!(extensions.is_none());
+  }
+
+  #[test]
+  fn client_hello_tls12_layout() {
+    let hello = client_hello(0x0303, &[0xc02f], Some("example.com"));
+
+    assert_eq!(&hello[..3], &[0x16, 0x03, 0x01]);
+    assert_eq!(&hello[9..11], &[0x03, 0x03]);
+
+    let (session, extensions) = hello_parts(&hello);
+    let extensions = extensions.unwrap();
+    assert!(session.is_empty());
+    assert!(extensions.iter().any(|i| i.0 == 0x0000 && i.1.ends_with(b"example.com")));
+    assert!(extensions.iter().all(|i| i.0 != 0x002b && i.0 != 0x0033));
+
+    let (_, extensions) = hello_parts(&client_hello(0x0301, &[0x002f], None));
+    assert!(extensions.unwrap().iter().all(|i| i.0 != 0x0000));
+  }
+
+  #[test]
+  fn client_hello_tls13_layout() {
+    let hello = client_hello(0x0304, &[0x1301, 0x1302], None);
+
+    assert_eq!(&hello[..3], &[0x16, 0x03, 0x01]);
+    assert_eq!(&hello[9..11], &[0x03, 0x03]);
+
+    let (session, extensions) = hello_parts(&hello);
+    let extensions = extensions.unwrap();
+    assert_eq!(session.len(), 32);
+    assert!(extensions.contains(&(0x002b, vec![0x02, 0x03, 0x04])));
+
+    let share = extensions.iter().find(|i| i.0 == 0x0033).unwrap();
+    assert_eq!(&share.1[..6], &[0x00, 0x24, 0x00, 0x1d, 0x00, 0x20]);
+    assert_eq!(share.1.len(), 38);
+  }
+
+  fn certificate(subject: &str, sans: &[&str]) -> CertificateInfo {
+    CertificateInfo {
+      subject: subject.to_string(),
+      sans: sans.iter().map(|i| i.to_string()).collect(),
+      issuer: String::from("CN=Example CA"),
+      not_before: String::from("2020-01-01T00:00:00Z"),
+      not_after: String::from("2999-01-01T00:00:00Z"),
+      ..Default::default()
+    }
+  }
+
+  #[test]
+  fn certificate_findings_checks_hostnames() {
+    let cert = certificate("CN=www.example.com", &["www.example.com", "*.example.org"]);
+
+    assert!(certificate_findings(&cert, Some("www.example.com")).is_empty());
+    assert!(certificate_findings(&cert, Some("mail.example.org")).is_empty());
+    assert_eq!(certificate_findings(&cert, Some("a.b.example.org")).len(), 1);
+    assert!(certificate_findings(&certificate("CN=example.net", &[]), Some("example.net")).is_empty());
+  }
+
+  #[test]
+  fn certificate_findings_skips_name_for_addresses() {
+    let cert = certificate("CN=www.example.com", &["www.example.com"]);
+    assert!(certificate_findings(&cert, None).is_empty());
+
+    let self_signed = certificate("CN=Example CA", &[]);
+    assert_eq!(certificate_findings(&self_signed, None), vec![TlsFinding::new(Severity::Medium, String::from("The certificate is self-signed"))]);
+  }
+}
@@ -0,0 +1,419 @@
+use std::collections::HashMap;
+use std::io::{Error, ErrorKind};
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr};
+use std::sync::{Arc, Mutex, atomic::{AtomicBool, Ordering}};
+use std::thread;
+use std::time::{Duration, Instant};
+use crossbeam::channel::unbounded;
+use pnet_packet::Packet;
+use pnet_packet::ip::IpNextHeaderProtocols;
+use pnet_transport::{transport_channel, TransportChannelType, TransportProtocol, TransportReceiver};
+use super::{PortState, RateLimiter, RttTable, SourceBinding};
+
+const MAX_UDP_THREADS: usize = 256;      // Udp probes block until they time out, so they run on a limited number of threads.
+
+/**Function returns a datagram that should make the service on the port answer.
+ * Ports without a known service get an empty datagram.
+ * Params:
+ *  port: u16 {The port number}
+ * Returns Vec<u8> (The payload)
+ */
+pub fn udp_payload(port: u16) -> Vec<u8> {
+  match port {
+    // DNS status request for version.bind.
+    53 => {
+      b"\x00\x06\x01\x00\x00\x01\x00\x00\x00\x00\x00\x00\x07version\x04bind\x00\x00\x10\x00\x03".to_vec()
+    }
+
+    // TFTP read request for a file that should not exist.
+    69 => {
+      b"\x00\x01r7tftp.txt\x00octet\x00".to_vec()
+    }
+
+    // Portmapper null procedure call.
+    111 => {
+      b"\x72\xfe\x1d\x13\x00\x00\x00\x00\x00\x00\x00\x02\x00\x01\x86\xa0\x00\x00\x00\x02\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00".to_vec()
+    }
+
+    // NTP version 4 client request.
+    123 => {
+      let mut out = vec![0u8; 48];
+      out[0] = 0xe3;
+      out
+    }
+
+    // NetBIOS node status request for the wildcard name.
+    137 => {
+      b"\x80\xf0\x00\x00\x00\x01\x00\x00\x00\x00\x00\x00\x20CKAAAAAAAAAAAAAAAAAAAAAAAAAAAAAA\x00\x00\x21\x00\x01".to_vec()
+    }
+
+    // SNMP v1 get request for sysDescr using the public community.
+    161 => {
+      b"\x30\x29\x02\x01\x00\x04\x06public\xa0\x1c\x02\x04\x71\xb4\xc8\xa1\x02\x01\x00\x02\x01\x00\x30\x0e\x30\x0c\x06\x08\x2b\x06\x01\x02\x01\x01\x01\x00\x05\x00".to_vec()
+    }
+
+    // Microsoft SQL Server browser request.
+    1434 => {
+      b"\x02".to_vec()
+    }
+
+    // SSDP discovery request.
+    1900 => {
+      b"M-SEARCH * HTTP/1.1\r\nHOST: 239.255.255.250:1900\r\nMAN: \"ssdp:discover\"\r\nMX: 1\r\nST: ssdp:all\r\n\r\n".to_vec()
+    }
+
+    // mDNS query for every advertised service.
+    5353 => {
+      b"\x00\x00\x00\x00\x00\x01\x00\x00\x00\x00\x00\x00\x09_services\x07_dns-sd\x04_udp\x05local\x00\x00\x0c\x00\x01".to_vec()
+    }
+
+    // Memcached stats request.
+    11211 => {
+      b"\x00\x01\x00\x00\x00\x01\x00\x00stats\r\n".to_vec()
+    }
+
+    _ => { Default::default() }
+  }
+}
+
+/**Function opens a raw socket that receives icmp messages for one ip version.
+ * Params:
+ *  is_v6: bool {Opens an icmpv6 socket when true}
+ * Returns Result<TransportReceiver, Error>
+ */
+pub fn open_icmp_channel(is_v6: bool) -> Result<TransportReceiver, Error> {
+  let channel_type = match is_v6 {
+    false => { TransportChannelType::Layer4(TransportProtocol::Ipv4(IpNextHeaderProtocols::Icmp)) }
+    true =>  { TransportChannelType::Layer4(TransportProtocol::Ipv6(IpNextHeaderProtocols::Icmpv6)) }
+  };
+
+  let (_, rx) = transport_channel(65535, channel_type)?;
+  Ok(rx)
+}
+
+/**Function reads the target of the udp datagram that caused an icmp unreachable message.
+ * The message quotes the ip header and the first 8 bytes of our datagram after 4 unused bytes.
+ * Params:
+ *  payload: &[u8] {The icmp payload after the type, code and checksum}
+ *  is_v6:   bool  {True when the payload quotes an ipv6 header}
+ * Returns Option<(IpAddr, u16)> (The address and port the datagram was sent to)
+ */
+pub fn quoted_udp_target(payload: &[u8], is_v6: bool) -> Option<(IpAddr, u16)> {
+  let quoted = payload.get(4..)?;
+
+  if is_v6 == false {
+    let header_len = (*quoted.first()? & 0x0f) as usize * 4;
+    if *quoted.get(9)? != 17 {
+      return None;
+    }
+
+    let dst: [u8; 4] = quoted.get(16..20)?.try_into().ok()?;
+    let udp = quoted.get(header_len..header_len + 4)?;
+    return Some((IpAddr::V4(Ipv4Addr::from(dst)), u16::from_be_bytes([udp[2], udp[3]])));
+  }
+
+  if *quoted.get(6)? != 17 {
+    return None;
+  }
+
+  let dst: [u8; 16] = quoted.get(24..40)?.try_into().ok()?;
+  let udp = quoted.get(40..44)?;
+  Some((IpAddr::V6(Ipv6Addr::from(dst)), u16::from_be_bytes([udp[2], udp[3]])))
+}
+
+/**Function returns the state for an icmp unreachable message, port unreachable means the port is closed.
+ * Params:
+ *  icmp_type: u8   {The icmp type}
+ *  code:      u8   {The icmp code}
+ *  is_v6:     bool {True when the message is icmpv6}
+ * Returns Option<PortState> (None when the message is not an unreachable message)
+ */
+pub fn unreachable_state(icmp_type: u8, code: u8, is_v6: bool) -> Option<PortState> {
+  match (is_v6, icmp_type, code) {
+    (false, 3, 3) | (true, 1, 4) =>   { Some(PortState::Closed) }
+    (false, 3, _) | (true, 1, _) =>   { Some(PortState::Filtered) }
+    _ =>                              { None }
+  }
+}
+
+/**Function records icmp unreachable messages until the scan stops running.
+ * Params:
+ *  receiver: TransportReceiver                           {The raw icmp socket}
+ *  is_v6:    bool                                        {True when the socket receives icmpv6}
+ *  replies:  Arc<Mutex<HashMap<(IpAddr, u16), PortState>>> {The state of every target that was reported unreachable}
+ *  running:  Arc<AtomicBool>                             {Set to false when the scan is done}
+ * Returns nothing.
+ */
+#[cfg(unix)]
+pub fn receive_icmp(mut receiver: TransportReceiver, is_v6: bool, replies: Arc<Mutex<HashMap<(IpAddr, u16), PortState>>>, running: Arc<AtomicBool>) -> () {
+  let poll_interval = Duration::from_millis(50);
+
+  let record = |icmp_type: u8, code: u8, payload: &[u8]| -> () {
+    if let (Some(state), Some(target)) = (unreachable_state(icmp_type, code, is_v6), quoted_udp_target(payload, is_v6)) {
+      if let Ok(mut r) = replies.lock() {
+        r.insert(target, state);
+      }
+    }
+  };
+
+  if is_v6 == true {
+    let mut iter = pnet_transport::icmpv6_packet_iter(&mut receiver);
+
+    while running.load(Ordering::Relaxed) == true {
+      if let Ok(Some((packet, _))) = iter.next_with_timeout(poll_interval) {
+        record(packet.get_icmpv6_type().0, packet.get_icmpv6_code().0, packet.payload());
+      }
+    }
+  }
+
+  else {
+    let mut iter = pnet_transport::icmp_packet_iter(&mut receiver);
+
+    while running.load(Ordering::Relaxed) == true {
+      if let Ok(Some((packet, _))) = iter.next_with_timeout(poll_interval) {
+        record(packet.get_icmp_type().0, packet.get_icmp_code().0, packet.payload());
+      }
+    }
+  }
+}
+
+#[cfg(not(unix))]
+pub fn receive_icmp(_receiver: TransportReceiver, _is_v6: bool, _replies: Arc<Mutex<HashMap<(IpAddr, u16), PortState>>>, _running: Arc<AtomicBool>) -> () {}
+
+/**Function sends a single probe from its own connected socket and waits for an answer.
+ * A connected socket also reports icmp port unreachable messages as a refused connection, which
+ * lets us find closed ports without raw socket access.
+ * Params:
+ *  target:  &SocketAddr    {The address and port to probe}
+ *  timeout: Duration       {The time to wait for an answer}
+ *  binding: &SourceBinding {The interface and address picked by the user}
+ * Returns (PortState, Vec<u8>) (The state of the port and the answer if there was one)
+ */
+pub fn udp_probe(target: &SocketAddr, timeout: Duration, binding: &SourceBinding) -> (PortState, Vec<u8>) {
+  let socket = match binding.udp_socket(target) {
+    Ok(s) => { s },
+    Err(_) => { return (PortState::OpenFiltered, Default::default()); }
+  };
+
+  if socket.connect(target).is_err() || socket.set_read_timeout(Some(timeout.max(Duration::from_millis(1)))).is_err() {
+    return (PortState::OpenFiltered, Default::default());
+  }
+
+  if socket.send(udp_payload(target.port()).as_slice()).is_err() {
+    return (PortState::OpenFiltered, Default::default());
+  }
+
+  let mut buffer = [0u8; 4096];
+  match socket.recv(&mut buffer) {
+    Ok(size) => { (PortState::Open, buffer[..size].to_vec()) },
+    Err(e) => {
+      match e.kind() {
+        ErrorKind::ConnectionRefused | ErrorKind::ConnectionReset => { (PortState::Closed, Default::default()) }
+        _ =>                                                         { (PortState::OpenFiltered, Default::default()) }
+      }
+    }
+  }
+}
+
+/**Function scans every target with udp datagrams and returns the state of each port.
+ * Icmp unreachable messages are read through raw sockets when the process is allowed to open them.
+ * Params:
+ *  targets: &Vec<SocketAddr>   {Every address and port to scan}
+ *  rtt:     Arc<RttTable>      {Measures each host and decides how long to wait for an answer to each probe}
+ *  threads: u32                {The number of probes in flight at once, up to MAX_UDP_THREADS}
+ *  debug:   bool               {Shows debug messages}
+ *  limiter: Arc<RateLimiter>   {Paces the probes of every thread}
+ *  binding: Arc<SourceBinding> {The interface and address picked by the user}
+ * Returns HashMap<SocketAddr, (PortState, Vec<u8>)>
+ */
+pub fn udp_scan(targets: &Vec<SocketAddr>, rtt: Arc<RttTable>, threads: u32, debug: bool, limiter: Arc<RateLimiter>, binding: Arc<SourceBinding>) -> HashMap<SocketAddr, (PortState, Vec<u8>)> {
+  let unreachable: Arc<Mutex<HashMap<(IpAddr, u16), PortState>>> = Arc::new(Mutex::new(Default::default()));
+  let running = Arc::new(AtomicBool::new(true));
+  let mut icmp_handles: Vec<thread::JoinHandle<()>> = Default::default();
+
+  for is_v6 in [false, true] {
+    if targets.iter().any(|i| i.is_ipv6() == is_v6) == false {
+      continue;
+    }
+
+    match open_icmp_channel(is_v6) {
+      Ok(rx) => {
+        let th_unreachable = unreachable.clone();
+        let th_running = running.clone();
+
+        icmp_handles.push(thread::spawn(move || {
+          receive_icmp(rx, is_v6, th_unreachable, th_running);
+        }));
+      },
+
+      Err(e) => {
+        if debug == true {
+          super::fmt::f_debug("Unable to listen for icmp messages, closed ports are read from socket errors", format!("{}", e).as_str());
+        }
+      }
+    }
+  }
+
+  // Probes are handed out to the workers through a queue.
+  let (job_sender, job_recv) = unbounded::<SocketAddr>();
+  let (result_sender, result_recv) = unbounded::<(SocketAddr, PortState, Vec<u8>)>();
+
+  for i in targets {
+    let _ = job_sender.send(*i);
+  }
+
+  drop(job_sender);
+
+  // Workers past the usual count only probe while the scan is behind the minimum rate.
+  let workers = (threads.max(1) as usize).min(MAX_UDP_THREADS).min(targets.len());
+  let mut handles: Vec<thread::JoinHandle<()>> = Default::default();
+
+  for idx in 0..(workers * limiter.burst()).min(targets.len()) {
+    let extra = idx >= workers;
+    let th_jobs = job_recv.clone();
+    let th_results = result_sender.clone();
+    let th_limiter = limiter.clone();
+    let th_rtt = rtt.clone();
+    let th_binding = binding.clone();
+
+    handles.push(thread::spawn(move || {
+      while let Some(target) = th_limiter.next_job(&th_jobs, extra) {
+        th_limiter.wait();
+
+        let started = Instant::now();
+        let (state, data) = udp_probe(&target, th_rtt.timeout(&target.ip()), &th_binding);
+
+        // Answers and port unreachable messages both tell us how far away the host is.
+        if state == PortState::Open || state == PortState::Closed {
+          th_rtt.sample(target.ip(), started.elapsed());
+        }
+
+        let _ = th_results.send((target, state, data));
+      }
+    }));
+  }
+
+  drop(result_sender);
+
+  let mut out: HashMap<SocketAddr, (PortState, Vec<u8>)> = Default::default();
+  while let Ok((target, state, data)) = result_recv.recv() {
+    out.insert(target, (state, data));
+  }
+
+  for i in handles {
+    let _ = i.join();
+  }
+
+  // Late icmp messages are given a short moment to arrive before the listeners stop.
+  if !icmp_handles.is_empty() {
+    thread::sleep(Duration::from_millis(100));
+  }
+
+  running.store(false, Ordering::Relaxed);
+  for i in icmp_handles {
+    let _ = i.join();
+  }
+
+  // Unreachable messages replace the guess for every port that did not answer.
+  if let Ok(r) = unreachable.lock() {
+    for (target, result) in out.iter_mut() {
+      if result.0 == PortState::OpenFiltered {
+        if let Some(state) = r.get(&(target.ip(), target.port())) {
+          result.0 = *state;
+        }
+      }
+    }
+  }
+
+  out
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  // Builds the payload of an icmpv4 unreachable message quoting a datagram sent to the address and port.
+  fn quoted_v4(target: Ipv4Addr, port: u16, options: &[u8], protocol: u8) -> Vec<u8> {
+    let mut header = vec![0x40 | ((20 + options.len()) / 4) as u8, 0, 0, 0, 0, 0, 0, 0, 64, protocol, 0, 0, 192, 0, 2, 1];
+    header.extend_from_slice(&target.octets());
+    header.extend_from_slice(options);
+
+    let mut out = vec![0u8; 4];
+    out.extend(header);
+    out.extend_from_slice(&40000u16.to_be_bytes());
+    out.extend_from_slice(&port.to_be_bytes());
+    out.extend_from_slice(&[0, 8, 0, 0]);
+    out
+  }
+
+  // Builds the payload of an icmpv6 unreachable message quoting a datagram sent to the address and port.
+  fn quoted_v6(target: Ipv6Addr, port: u16, next_header: u8) -> Vec<u8> {
+    let mut out = vec![0u8; 4];
+    out.extend_from_slice(&[0x60, 0, 0, 0, 0, 8, next_header, 64]);
+    out.extend_from_slice(&"2001:db8::1".parse::<Ipv6Addr>().unwrap().octets());
+    out.extend_from_slice(&target.octets());
+    out.extend_from_slice(&40000u16.to_be_bytes());
+    out.extend_from_slice(&port.to_be_bytes());
+    out.extend_from_slice(&[0, 8, 0, 0]);
+    out
+  }
+
+  #[test]
+  fn quoted_udp_target_v4_port_unreachable() {
+    let target = Ipv4Addr::new(192, 0, 2, 10);
+    let payload = quoted_v4(target, 161, &[], 17);
+
+    assert_eq!(unreachable_state(3, 3, false), Some(PortState::Closed));
+    assert_eq!(unreachable_state(3, 13, false), Some(PortState::Filtered));
+    assert_eq!(quoted_udp_target(&payload, false), Some((IpAddr::V4(target), 161)));
+  }
+
+  #[test]
+  fn quoted_udp_target_v4_with_options() {
+    let target = Ipv4Addr::new(192, 0, 2, 10);
+    let payload = quoted_v4(target, 53, &[0x01, 0x01, 0x01, 0x00], 17);
+
+    assert_eq!(payload[4] & 0x0f, 6);
+    assert_eq!(quoted_udp_target(&payload, false), Some((IpAddr::V4(target), 53)));
+  }
+
+  #[test]
+  fn quoted_udp_target_v6_port_unreachable() {
+    let target: Ipv6Addr = "2001:db8::10".parse().unwrap();
+    let payload = quoted_v6(target, 123, 17);
+
+    assert_eq!(unreachable_state(1, 4, true), Some(PortState::Closed));
+    assert_eq!(unreachable_state(1, 1, true), Some(PortState::Filtered));
+    assert_eq!(unreachable_state(3, 3, true), None);
+    assert_eq!(quoted_udp_target(&payload, true), Some((IpAddr::V6(target), 123)));
+  }
+
+  #[test]
+  fn quoted_udp_target_not_udp() {
+    assert_eq!(quoted_udp_target(&quoted_v4(Ipv4Addr::new(192, 0, 2, 10), 80, &[], 6), false), None);
+    assert_eq!(quoted_udp_target(&quoted_v6("2001:db8::10".parse().unwrap(), 80, 6), true), None);
+  }
+
+  #[test]
+  fn quoted_udp_target_truncated() {
+    let payload = quoted_v4(Ipv4Addr::new(192, 0, 2, 10), 161, &[0x01, 0x01, 0x01, 0x00], 17);
+    assert_eq!(quoted_udp_target(&payload[..payload.len() - 6], false), None);
+    assert_eq!(quoted_udp_target(&payload[..18], false), None);
+    assert_eq!(quoted_udp_target(&payload[..4], false), None);
+
+    let payload = quoted_v6("2001:db8::10".parse().unwrap(), 123, 17);
+    assert_eq!(quoted_udp_target(&payload[..46], true), None);
+    assert_eq!(quoted_udp_target(&payload[..20], true), None);
+    assert_eq!(quoted_udp_target(&[], true), None);
+  }
+
+  #[test]
+  fn udp_payload_by_port() {
+    assert!(udp_payload(53).windows(7).any(|i| i == b"version"));
+    assert_eq!(udp_payload(123).len(), 48);
+    assert_eq!(udp_payload(123)[0], 0xe3);
+    assert!(udp_payload(161).windows(6).any(|i| i == b"public"));
+    assert!(udp_payload(9999).is_empty());
+    assert!(udp_payload(80).is_empty());
+  }
+}